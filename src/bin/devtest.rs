@@ -57,10 +57,14 @@ fn test_data_spec() -> ApiSpec {
                 EnumVariant {
                     name: "Foo".into(),
                     data: EnumVariantData::None,
+                    doc: None,
+                    discriminant: None,
                 },
                 EnumVariant {
                     name: "Bar".into(),
                     data: EnumVariantData::Single(("bool".into(), "Bool".into())),
+                    doc: None,
+                    discriminant: None,
                 },
                 EnumVariant {
                     name: "Qux".into(),
@@ -68,14 +72,22 @@ fn test_data_spec() -> ApiSpec {
                         StructField {
                             name: "sub1".into(),
                             data: ("u32".into(), "Int".into()),
+                            doc: None,
                         },
                         StructField {
                             name: "sub2".into(),
                             data: ("String".into(), "String".into()),
+                            doc: None,
                         },
                     ]),
+                    doc: None,
+                    discriminant: None,
                 },
             ],
+            doc: None,
+            targets: None,
+            unknown_fallback: false,
         }],
+        ..Default::default()
     }
 }
@@ -1,8 +1,29 @@
 use polymorphio::{FileOrStdin, FileOrStdout};
-use rust_elm_types::ApiSpec;
-use std::{error::Error, path::PathBuf, process::exit};
+use rust_elm_types::{matches_pattern, ApiSpec, Diagnostic, Severity, SpecFormat};
+use std::{
+    error::Error,
+    io::{self, Write},
+    path::PathBuf,
+    process::{exit, Command, Stdio},
+    thread,
+};
 use structopt::StructOpt;
 
+#[derive(Debug, StructOpt)]
+enum Cmd {
+    /// Compare two versions of a spec and report changes that break old clients or servers
+    Diff {
+        #[structopt(parse(from_os_str), help = "Old spec file")]
+        old: PathBuf,
+
+        #[structopt(parse(from_os_str), help = "New spec file")]
+        new: PathBuf,
+
+        #[structopt(long, help = "Print the changes as JSON")]
+        json: bool,
+    },
+}
+
 #[derive(Debug, StructOpt)]
 struct Opt {
     #[structopt(short, long, help = "Silence all log messages")]
@@ -31,19 +52,257 @@ struct Opt {
         help = "Output Rust File"
     )]
     rust: PathBuf,
+
+    #[structopt(long, help = "Don't run the Rust output through rustfmt")]
+    no_rustfmt: bool,
+
+    #[structopt(
+        long,
+        number_of_values = 1,
+        help = "Only generate types matching this pattern, and the types they depend on. \
+                `*` and `?` are wildcards. Endpoints, channels, ports and upgrades that use other \
+                types are left out. (repeatable)"
+    )]
+    include: Vec<String>,
+
+    #[structopt(
+        long,
+        number_of_values = 1,
+        help = "Don't generate types matching this pattern, or the endpoints, channels, ports and \
+                upgrades that use them (repeatable)"
+    )]
+    exclude: Vec<String>,
+
+    #[structopt(long, help = "Put every type after the types it depends on")]
+    topological: bool,
+
+    #[structopt(
+        long,
+        parse(from_os_str),
+        help = "Write the type dependency graph in Graphviz DOT format"
+    )]
+    graph: Option<PathBuf>,
+
+    #[structopt(
+        long,
+        parse(from_os_str),
+        help = "Write TypeScript declarations for the Elm ports"
+    )]
+    dts: Option<PathBuf>,
+
+    #[structopt(subcommand)]
+    cmd: Option<Cmd>,
+}
+
+/// Formats Rust code with `rustfmt`, which must be on the `PATH`.
+fn rustfmt(code: String) -> io::Result<String> {
+    let mut child = Command::new("rustfmt")
+        .args(["--edition", "2018"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = thread::spawn(move || stdin.write_all(code.as_bytes()));
+
+    let output = child.wait_with_output()?;
+    writer.join().expect("rustfmt writer panicked")?;
+
+    if output.status.success() {
+        String::from_utf8(output.stdout).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    } else {
+        Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ))
+    }
+}
+
+/// A spec file, as read.
+struct SpecFile {
+    name: String,
+    source: String,
+}
+
+impl SpecFile {
+    /// Prints `diagnostic` with the part of the file it points at.
+    fn report(&self, diagnostic: &Diagnostic) {
+        eprintln!("{}\n", diagnostic.render(&self.name, &self.source));
+    }
+}
+
+/// Reads a spec in the format its extension says: `.toml`, `.json`, `.types` for the compact
+/// language, and YAML otherwise.
+fn read_spec(path: &PathBuf) -> Result<(ApiSpec, SpecFile), Box<dyn Error>> {
+    let mut input_file = FileOrStdin::from_path(path)?;
+    let mut source = String::new();
+    input_file.lock().read_to_string(&mut source)?;
+
+    let file = SpecFile {
+        name: match path.to_str() {
+            Some("-") => "<stdin>".to_string(),
+            _ => path.display().to_string(),
+        },
+        source,
+    };
+    let format = SpecFormat::from_extension(path.extension().and_then(|ext| ext.to_str()));
+
+    match ApiSpec::parse(&file.source, format) {
+        Ok(spec) => Ok((spec, file)),
+        Err(diagnostic) => {
+            file.report(&diagnostic);
+            Err(format!("couldn't parse {}", file.name).into())
+        }
+    }
+}
+
+fn diff(old: &PathBuf, new: &PathBuf, json: bool) -> Result<(), Box<dyn Error>> {
+    let changes = read_spec(old)?.0.diff(&read_spec(new)?.0);
+    let breaking = changes.iter().filter(|change| change.is_breaking()).count();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&changes)?);
+    } else {
+        for change in &changes {
+            println!("{}", change);
+        }
+        if changes.is_empty() {
+            println!("no changes");
+        } else {
+            println!("{} change(s), {} of them breaking", changes.len(), breaking);
+        }
+    }
+
+    if breaking > 0 {
+        return Err(format!("{} breaking change(s)", breaking).into());
+    }
+
+    Ok(())
+}
+
+/// Warns about the items named `before` that `--include`/`--exclude` left out.
+fn warn_left_out<'a>(
+    kind: &str,
+    before: impl Iterator<Item = &'a str>,
+    after: impl Iterator<Item = &'a str>,
+) {
+    let after = after.collect::<Vec<_>>();
+    for name in before.filter(|name| !after.contains(name)) {
+        log::warn!(
+            "the {} `{}` is left out, as it uses a type that isn't generated",
+            kind,
+            name
+        );
+    }
 }
 
 fn app(opt: Opt) -> Result<(), Box<dyn Error>> {
-    let mut input_file = FileOrStdin::from_path(&opt.input)?;
+    if let Some(Cmd::Diff { old, new, json }) = &opt.cmd {
+        return diff(old, new, *json);
+    }
+
+    let (mut spec, file) = read_spec(&opt.input)?;
+    let full_spec = spec.clone();
 
-    let spec: ApiSpec = serde_yaml::from_reader(input_file.lock())?;
+    if !opt.include.is_empty() {
+        let mut roots = vec![];
+        for pattern in &opt.include {
+            let len = roots.len();
+            roots.extend(
+                spec.types
+                    .iter()
+                    .map(|t| t.name().to_string())
+                    .filter(|name| matches_pattern(pattern, name)),
+            );
+            if roots.len() == len {
+                return Err(format!("no type matches `{}`", pattern).into());
+            }
+        }
+
+        let roots = roots.iter().map(String::as_str).collect::<Vec<_>>();
+        spec = spec.subset(&roots);
+    }
 
-    let elm_str = format!("-- Auto-generated by rust_elm_types\n\n{}\n", spec.to_elm());
-    let rust_str = format!(
+    if !opt.exclude.is_empty() {
+        let graph = spec.type_graph();
+        let patterns = opt.exclude.iter().map(String::as_str).collect::<Vec<_>>();
+        spec = spec.without(&patterns);
+
+        for t in &spec.types {
+            for name in graph.references(t.name()) {
+                if !spec.types.iter().any(|t| t.name() == name) {
+                    log::warn!("`{}` refers to the excluded type `{}`", t.name(), name);
+                }
+            }
+        }
+    }
+
+    warn_left_out(
+        "endpoint",
+        full_spec.endpoints.iter().map(|e| e.name.as_str()),
+        spec.endpoints.iter().map(|e| e.name.as_str()),
+    );
+    warn_left_out(
+        "channel",
+        full_spec.channels.iter().map(|c| c.name.as_str()),
+        spec.channels.iter().map(|c| c.name.as_str()),
+    );
+    warn_left_out(
+        "port",
+        full_spec.ports.iter().map(|p| p.name.as_str()),
+        spec.ports.iter().map(|p| p.name.as_str()),
+    );
+    warn_left_out(
+        "upgrade from",
+        full_spec.upgrades.iter().map(|u| u.from.as_str()),
+        spec.upgrades.iter().map(|u| u.from.as_str()),
+    );
+
+    let diagnostics = spec.validate();
+    for diagnostic in &diagnostics {
+        if diagnostic.severity == Severity::Error || !opt.quiet {
+            file.report(diagnostic);
+        }
+    }
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        return Err("invalid spec".into());
+    }
+
+    if opt.topological {
+        spec = spec.sorted_topologically();
+    }
+
+    if let Some(path) = &opt.graph {
+        FileOrStdout::write_all(path, spec.type_graph().to_dot().as_bytes())?;
+    }
+
+    if let Some(path) = &opt.dts {
+        let dts = format!(
+            "// Auto-generated by rust_elm_types\n\n{}",
+            spec.ports_to_ts()
+        );
+        FileOrStdout::write_all(path, dts.as_bytes())?;
+    }
+
+    let elm_str = format!("-- Auto-generated by rust_elm_types\n\n{}", spec.to_elm());
+    let mut rust_str = format!(
         "// Auto-generated by rust_elm_types\n\n{}\n",
         spec.to_rust()
     );
 
+    if !opt.no_rustfmt {
+        match rustfmt(rust_str.clone()) {
+            Ok(formatted) => rust_str = formatted,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                log::info!("rustfmt not found, leaving the Rust output as generated")
+            }
+            Err(e) => log::warn!(
+                "rustfmt failed, leaving the Rust output as generated: {}",
+                e
+            ),
+        }
+    }
+
     FileOrStdout::write_all(&opt.elm, elm_str.as_bytes())?;
     FileOrStdout::write_all(&opt.rust, rust_str.as_bytes())?;
 
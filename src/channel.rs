@@ -0,0 +1,186 @@
+//! Message channels, e.g. over a WebSocket: JSON frames carrying one message each.
+
+use crate::{
+    elm::{self, Expr, Uses},
+    endpoint::elm_camel_case,
+    graph::ElmGraphs,
+    spec::*,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Channel {
+    /// `snake_case` name, e.g. `live_updates`.
+    pub name: String,
+    /// Type of the messages the client sends, usually an enum.
+    pub client: String,
+    /// Type of the messages the server sends, usually an enum.
+    pub server: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+}
+
+pub(crate) const RUST_FRAME_ERROR: &str = "\
+/// A frame that doesn't hold a valid message for its channel.
+#[derive(Debug)]
+pub struct FrameError {
+    pub channel: &'static str,
+    pub error: serde_json::Error,
+}
+
+impl std::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, \"invalid `{}` frame: {}\", self.channel, self.error)
+    }
+}
+
+impl std::error::Error for FrameError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}";
+
+impl Channel {
+    /// Elm names are built on `PascalCase`, e.g. `subscribeLiveUpdates`.
+    fn elm_suffix(&self) -> String {
+        let camel = elm_camel_case(&self.name);
+        let mut chars = camel.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    }
+
+    pub fn elm_exports(&self) -> Vec<String> {
+        let suffix = self.elm_suffix();
+        vec![
+            format!("decode{}Frame", suffix),
+            format!("encode{}Frame", suffix),
+            format!("subscribe{}", suffix),
+            format!("send{}", suffix),
+        ]
+    }
+
+    pub fn to_elm(&self, spec: &ApiSpec, graphs: &ElmGraphs, uses: &Uses) -> String {
+        uses.module("Json.Decode");
+        uses.module("Json.Encode");
+        uses.code(&self.server);
+        uses.code(&self.client);
+
+        let suffix = self.elm_suffix();
+        let client = (self.client.clone(), self.client.clone());
+        let server = (self.server.clone(), self.server.clone());
+
+        let decode = elm::definition(
+            &format!(
+                "decode{}Frame : String -> Result Json.Decode.Error {}",
+                suffix, self.server
+            ),
+            &format!("decode{}Frame", suffix),
+            &Expr::line(format!(
+                "Json.Decode.decodeString {}",
+                elm_decoder(&server, "", spec, graphs, uses)
+            )),
+        );
+
+        let encode = elm::definition(
+            &format!("encode{}Frame : {} -> String", suffix, self.client),
+            &format!("encode{}Frame message", suffix),
+            &Expr::line(format!(
+                "Json.Encode.encode 0 ({} message)",
+                elm_encoder(&client, spec, uses)
+            )),
+        );
+
+        let subscribe = elm::definition(
+            &format!(
+                "subscribe{} : ((String -> msg) -> Sub msg) -> (Result Json.Decode.Error {} -> msg) -> Sub msg",
+                suffix, self.server
+            ),
+            &format!("subscribe{} listen toMsg", suffix),
+            &Expr::line(format!("listen (toMsg << decode{}Frame)", suffix)),
+        );
+
+        let send = elm::definition(
+            &format!(
+                "send{} : (String -> Cmd msg) -> {} -> Cmd msg",
+                suffix, self.client
+            ),
+            &format!("send{} toPort message", suffix),
+            &Expr::line(format!("toPort (encode{}Frame message)", suffix)),
+        );
+
+        let doc = self
+            .doc
+            .as_deref()
+            .map_or(String::new(), |doc| format!("{}\n\n", doc.trim_end()));
+
+        [
+            format!(
+                "{{-| {}Reads a frame received on the `{}` channel.\n-}}\n{}",
+                doc, self.name, decode
+            ),
+            format!(
+                "{{-| Writes a frame to send on the `{}` channel.\n-}}\n{}",
+                self.name, encode
+            ),
+            format!(
+                "{{-| Listens for messages on the `{}` channel through an incoming port.\n-}}\n{}",
+                self.name, subscribe
+            ),
+            format!(
+                "{{-| Sends a message on the `{}` channel through an outgoing port.\n-}}\n{}",
+                self.name, send
+            ),
+        ]
+        .join(elm::DECLARATION_SEPARATOR)
+    }
+
+    pub fn to_rust(&self, vis: Visibility) -> String {
+        format!(
+            "\
+{doc}/// Parses a frame the client sent on the `{name}` channel.
+{vis}fn parse_{name}_frame(frame: &str) -> Result<{client}, FrameError> {{
+    serde_json::from_str(frame).map_err(|error| FrameError {{
+        channel: \"{name}\",
+        error,
+    }})
+}}
+
+/// Writes a message to send to the client on the `{name}` channel.
+{vis}fn {name}_frame(message: &{server}) -> String {{
+    serde_json::to_string(message).expect(\"messages serialize to JSON\")
+}}",
+            doc = self.doc.as_ref().map_or(String::new(), |doc| {
+                format!("{}\n///\n", doc_lines(doc, "///"))
+            }),
+            vis = vis.to_rust(),
+            name = self.name,
+            client = rust_type(&self.client),
+            server = rust_type(&self.server),
+        )
+    }
+}
+
+impl ApiSpec {
+    /// Frame helpers for the channels, if there are any.
+    pub fn channels_to_rust(&self) -> Option<String> {
+        if self.channels.is_empty() {
+            return None;
+        }
+
+        let vis = self.rust.visibility;
+
+        let channels = self
+            .channels
+            .iter()
+            .map(|channel| channel.to_rust(vis))
+            .collect::<Vec<_>>();
+
+        Some(format!(
+            "{}\n\n{}",
+            RUST_FRAME_ERROR.replace("pub ", vis.to_rust()),
+            channels.join("\n\n")
+        ))
+    }
+}
@@ -0,0 +1,555 @@
+//! Changes between two versions of a spec, and whether code generated from the old version still
+//! works with code generated from the new one.
+//!
+//! Clients are the generated Elm, servers the generated Rust. Types may be sent either way, so a
+//! change to a type is judged for both directions: a change breaks old clients if an old client
+//! and a new server can't read each other's JSON, and old servers if a new client and an old
+//! server can't. Endpoint bodies only go one way, so a change to one can break just one side.
+
+use crate::{channel::Channel, endpoint::*, spec::*};
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpecChange {
+    /// What changed, e.g. `User.email` or `get_user`.
+    pub path: String,
+    pub message: String,
+    /// Old clients can't talk to new servers anymore.
+    pub breaks_old_clients: bool,
+    /// New clients can't talk to old servers anymore.
+    pub breaks_old_servers: bool,
+}
+
+impl SpecChange {
+    fn new(
+        path: impl Into<String>,
+        message: impl Into<String>,
+        breaks_old_clients: bool,
+        breaks_old_servers: bool,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+            breaks_old_clients,
+            breaks_old_servers,
+        }
+    }
+
+    fn compatible(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(path, message, false, false)
+    }
+
+    fn breaking(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(path, message, true, true)
+    }
+
+    pub fn is_breaking(&self) -> bool {
+        self.breaks_old_clients || self.breaks_old_servers
+    }
+}
+
+impl fmt::Display for SpecChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let breaks = match (self.breaks_old_clients, self.breaks_old_servers) {
+            (true, true) => " (breaks old clients and old servers)",
+            (true, false) => " (breaks old clients)",
+            (false, true) => " (breaks old servers)",
+            (false, false) => "",
+        };
+        let kind = if self.is_breaking() {
+            "breaking"
+        } else {
+            "compatible"
+        };
+
+        write!(f, "{}: {}: {}{}", kind, self.path, self.message, breaks)
+    }
+}
+
+fn is_maybe(data: &(String, String)) -> bool {
+    data.1.starts_with("Maybe ")
+}
+
+fn type_kind(t: &TypeSpec) -> &'static str {
+    match t {
+        TypeSpec::Struct { .. } => "struct",
+        TypeSpec::Enum { .. } => "enum",
+        TypeSpec::Newtype { .. } => "newtype",
+        TypeSpec::Alias { .. } => "alias",
+    }
+}
+
+fn repr_name(repr: EnumRepr) -> &'static str {
+    match repr {
+        EnumRepr::Tagged => "tagged objects",
+        EnumRepr::String => "strings",
+        EnumRepr::Int => "integers",
+    }
+}
+
+fn data_change(
+    path: &str,
+    what: &str,
+    old: &(String, String),
+    new: &(String, String),
+) -> Vec<SpecChange> {
+    if old == new {
+        return vec![];
+    }
+
+    let message = match (is_maybe(old), is_maybe(new)) {
+        (true, false) => format!("{} is now required, as `{}`", what, new.0),
+        (false, true) => format!("{} is now optional, as `{}`", what, new.0),
+        _ => format!("{} changed from `{}` to `{}`", what, old.0, new.0),
+    };
+
+    vec![SpecChange::breaking(path, message)]
+}
+
+/// Which way the JSON of an endpoint body goes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Flow {
+    /// From client to server, like a request body.
+    ToServer,
+    /// From server to client, like a response.
+    ToClient,
+}
+
+impl Flow {
+    /// `(breaks_old_clients, breaks_old_servers)` for a change after which new receivers can't
+    /// read what old senders send, or old receivers what new senders send.
+    fn breaks(self, new_cant_read_old: bool, old_cant_read_new: bool) -> (bool, bool) {
+        match self {
+            Self::ToServer => (new_cant_read_old, old_cant_read_new),
+            Self::ToClient => (old_cant_read_new, new_cant_read_old),
+        }
+    }
+}
+
+/// Like [`data_change`], for a body that only goes one way, so that only the side that reads it
+/// has to understand the other's version.
+fn body_change(
+    path: &str,
+    what: &str,
+    flow: Flow,
+    old: &Option<(String, String)>,
+    new: &Option<(String, String)>,
+) -> Vec<SpecChange> {
+    // A missing body is ignored by a side that doesn't expect one, but fails one that does.
+    let (message, new_cant_read_old, old_cant_read_new) = match (old, new) {
+        (Some(old), Some(new)) if old == new => return vec![],
+        (None, None) => return vec![],
+        (Some(old), Some(new)) => match (is_maybe(old), is_maybe(new)) {
+            (true, false) => (
+                format!("{} is now required, as `{}`", what, new.0),
+                true,
+                false,
+            ),
+            (false, true) => (
+                format!("{} is now optional, as `{}`", what, new.0),
+                false,
+                true,
+            ),
+            _ => (
+                format!("{} changed from `{}` to `{}`", what, old.0, new.0),
+                true,
+                true,
+            ),
+        },
+        (None, Some(_)) => (format!("{} added", what), true, false),
+        (Some(_), None) => (format!("{} removed", what), false, true),
+    };
+
+    let (breaks_old_clients, breaks_old_servers) =
+        flow.breaks(new_cant_read_old, old_cant_read_new);
+    vec![SpecChange::new(
+        path,
+        message,
+        breaks_old_clients,
+        breaks_old_servers,
+    )]
+}
+
+/// Items of `old` and `new`, matched by name. Removed and added items at the same position with
+/// the same contents are taken to be renamed.
+struct Matched<'a, T> {
+    removed: Vec<&'a T>,
+    added: Vec<&'a T>,
+    renamed: Vec<(&'a T, &'a T)>,
+    kept: Vec<(&'a T, &'a T)>,
+}
+
+fn match_by_name<'a, T>(
+    old: &'a [T],
+    new: &'a [T],
+    name: impl Fn(&T) -> &str,
+    same_contents: impl Fn(&T, &T) -> bool,
+) -> Matched<'a, T> {
+    let mut matched = Matched {
+        removed: vec![],
+        added: vec![],
+        renamed: vec![],
+        kept: vec![],
+    };
+
+    for (i, old_item) in old.iter().enumerate() {
+        if let Some(new_item) = new.iter().find(|item| name(item) == name(old_item)) {
+            matched.kept.push((old_item, new_item));
+        } else if let Some(new_item) = new.get(i).filter(|new_item| {
+            !old.iter().any(|item| name(item) == name(new_item))
+                && same_contents(old_item, new_item)
+        }) {
+            matched.renamed.push((old_item, new_item));
+        } else {
+            matched.removed.push(old_item);
+        }
+    }
+
+    matched.added = new
+        .iter()
+        .filter(|new_item| {
+            !old.iter().any(|item| name(item) == name(new_item))
+                && !matched
+                    .renamed
+                    .iter()
+                    .any(|(_, renamed)| std::ptr::eq(*renamed, *new_item))
+        })
+        .collect();
+
+    matched
+}
+
+fn diff_fields(path: &str, old: &[StructField], new: &[StructField]) -> Vec<SpecChange> {
+    let matched = match_by_name(old, new, |field| &field.name, |a, b| a.data == b.data);
+    let mut changes = vec![];
+
+    for field in matched.removed {
+        // A new client leaves the field out, which an old server accepts only for `Option`s.
+        changes.push(SpecChange::new(
+            format!("{}.{}", path, field.name),
+            "field removed",
+            true,
+            !is_maybe(&field.data),
+        ));
+    }
+    for (old_field, new_field) in matched.renamed {
+        changes.push(SpecChange::breaking(
+            format!("{}.{}", path, old_field.name),
+            format!("field renamed to `{}`", new_field.name),
+        ));
+    }
+    for (old_field, new_field) in matched.kept {
+        changes.extend(data_change(
+            &format!("{}.{}", path, old_field.name),
+            "type",
+            &old_field.data,
+            &new_field.data,
+        ));
+    }
+    for field in matched.added {
+        // Old JSON lacks the field. Rust reads a missing `Option` as `None`, but Elm decoders
+        // expect every field to be there.
+        let path = format!("{}.{}", path, field.name);
+        changes.push(if is_maybe(&field.data) {
+            SpecChange::new(path, "optional field added", false, true)
+        } else {
+            SpecChange::breaking(path, "required field added")
+        });
+    }
+
+    changes
+}
+
+fn same_variant_data(old: &EnumVariantData, new: &EnumVariantData) -> bool {
+    match (old, new) {
+        (EnumVariantData::None, EnumVariantData::None) => true,
+        (EnumVariantData::Single(old), EnumVariantData::Single(new)) => old == new,
+        (EnumVariantData::Struct(old), EnumVariantData::Struct(new)) => {
+            old.len() == new.len()
+                && old
+                    .iter()
+                    .zip(new)
+                    .all(|(old, new)| old.name == new.name && old.data == new.data)
+        }
+        _ => false,
+    }
+}
+
+fn diff_variants(
+    name: &str,
+    (old, old_fallback): (&[EnumVariant], bool),
+    (new, new_fallback): (&[EnumVariant], bool),
+) -> Vec<SpecChange> {
+    let matched = match_by_name(
+        old,
+        new,
+        |var| &var.name,
+        |a, b| same_variant_data(&a.data, &b.data) && a.discriminant == b.discriminant,
+    );
+    let mut changes = vec![];
+
+    // A side that doesn't know a variant can still read it into its `Unknown` fallback.
+    for var in matched.removed {
+        changes.push(SpecChange::new(
+            format!("{}::{}", name, var.name),
+            "variant removed",
+            !new_fallback,
+            !new_fallback,
+        ));
+    }
+    for (old_var, new_var) in matched.renamed {
+        let breaks = !(old_fallback && new_fallback);
+        changes.push(SpecChange::new(
+            format!("{}::{}", name, old_var.name),
+            format!("variant renamed to `{}`", new_var.name),
+            breaks,
+            breaks,
+        ));
+    }
+    for (old_var, new_var) in matched.kept {
+        let path = format!("{}::{}", name, old_var.name);
+
+        if old_var.discriminant != new_var.discriminant {
+            changes.push(SpecChange::breaking(&path, "discriminant changed"));
+        }
+
+        match (&old_var.data, &new_var.data) {
+            (EnumVariantData::None, EnumVariantData::None) => {}
+            (EnumVariantData::Single(old), EnumVariantData::Single(new)) => {
+                changes.extend(data_change(&path, "data", old, new))
+            }
+            (EnumVariantData::Struct(old), EnumVariantData::Struct(new)) => {
+                changes.extend(diff_fields(&path, old, new))
+            }
+            _ => changes.push(SpecChange::breaking(&path, "data changed")),
+        }
+    }
+    for var in matched.added {
+        changes.push(SpecChange::new(
+            format!("{}::{}", name, var.name),
+            "variant added",
+            !old_fallback,
+            !old_fallback,
+        ));
+    }
+
+    changes
+}
+
+fn diff_type(old: &TypeSpec, new: &TypeSpec) -> Vec<SpecChange> {
+    let name = old.name();
+
+    match (old, new) {
+        (TypeSpec::Struct { fields: old, .. }, TypeSpec::Struct { fields: new, .. }) => {
+            diff_fields(name, old, new)
+        }
+        (
+            TypeSpec::Enum {
+                variants: old,
+                unknown_fallback: old_fallback,
+                ..
+            },
+            TypeSpec::Enum {
+                variants: new,
+                unknown_fallback: new_fallback,
+                ..
+            },
+        ) => {
+            let (old_repr, new_repr) = (EnumRepr::of(old), EnumRepr::of(new));
+            if old_repr != new_repr {
+                return vec![SpecChange::breaking(
+                    name,
+                    format!(
+                        "now sent as {} instead of {}",
+                        repr_name(new_repr),
+                        repr_name(old_repr)
+                    ),
+                )];
+            }
+
+            diff_variants(name, (old, *old_fallback), (new, *new_fallback))
+        }
+        (TypeSpec::Newtype { data: old, .. }, TypeSpec::Newtype { data: new, .. })
+        | (TypeSpec::Alias { data: old, .. }, TypeSpec::Alias { data: new, .. }) => {
+            data_change(name, "type", old, new)
+        }
+        _ => vec![SpecChange::breaking(
+            name,
+            format!("changed from {} to {}", type_kind(old), type_kind(new)),
+        )],
+    }
+}
+
+fn diff_params(
+    name: &str,
+    what: &str,
+    old: &[StructField],
+    new: &[StructField],
+) -> Vec<SpecChange> {
+    let mut changes = vec![];
+
+    for param in old {
+        let path = format!("{}.{}", name, param.name);
+        match new.iter().find(|p| p.name == param.name) {
+            // Servers ignore query parameters they don't know, but not missing required ones.
+            Some(new_param) => {
+                changes.extend(data_change(&path, "type", &param.data, &new_param.data))
+            }
+            None => changes.push(SpecChange::new(
+                path,
+                format!("{} removed", what),
+                false,
+                !is_maybe(&param.data),
+            )),
+        }
+    }
+    for param in new
+        .iter()
+        .filter(|p| !old.iter().any(|old| old.name == p.name))
+    {
+        let path = format!("{}.{}", name, param.name);
+        changes.push(if is_maybe(&param.data) {
+            SpecChange::compatible(path, format!("optional {} added", what))
+        } else {
+            SpecChange::new(path, format!("required {} added", what), true, false)
+        });
+    }
+
+    changes
+}
+
+fn diff_endpoint(old: &Endpoint, new: &Endpoint) -> Vec<SpecChange> {
+    let name = &old.name;
+    let mut changes = vec![];
+
+    if old.method != new.method || old.path != new.path {
+        changes.push(SpecChange::breaking(
+            name,
+            format!(
+                "moved from `{} {}` to `{} {}`",
+                old.method.as_str(),
+                old.path,
+                new.method.as_str(),
+                new.path
+            ),
+        ));
+    }
+
+    // Path parameters only change along with the path, which is reported above.
+    for param in &old.params {
+        if let Some(new_param) = new.params.iter().find(|p| p.name == param.name) {
+            changes.extend(data_change(
+                &format!("{}.{}", name, param.name),
+                "type",
+                &param.data,
+                &new_param.data,
+            ));
+        }
+    }
+    changes.extend(diff_params(name, "query parameter", &old.query, &new.query));
+
+    let bodies = [
+        ("request body", Flow::ToServer, &old.body, &new.body),
+        ("response", Flow::ToClient, &old.response, &new.response),
+        ("error response", Flow::ToClient, &old.error, &new.error),
+    ];
+    for (what, flow, old, new) in bodies.iter() {
+        changes.extend(body_change(name, what, *flow, old, new));
+    }
+
+    changes
+}
+
+fn diff_channel(old: &Channel, new: &Channel) -> Vec<SpecChange> {
+    let name = &old.name;
+    let mut changes = vec![];
+
+    if old.client != new.client {
+        changes.push(SpecChange::breaking(
+            name,
+            format!(
+                "client messages changed from `{}` to `{}`",
+                old.client, new.client
+            ),
+        ));
+    }
+    if old.server != new.server {
+        changes.push(SpecChange::breaking(
+            name,
+            format!(
+                "server messages changed from `{}` to `{}`",
+                old.server, new.server
+            ),
+        ));
+    }
+
+    changes
+}
+
+impl ApiSpec {
+    /// Changes from this spec to `new`. Types, endpoints and channels are matched by name; ports
+    /// stay within the client and aren't compared.
+    pub fn diff(&self, new: &ApiSpec) -> Vec<SpecChange> {
+        let mut changes = vec![];
+
+        for old_type in &self.types {
+            match new.types.iter().find(|t| t.name() == old_type.name()) {
+                Some(new_type) => changes.extend(diff_type(old_type, new_type)),
+                // Whatever still refers to the type has changed too, and is reported there.
+                None => changes.push(SpecChange::compatible(old_type.name(), "type removed")),
+            }
+        }
+        for new_type in &new.types {
+            if !self.types.iter().any(|t| t.name() == new_type.name()) {
+                changes.push(SpecChange::compatible(new_type.name(), "type added"));
+            }
+        }
+
+        for old_endpoint in &self.endpoints {
+            match new.endpoints.iter().find(|e| e.name == old_endpoint.name) {
+                Some(new_endpoint) => changes.extend(diff_endpoint(old_endpoint, new_endpoint)),
+                None => changes.push(SpecChange::new(
+                    &old_endpoint.name,
+                    "endpoint removed",
+                    true,
+                    false,
+                )),
+            }
+        }
+        for new_endpoint in &new.endpoints {
+            if !self.endpoints.iter().any(|e| e.name == new_endpoint.name) {
+                changes.push(SpecChange::new(
+                    &new_endpoint.name,
+                    "endpoint added",
+                    false,
+                    true,
+                ));
+            }
+        }
+
+        for old_channel in &self.channels {
+            match new.channels.iter().find(|c| c.name == old_channel.name) {
+                Some(new_channel) => changes.extend(diff_channel(old_channel, new_channel)),
+                None => changes.push(SpecChange::new(
+                    &old_channel.name,
+                    "channel removed",
+                    true,
+                    false,
+                )),
+            }
+        }
+        for new_channel in &new.channels {
+            if !self.channels.iter().any(|c| c.name == new_channel.name) {
+                changes.push(SpecChange::new(
+                    &new_channel.name,
+                    "channel added",
+                    false,
+                    true,
+                ));
+            }
+        }
+
+        changes
+    }
+}
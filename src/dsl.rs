@@ -0,0 +1,543 @@
+//! A compact language for specs, as an alternative to YAML:
+//!
+//! ```text
+//! module Api
+//!
+//! /// A user of the app.
+//! type User { id: UserId, name: String, email: Option<String> }
+//! type UserId(u32)
+//! type UserIds = Vec<UserId>
+//! enum Shape = Circle { r: f64 } | Square(f64) | Empty
+//! enum Level = Low = 1 | High = 2
+//! ```
+//!
+//! `type X { ... }` is a struct, `type X(T)` a newtype and `type X = T` an alias. Types are
+//! written in Rust, and the Elm types are derived from them. Where there is no Elm equivalent, or
+//! another one is wanted, write it after `as`, e.g. `chrono::NaiveDate as String`.
+//! `///` comments document the item that follows, `//` comments are ignored.
+//!
+//! The language only covers types, and has no syntax for `targets` or `unknown_fallback`: specs
+//! that need those, or endpoints, channels, ports or upgrades, are written in YAML, TOML or JSON.
+
+use crate::{primitives, source::Span, spec::*, well_known};
+use std::{collections::HashMap, fmt};
+
+/// A syntax error, and where it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DslError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for DslError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}",
+            self.span.line, self.span.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for DslError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    /// An identifier or a path like `chrono::Utc` or `Time.Posix`.
+    Ident(String),
+    Int(i64),
+    Punct(char),
+    Doc(String),
+    Eof,
+}
+
+impl fmt::Display for Tok {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tok::Ident(name) => write!(f, "`{}`", name),
+            Tok::Int(value) => write!(f, "`{}`", value),
+            Tok::Punct(c) => write!(f, "`{}`", c),
+            Tok::Doc(_) => write!(f, "a doc comment"),
+            Tok::Eof => write!(f, "the end of the file"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    tok: Tok,
+    span: Span,
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, DslError> {
+    let mut tokens = vec![];
+
+    for (i, line) in source.lines().enumerate() {
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut pos = 0;
+
+        while pos < chars.len() {
+            let c = chars[pos];
+            let start = pos;
+            let span = |end: usize| Span {
+                line: i + 1,
+                column: start + 1,
+                len: end - start,
+            };
+
+            if c.is_whitespace() {
+                pos += 1;
+            } else if chars[pos..].starts_with(&['/', '/', '/']) {
+                let doc = chars[pos + 3..].iter().collect::<String>();
+                let doc = doc.strip_prefix(' ').unwrap_or(&doc).trim_end().to_string();
+                tokens.push(Token {
+                    tok: Tok::Doc(doc),
+                    span: span(chars.len()),
+                });
+                break;
+            } else if c == '/' && chars.get(pos + 1) == Some(&'/') {
+                break;
+            } else if is_ident_start(c) {
+                // Also takes in Rust paths like `chrono::Utc` and Elm ones like `Time.Posix`.
+                while pos < chars.len()
+                    && (is_ident_char(chars[pos])
+                        || (chars[pos] == ':'
+                            && chars.get(pos + 1) == Some(&':')
+                            && chars.get(pos + 2).copied().is_some_and(is_ident_start))
+                        || (chars[pos] == '.'
+                            && chars.get(pos + 1).is_some_and(char::is_ascii_uppercase)))
+                {
+                    pos += if chars[pos] == ':' { 2 } else { 1 };
+                }
+                tokens.push(Token {
+                    tok: Tok::Ident(chars[start..pos].iter().collect()),
+                    span: span(pos),
+                });
+            } else if c.is_ascii_digit()
+                || (c == '-' && chars.get(pos + 1).is_some_and(char::is_ascii_digit))
+            {
+                pos += 1;
+                while pos < chars.len() && chars[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+                let digits = chars[start..pos].iter().collect::<String>();
+                let value = digits.parse().map_err(|_| DslError {
+                    span: span(pos),
+                    message: format!("`{}` doesn't fit in an `i64`", digits),
+                })?;
+                tokens.push(Token {
+                    tok: Tok::Int(value),
+                    span: span(pos),
+                });
+            } else if "{}()<>,:=|".contains(c) {
+                pos += 1;
+                tokens.push(Token {
+                    tok: Tok::Punct(c),
+                    span: span(pos),
+                });
+            } else {
+                return Err(DslError {
+                    span: span(pos + 1),
+                    message: format!("unexpected character `{}`", c),
+                });
+            }
+        }
+    }
+
+    let line = source.lines().count().max(1);
+    let column = source.lines().last().map_or(0, |l| l.chars().count()) + 1;
+    tokens.push(Token {
+        tok: Tok::Eof,
+        span: Span {
+            line,
+            column,
+            len: 1,
+        },
+    });
+
+    Ok(tokens)
+}
+
+/// A Rust type as written, e.g. `Vec<Option<u32>>`.
+struct RustType {
+    name: String,
+    args: Vec<RustType>,
+}
+
+impl fmt::Display for RustType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.args.is_empty() {
+            let args = self
+                .args
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            write!(f, "<{}>", args.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl RustType {
+    /// The Elm type of the JSON serde makes of this type, if there is one.
+    fn elm_type(&self) -> Option<String> {
+        let short_name = self.name.rsplit("::").next().unwrap_or(&self.name);
+
+        match (short_name, self.args.as_slice()) {
+            ("Option", [inner]) => Some(format!("Maybe {}", elm_type_arg(&inner.elm_type()?))),
+            ("Vec", [inner]) => Some(format!("List {}", elm_type_arg(&inner.elm_type()?))),
+            ("Box", [inner]) => inner.elm_type(),
+            // serde writes maps as JSON objects, whose keys are strings.
+            ("HashMap", [key, value]) | ("BTreeMap", [key, value]) if key.name == "String" => {
+                Some(format!("Dict String {}", elm_type_arg(&value.elm_type()?)))
+            }
+            _ => {
+                let rust_type = self.to_string();
+                if let Some(primitive) = primitives::lookup(&rust_type) {
+                    Some(primitive.elm.to_string())
+                } else if let Some(elm_type) = well_known::elm_type(&rust_type) {
+                    Some(elm_type.to_string())
+                } else if self.args.is_empty() && !self.name.contains("::") {
+                    // Another type of the spec.
+                    Some(self.name.clone())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Spans of the nodes parsed so far, as in [`ApiSpec::spans`].
+    spans: HashMap<String, Span>,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.tok != Tok::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, span: Span, message: impl Into<String>) -> Result<T, DslError> {
+        Err(DslError {
+            span,
+            message: message.into(),
+        })
+    }
+
+    fn expected<T>(&self, what: &str) -> Result<T, DslError> {
+        let token = self.peek();
+        self.error(
+            token.span,
+            format!("expected {}, found {}", what, token.tok),
+        )
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek().tok == Tok::Punct(c) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), DslError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.expected(&format!("`{}`", c))
+        }
+    }
+
+    fn ident(&mut self, what: &str) -> Result<String, DslError> {
+        match &self.peek().tok {
+            Tok::Ident(name) => {
+                let name = name.clone();
+                self.next();
+                Ok(name)
+            }
+            _ => self.expected(what),
+        }
+    }
+
+    /// An identifier, recording its span as that of the node at `path(name)`.
+    fn name(&mut self, what: &str, path: impl FnOnce(&str) -> String) -> Result<String, DslError> {
+        let span = self.peek().span;
+        let name = self.ident(what)?;
+        self.spans.insert(path(&name), span);
+        Ok(name)
+    }
+
+    /// Consecutive `///` comments, joined into one doc.
+    fn doc(&mut self) -> Option<String> {
+        let mut lines = vec![];
+        while let Tok::Doc(line) = &self.peek().tok {
+            lines.push(line.clone());
+            self.next();
+        }
+
+        if lines.is_empty() {
+            None
+        } else {
+            Some(lines.join("\n"))
+        }
+    }
+
+    fn rust_type(&mut self) -> Result<RustType, DslError> {
+        let name = self.ident("a type")?;
+        let mut args = vec![];
+
+        if self.eat('<') {
+            loop {
+                args.push(self.rust_type()?);
+                if self.eat('>') {
+                    break;
+                }
+                self.expect(',')?;
+            }
+        }
+
+        Ok(RustType { name, args })
+    }
+
+    /// An Elm type after `as`, e.g. `Dict String (List Int)`. Tuples and records have no JSON
+    /// codecs, so they aren't accepted.
+    fn elm_type(&mut self) -> Result<String, DslError> {
+        let mut parts = vec![];
+        loop {
+            match &self.peek().tok {
+                Tok::Ident(name) if name.starts_with(|c: char| c.is_ascii_uppercase()) => {
+                    parts.push(name.clone());
+                    self.next();
+                }
+                Tok::Punct('(') => {
+                    self.next();
+                    let inner = self.elm_type()?;
+                    self.expect(')')?;
+                    parts.push(format!("({})", inner));
+                }
+                _ if parts.is_empty() => return self.expected("an Elm type"),
+                _ => break,
+            }
+        }
+
+        Ok(parts.join(" "))
+    }
+
+    /// A type, as the `(rust, elm)` pair of the spec.
+    fn type_pair(&mut self) -> Result<(String, String), DslError> {
+        let start = self.peek().span;
+        let rust_type = self.rust_type()?;
+
+        if matches!(&self.peek().tok, Tok::Ident(k) if k == "as") {
+            self.next();
+            return Ok((rust_type.to_string(), self.elm_type()?));
+        }
+
+        match rust_type.elm_type() {
+            Some(elm_type) => Ok((rust_type.to_string(), elm_type)),
+            None => {
+                let end = self.tokens[self.pos - 1].span;
+                let span = Span {
+                    len: if end.line == start.line {
+                        end.column + end.len - start.column
+                    } else {
+                        start.len
+                    },
+                    ..start
+                };
+                self.error(
+                    span,
+                    format!("`{}` has no Elm equivalent, give one with `as`", rust_type),
+                )
+            }
+        }
+    }
+
+    /// `{ name: Type, ... }`, with an optional trailing comma, in the node at `parent`.
+    fn fields(&mut self, parent: &str) -> Result<Vec<StructField>, DslError> {
+        self.expect('{')?;
+
+        let mut fields = vec![];
+        loop {
+            let doc = self.doc();
+            if self.eat('}') {
+                break;
+            }
+
+            let name = self.name("a field name or `}`", |name| format!("{}.{}", parent, name))?;
+            self.expect(':')?;
+            let data = self.type_pair()?;
+            fields.push(StructField { name, data, doc });
+
+            if !self.eat(',') {
+                self.doc();
+                self.expect('}')?;
+                break;
+            }
+        }
+
+        Ok(fields)
+    }
+
+    fn variant(&mut self, enum_name: &str) -> Result<EnumVariant, DslError> {
+        let doc = self.doc();
+        let name = self.name("a variant name", |name| format!("{}::{}", enum_name, name))?;
+
+        let data = match self.peek().tok {
+            Tok::Punct('{') => {
+                EnumVariantData::Struct(self.fields(&format!("{}::{}", enum_name, name))?)
+            }
+            Tok::Punct('(') => {
+                self.next();
+                let data = self.type_pair()?;
+                self.expect(')')?;
+                EnumVariantData::Single(data)
+            }
+            _ => EnumVariantData::None,
+        };
+
+        let discriminant = if self.eat('=') {
+            match self.next() {
+                Token {
+                    tok: Tok::Int(value),
+                    ..
+                } => Some(value),
+                token => {
+                    return self.error(
+                        token.span,
+                        format!("expected a discriminant, found {}", token.tok),
+                    )
+                }
+            }
+        } else {
+            None
+        };
+
+        Ok(EnumVariant {
+            name,
+            data,
+            doc,
+            discriminant,
+        })
+    }
+
+    fn type_spec(&mut self) -> Result<TypeSpec, DslError> {
+        let doc = self.doc();
+        let keyword = self.peek().clone();
+
+        match &keyword.tok {
+            Tok::Ident(k) if k == "type" => {
+                self.next();
+                let name = self.name("a type name", str::to_string)?;
+
+                match self.peek().tok {
+                    Tok::Punct('{') => Ok(TypeSpec::Struct {
+                        fields: self.fields(&name)?,
+                        name,
+                        doc,
+                        targets: None,
+                    }),
+                    Tok::Punct('(') => {
+                        self.next();
+                        let data = self.type_pair()?;
+                        self.expect(')')?;
+                        Ok(TypeSpec::Newtype {
+                            name,
+                            data,
+                            doc,
+                            targets: None,
+                        })
+                    }
+                    Tok::Punct('=') => {
+                        self.next();
+                        Ok(TypeSpec::Alias {
+                            name,
+                            data: self.type_pair()?,
+                            doc,
+                            targets: None,
+                        })
+                    }
+                    _ => self.expected("`{`, `(` or `=`"),
+                }
+            }
+            Tok::Ident(k) if k == "enum" => {
+                self.next();
+                let name = self.name("an enum name", str::to_string)?;
+                self.expect('=')?;
+                self.eat('|');
+
+                let mut variants = vec![self.variant(&name)?];
+                while self.eat('|') {
+                    variants.push(self.variant(&name)?);
+                }
+
+                Ok(TypeSpec::Enum {
+                    name,
+                    variants,
+                    doc,
+                    targets: None,
+                    unknown_fallback: false,
+                })
+            }
+            _ => self.expected("`type` or `enum`"),
+        }
+    }
+
+    fn spec(&mut self) -> Result<ApiSpec, DslError> {
+        let doc = self.doc();
+
+        match &self.peek().tok {
+            Tok::Ident(k) if k == "module" => {
+                self.next();
+            }
+            _ => return self.expected("`module`"),
+        }
+        let module = self.ident("a module name")?;
+
+        let mut types = vec![];
+        while self.peek().tok != Tok::Eof {
+            types.push(self.type_spec()?);
+        }
+
+        Ok(ApiSpec {
+            module,
+            doc,
+            types,
+            spans: std::mem::take(&mut self.spans),
+            ..Default::default()
+        })
+    }
+}
+
+impl ApiSpec {
+    /// Parses a spec written in the compact language described in [the module docs](self).
+    pub fn from_dsl(source: &str) -> Result<Self, DslError> {
+        Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+            spans: HashMap::new(),
+        }
+        .spec()
+    }
+}
@@ -0,0 +1,303 @@
+//! A small pretty-printer for the Elm code we generate.
+//!
+//! Expressions are laid out the way elm-format lays them out: each construct decides which of its
+//! parts go on their own line, and nested lines are indented to the next multiple of four
+//! columns, so the output passes `elm-format --validate` unchanged.
+
+use std::{cell::RefCell, collections::BTreeSet};
+
+/// Blank lines between top-level declarations.
+pub const DECLARATION_SEPARATOR: &str = "\n\n\n";
+
+const TAB_WIDTH: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// Code that stays on one line, e.g. `Json.Decode.int` or `value`.
+    Line(String),
+    /// `head` followed by one argument per line, e.g. `Json.Decode.map2 Record` and its field
+    /// decoders.
+    Call(String, Vec<Expr>),
+    /// `first |> step |> ...`, with each step on its own line.
+    Pipeline(Box<Expr>, Vec<Expr>),
+    /// `lhs <|` with the right-hand side on the next line.
+    BackwardPipe(String, Box<Expr>),
+    /// A list with one element per line.
+    List(Vec<Expr>),
+    /// `( a, b )`, split over several lines if any element is.
+    Tuple(Vec<Expr>),
+    Parens(Box<Expr>),
+    /// `\args -> body`
+    Lambda(String, Box<Expr>),
+    /// `case subject of` with `(pattern, body)` branches.
+    Case(String, Vec<(String, Expr)>),
+    /// A record with one field per line.
+    Record(Vec<(String, Expr)>),
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Text(String),
+    /// Advances to the next tab stop.
+    Tab,
+}
+
+type Line = Vec<Segment>;
+
+fn text(s: impl Into<String>) -> Line {
+    vec![Segment::Text(s.into())]
+}
+
+fn indent(lines: Vec<Line>) -> Vec<Line> {
+    lines
+        .into_iter()
+        .map(|line| std::iter::once(Segment::Tab).chain(line).collect())
+        .collect()
+}
+
+/// Puts `prefix` in front of the first line, and pads the others to line up with it.
+fn prefix(prefix: &str, lines: Vec<Line>) -> Vec<Line> {
+    let padding = " ".repeat(prefix.chars().count());
+
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let first = if i == 0 { prefix } else { &padding };
+            std::iter::once(Segment::Text(first.to_string()))
+                .chain(line)
+                .collect()
+        })
+        .collect()
+}
+
+fn render_line(line: &[Segment]) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+
+    for segment in line {
+        match segment {
+            Segment::Text(s) => {
+                out.push_str(s);
+                column += s.chars().count();
+            }
+            Segment::Tab => {
+                let width = TAB_WIDTH - column % TAB_WIDTH;
+                out.push_str(&" ".repeat(width));
+                column += width;
+            }
+        }
+    }
+
+    out.trim_end().to_string()
+}
+
+fn render(lines: &[Line]) -> String {
+    lines
+        .iter()
+        .map(|line| render_line(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl Expr {
+    pub fn line(s: impl Into<String>) -> Self {
+        Self::Line(s.into())
+    }
+
+    pub fn call(head: impl Into<String>, args: Vec<Expr>) -> Self {
+        Self::Call(head.into(), args)
+    }
+
+    pub fn pipeline(first: Expr, steps: Vec<Expr>) -> Self {
+        Self::Pipeline(Box::new(first), steps)
+    }
+
+    pub fn backward_pipe(lhs: impl Into<String>, rhs: Expr) -> Self {
+        Self::BackwardPipe(lhs.into(), Box::new(rhs))
+    }
+
+    pub fn parens(inner: Expr) -> Self {
+        Self::Parens(Box::new(inner))
+    }
+
+    pub fn lambda(args: impl Into<String>, body: Expr) -> Self {
+        Self::Lambda(args.into(), Box::new(body))
+    }
+
+    pub fn case(subject: impl Into<String>, branches: Vec<(String, Expr)>) -> Self {
+        Self::Case(subject.into(), branches)
+    }
+
+    /// The single-line form of this expression, if it has one.
+    fn as_line(&self) -> Option<String> {
+        match self.lines().as_slice() {
+            [line] => Some(render_line(line)),
+            _ => None,
+        }
+    }
+
+    fn lines(&self) -> Vec<Line> {
+        match self {
+            Self::Line(s) => vec![text(s.as_str())],
+            Self::Call(head, args) => {
+                let mut lines = vec![text(head.as_str())];
+                for arg in args {
+                    lines.extend(indent(arg.lines()));
+                }
+                lines
+            }
+            Self::Pipeline(first, steps) => {
+                let mut lines = first.lines();
+                for step in steps {
+                    lines.extend(indent(prefix("|> ", step.lines())));
+                }
+                lines
+            }
+            Self::BackwardPipe(lhs, rhs) => {
+                let mut lines = vec![text(format!("{} <|", lhs))];
+                lines.extend(indent(rhs.lines()));
+                lines
+            }
+            Self::List(items) if items.is_empty() => vec![text("[]")],
+            Self::List(items) => {
+                let mut lines = vec![];
+                for (i, item) in items.iter().enumerate() {
+                    lines.extend(prefix(if i == 0 { "[ " } else { ", " }, item.lines()));
+                }
+                lines.push(text("]"));
+                lines
+            }
+            Self::Tuple(items) => {
+                let single_line = items.iter().map(Self::as_line).collect::<Option<Vec<_>>>();
+
+                match single_line {
+                    Some(items) => vec![text(format!("( {} )", items.join(", ")))],
+                    None => {
+                        let mut lines = vec![];
+                        for (i, item) in items.iter().enumerate() {
+                            lines.extend(prefix(if i == 0 { "( " } else { ", " }, item.lines()));
+                        }
+                        lines.push(text(")"));
+                        lines
+                    }
+                }
+            }
+            Self::Parens(inner) => match inner.as_line() {
+                Some(line) => vec![text(format!("({})", line))],
+                None => {
+                    let mut lines = prefix("(", inner.lines());
+                    lines.push(text(")"));
+                    lines
+                }
+            },
+            Self::Lambda(args, body) => match body.as_line() {
+                Some(line) => vec![text(format!("\\{} -> {}", args, line))],
+                None => {
+                    let mut lines = vec![text(format!("\\{} ->", args))];
+                    lines.extend(indent(body.lines()));
+                    lines
+                }
+            },
+            Self::Case(subject, branches) => {
+                let mut lines = vec![text(format!("case {} of", subject))];
+                for (i, (pattern, body)) in branches.iter().enumerate() {
+                    if i > 0 {
+                        lines.push(vec![]);
+                    }
+                    let mut branch = vec![text(format!("{} ->", pattern))];
+                    branch.extend(indent(body.lines()));
+                    lines.extend(indent(branch));
+                }
+                lines
+            }
+            Self::Record(fields) if fields.is_empty() => vec![text("{}")],
+            Self::Record(fields) => {
+                let mut lines = vec![];
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    let field = match value.as_line() {
+                        Some(line) => vec![text(format!("{} = {}", name, line))],
+                        None => {
+                            let mut field = vec![text(format!("{} =", name))];
+                            field.extend(indent(value.lines()));
+                            field
+                        }
+                    };
+                    lines.extend(prefix(if i == 0 { "{ " } else { ", " }, field));
+                }
+                lines.push(text("}"));
+                lines
+            }
+        }
+    }
+
+    pub fn to_elm(&self) -> String {
+        render(&self.lines())
+    }
+}
+
+/// A top-level value with its type annotation, e.g. `decodeFoo : Json.Decode.Decoder Foo`.
+pub fn definition(annotation: &str, head: &str, body: &Expr) -> String {
+    let mut lines = vec![text(annotation), text(format!("{} =", head))];
+    lines.extend(indent(body.lines()));
+
+    render(&lines)
+}
+
+/// What generated code refers to outside of its own declarations: the modules it needs imported,
+/// the private helpers it calls and the types it mentions. Generators record these as they emit
+/// code, so that text the spec passes through, like doc comments, doesn't count.
+#[derive(Debug, Default)]
+pub struct Uses {
+    modules: RefCell<BTreeSet<String>>,
+    helpers: RefCell<BTreeSet<&'static str>>,
+    types: RefCell<BTreeSet<String>>,
+}
+
+impl Uses {
+    pub fn module(&self, module: &str) {
+        self.modules.borrow_mut().insert(module.to_string());
+    }
+
+    pub fn helper(&self, name: &'static str) {
+        self.helpers.borrow_mut().insert(name);
+    }
+
+    /// Records the modules and types named in a type or expression written in the spec, e.g. the
+    /// module `Dict` and the type `User` for `Dict.Dict String (List User)`.
+    pub fn code(&self, code: &str) {
+        let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+
+        for word in code.split(|c| !is_name_char(c)) {
+            match word.rsplit_once('.') {
+                Some((module, name))
+                    if module.starts_with(char::is_uppercase) && !name.is_empty() =>
+                {
+                    self.module(module)
+                }
+                Some(_) => {}
+                None if word.starts_with(char::is_uppercase) => {
+                    self.types.borrow_mut().insert(word.to_string());
+                }
+                None => {}
+            }
+        }
+    }
+
+    pub fn has_module(&self, module: &str) -> bool {
+        self.modules.borrow().contains(module)
+    }
+
+    /// The recorded modules, sorted.
+    pub fn modules(&self) -> Vec<String> {
+        self.modules.borrow().iter().cloned().collect()
+    }
+
+    pub fn has_helper(&self, name: &str) -> bool {
+        self.helpers.borrow().contains(name)
+    }
+
+    pub fn has_type(&self, name: &str) -> bool {
+        self.types.borrow().contains(name)
+    }
+}
@@ -0,0 +1,472 @@
+//! HTTP endpoints: typed Elm clients and a Rust route table.
+
+use crate::{
+    elm::{self, Expr, Uses},
+    graph::ElmGraphs,
+    spec::*,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum HttpMethod {
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Endpoint {
+    /// `snake_case` name, e.g. `get_user`. The Elm function is named in `camelCase`.
+    pub name: String,
+    pub method: HttpMethod,
+    /// Path with `{param}` placeholders, e.g. `/users/{id}`.
+    pub path: String,
+    /// Types of the path placeholders.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<StructField>,
+    /// Query parameters. `Maybe` parameters are left out of the URL when `Nothing`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub query: Vec<StructField>,
+    /// JSON request body.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<(String, String)>,
+    /// JSON response body. Without one, any successful response is accepted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<(String, String)>,
+    /// JSON body of unsuccessful responses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+}
+
+pub(crate) const RUST_ROUTE_STRUCT: &str = "\
+/// An endpoint of the API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    pub name: &'static str,
+    pub method: &'static str,
+    pub path: &'static str,
+}";
+
+pub(crate) const ELM_API_ERROR: &str = "\
+{-| The ways a request to an endpoint with a declared error type can fail.
+-}
+type ApiError e
+    = HttpError Http.Error
+    | ErrorResponse e
+
+
+expectJsonOrError : (Result (ApiError e) a -> msg) -> Json.Decode.Decoder e -> Json.Decode.Decoder a -> Http.Expect msg
+expectJsonOrError toMsg errorDecoder decoder =
+    Http.expectStringResponse toMsg <|
+        \\response ->
+            case response of
+                Http.BadUrl_ url ->
+                    Err (HttpError (Http.BadUrl url))
+
+                Http.Timeout_ ->
+                    Err (HttpError Http.Timeout)
+
+                Http.NetworkError_ ->
+                    Err (HttpError Http.NetworkError)
+
+                Http.BadStatus_ metadata body ->
+                    case Json.Decode.decodeString errorDecoder body of
+                        Ok error ->
+                            Err (ErrorResponse error)
+
+                        Err _ ->
+                            Err (HttpError (Http.BadStatus metadata.statusCode))
+
+                Http.GoodStatus_ _ body ->
+                    let
+                        json =
+                            if String.isEmpty body then
+                                \"null\"
+
+                            else
+                                body
+                    in
+                    Json.Decode.decodeString decoder json
+                        |> Result.mapError (HttpError << Http.BadBody << Json.Decode.errorToString)";
+
+pub(crate) const ELM_BOOL_TO_STRING: &str = "\
+boolToString : Bool -> String
+boolToString value =
+    if value then
+        \"true\"
+
+    else
+        \"false\"";
+
+impl HttpMethod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Get => "GET",
+            Self::Post => "POST",
+            Self::Put => "PUT",
+            Self::Patch => "PATCH",
+            Self::Delete => "DELETE",
+        }
+    }
+}
+
+/// A piece of an endpoint path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment<'a> {
+    Literal(&'a str),
+    Param(&'a str),
+}
+
+impl Endpoint {
+    pub fn path_segments(&self) -> Vec<PathSegment<'_>> {
+        self.path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(
+                |segment| match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                    Some(param) => PathSegment::Param(param),
+                    None => PathSegment::Literal(segment),
+                },
+            )
+            .collect()
+    }
+
+    pub fn elm_name(&self) -> String {
+        elm_camel_case(&self.name)
+    }
+
+    /// Every `(rust, elm)` type pair in this endpoint, labelled with its path for error messages.
+    pub fn type_pairs(&self) -> Vec<(String, &(String, String))> {
+        let params = self
+            .params
+            .iter()
+            .chain(&self.query)
+            .map(|param| (format!("{}.{}", self.name, param.name), &param.data));
+
+        let bodies = vec![
+            ("body", &self.body),
+            ("response", &self.response),
+            ("error", &self.error),
+        ]
+        .into_iter()
+        .filter_map(move |(label, data)| {
+            data.as_ref()
+                .map(|data| (format!("{}.{}", self.name, label), data))
+        });
+
+        params.chain(bodies).collect()
+    }
+
+    pub fn to_elm(&self, spec: &ApiSpec, graphs: &ElmGraphs, uses: &Uses) -> String {
+        uses.module("Http");
+
+        let name = self.elm_name();
+
+        let result_type = match (&self.response, &self.error) {
+            (response, Some(error)) => format!(
+                "Result (ApiError {}) {}",
+                elm_type_arg(&error.1),
+                response
+                    .as_ref()
+                    .map_or("()".into(), |data| elm_type_arg(&data.1))
+            ),
+            (Some(response), None) => format!("Result Http.Error {}", elm_type_arg(&response.1)),
+            (None, None) => "Result Http.Error ()".into(),
+        };
+
+        let mut args = vec![("baseUrl".to_string(), "String".to_string())];
+        args.extend(
+            self.params
+                .iter()
+                .chain(&self.query)
+                .map(|param| (elm_camel_case(&param.name), param.data.1.clone())),
+        );
+        if let Some(body) = &self.body {
+            args.push(("body".into(), body.1.clone()));
+        }
+        args.push(("toMsg".into(), format!("({} -> msg)", result_type)));
+        for (_, elm_type) in &args {
+            uses.code(elm_type);
+        }
+
+        let annotation = format!(
+            "{} : {} -> Cmd msg",
+            name,
+            args.iter()
+                .map(|(_, elm_type)| elm_type.as_str())
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        );
+        let head = format!(
+            "{} {}",
+            name,
+            args.iter()
+                .map(|(arg, _)| arg.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+
+        let body = match &self.body {
+            Some(data) => format!("Http.jsonBody ({} body)", elm_encoder(data, spec, uses)),
+            None => "Http.emptyBody".into(),
+        };
+
+        let expect = match (&self.response, &self.error) {
+            (response, Some(error)) => {
+                uses.helper("expectJsonOrError");
+                format!(
+                    "expectJsonOrError toMsg {} {}",
+                    elm_decoder(error, "", spec, graphs, uses),
+                    match response {
+                        Some(data) => elm_decoder(data, "", spec, graphs, uses),
+                        None => {
+                            uses.module("Json.Decode");
+                            "(Json.Decode.succeed ())".into()
+                        }
+                    }
+                )
+            }
+            (Some(response), None) => format!(
+                "Http.expectJson toMsg {}",
+                elm_decoder(response, "", spec, graphs, uses)
+            ),
+            (None, None) => "Http.expectWhatever toMsg".into(),
+        };
+
+        let request = Expr::call(
+            "Http.request",
+            vec![Expr::Record(vec![
+                (
+                    "method".into(),
+                    Expr::line(format!("\"{}\"", self.method.as_str())),
+                ),
+                ("headers".into(), Expr::line("[]")),
+                ("url".into(), Expr::line(self.elm_url(spec, uses))),
+                ("body".into(), Expr::line(body)),
+                ("expect".into(), Expr::line(expect)),
+                ("timeout".into(), Expr::line("Nothing")),
+                ("tracker".into(), Expr::line("Nothing")),
+            ])],
+        );
+
+        format!(
+            "{}{}",
+            elm_doc_comment(&self.doc, &[]),
+            elm::definition(&annotation, &head, &request)
+        )
+    }
+
+    fn elm_url(&self, spec: &ApiSpec, uses: &Uses) -> String {
+        uses.module("Url.Builder");
+
+        let segments = self
+            .path_segments()
+            .into_iter()
+            .map(|segment| match segment {
+                PathSegment::Literal(s) => format!("\"{}\"", s),
+                PathSegment::Param(name) => {
+                    let arg = elm_camel_case(name);
+                    let elm_type = self
+                        .params
+                        .iter()
+                        .find(|param| param.name == name)
+                        .map_or("String", |param| param.data.1.as_str());
+
+                    // `Url.Builder` escapes query parameters, but not path segments.
+                    match elm_url_string(elm_type, &arg, spec, uses) {
+                        Some(UrlString::Raw(s)) => {
+                            uses.module("Url");
+                            format!("Url.percentEncode {}", elm_parens(&s))
+                        }
+                        Some(UrlString::Safe(s)) => s,
+                        None => arg,
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let is_optional = |param: &StructField| param.data.1.starts_with("Maybe ");
+        let has_optional = self.query.iter().any(is_optional);
+
+        let query = self
+            .query
+            .iter()
+            .map(|param| {
+                let arg = elm_camel_case(&param.name);
+                let (elm_type, value) = match param.data.1.strip_prefix("Maybe ") {
+                    Some(elm_type) => (elm_type, "value"),
+                    None => (param.data.1.as_str(), arg.as_str()),
+                };
+                let string = match elm_url_string(elm_type, value, spec, uses) {
+                    Some(UrlString::Raw(s)) | Some(UrlString::Safe(s)) => s,
+                    None => value.to_string(),
+                };
+                let builder = format!(
+                    "Url.Builder.string \"{}\" {}",
+                    param.name,
+                    elm_parens(&string)
+                );
+
+                match (is_optional(param), has_optional) {
+                    (true, _) => format!("Maybe.map (\\value -> {}) {}", builder, arg),
+                    (false, true) => format!("Just ({})", builder),
+                    (false, false) => builder,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let query = if has_optional {
+            format!("(List.filterMap identity {})", elm_list(&query))
+        } else {
+            elm_list(&query)
+        };
+
+        format!(
+            "Url.Builder.crossOrigin baseUrl {} {}",
+            elm_list(&segments),
+            query
+        )
+    }
+
+    pub fn to_rust_route(&self) -> String {
+        format!(
+            "\
+Route {{
+    name: \"{}\",
+    method: \"{}\",
+    path: \"{}\",
+}}",
+            self.name,
+            self.method.as_str(),
+            self.path
+        )
+    }
+}
+
+/// `getUser` for `get_user`.
+pub(crate) fn elm_camel_case(name: &str) -> String {
+    let mut words = name.split('_').filter(|word| !word.is_empty());
+
+    let first = words.next().unwrap_or("").to_string();
+    words.fold(first, |mut out, word| {
+        let mut chars = word.chars();
+        if let Some(c) = chars.next() {
+            out.extend(c.to_uppercase());
+            out.push_str(chars.as_str());
+        }
+        out
+    })
+}
+
+fn elm_list(items: &[String]) -> String {
+    if items.is_empty() {
+        "[]".into()
+    } else {
+        format!("[ {} ]", items.join(", "))
+    }
+}
+
+fn elm_parens(expr: &str) -> String {
+    // `(a b)` is already wrapped, `(a) b` isn't.
+    let mut depth = 0;
+    let is_wrapped = expr.starts_with('(')
+        && expr.char_indices().all(|(i, c)| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            depth > 0 || i == expr.len() - 1
+        });
+
+    if expr.contains(' ') && !is_wrapped {
+        format!("({})", expr)
+    } else {
+        expr.to_string()
+    }
+}
+
+/// An Elm expression that produces a string for a URL.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UrlString {
+    /// Arbitrary text, which must be escaped in a path segment.
+    Raw(String),
+    /// Text that never needs escaping, like a number.
+    Safe(String),
+}
+
+/// Expression that turns `value`, of the Elm type `elm_type`, into a string for a URL. `None` if
+/// the type has no natural string form.
+pub fn elm_url_string(
+    elm_type: &str,
+    value: &str,
+    spec: &ApiSpec,
+    uses: &Uses,
+) -> Option<UrlString> {
+    match elm_type {
+        "String" => return Some(UrlString::Raw(value.to_string())),
+        "Int" => return Some(UrlString::Safe(format!("String.fromInt {}", value))),
+        "Float" => return Some(UrlString::Safe(format!("String.fromFloat {}", value))),
+        "Bool" => {
+            uses.helper("boolToString");
+            return Some(UrlString::Safe(format!("boolToString {}", value)));
+        }
+        _ => {}
+    }
+
+    let builtin_types = crate::well_known::elm_types();
+    let t = spec
+        .types
+        .iter()
+        .chain(&builtin_types)
+        .find(|t| t.name() == elm_type)?;
+
+    match t {
+        TypeSpec::Newtype { name, data, .. } => {
+            elm_url_string(&data.1, &format!("(unwrap{} {})", name, value), spec, uses)
+        }
+        TypeSpec::Enum { name, variants, .. } => match EnumRepr::of(variants) {
+            EnumRepr::String => Some(UrlString::Raw(format!(
+                "{}ToString {}",
+                elm_lower_camel(name),
+                value
+            ))),
+            EnumRepr::Int => Some(UrlString::Safe(format!(
+                "String.fromInt ({}ToInt {})",
+                elm_lower_camel(name),
+                value
+            ))),
+            EnumRepr::Tagged => None,
+        },
+        _ => None,
+    }
+}
+
+impl ApiSpec {
+    /// Rust route table for the endpoints, if there are any.
+    pub fn endpoints_to_rust(&self) -> Option<String> {
+        if self.endpoints.is_empty() {
+            return None;
+        }
+
+        let vis = self.rust.visibility.to_rust();
+
+        let routes = self
+            .endpoints
+            .iter()
+            .map(|endpoint| format!("{},\n", endpoint.to_rust_route()))
+            .collect::<String>();
+
+        Some(format!(
+            "{route}\n\n{vis}const ROUTES: &[Route] = &[\n{routes}];",
+            route = RUST_ROUTE_STRUCT.replace("pub ", vis),
+            vis = vis,
+            routes = indent_lines(&routes, 1) + "\n"
+        ))
+    }
+}
@@ -0,0 +1,402 @@
+//! References between the types of a spec.
+
+use crate::spec::{ApiSpec, TypeSpec};
+
+/// Which types of a spec refer to which, by their Elm types.
+#[derive(Debug, Clone)]
+pub struct TypeGraph {
+    names: Vec<String>,
+    /// `edges[i]` holds the indices of the types that type `i` refers to, in order of first use.
+    edges: Vec<Vec<usize>>,
+    /// `scc[i]` identifies the strongly connected component type `i` belongs to.
+    scc: Vec<usize>,
+}
+
+/// The names in an Elm type, e.g. `Maybe`, `Time.Posix` and `User` in `Maybe (Time.Posix, User)`.
+fn elm_idents(elm_type: &str) -> impl Iterator<Item = &str> {
+    elm_type
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+        .filter(|ident| !ident.is_empty())
+}
+
+impl TypeGraph {
+    pub fn new(types: &[TypeSpec]) -> Self {
+        let names = types
+            .iter()
+            .map(|t| t.name().to_string())
+            .collect::<Vec<_>>();
+
+        let edges = types
+            .iter()
+            .map(|t| {
+                let mut refs = vec![];
+                for (_, (_, elm_type)) in t.type_pairs() {
+                    for ident in elm_idents(elm_type) {
+                        if let Some(i) = names.iter().position(|name| name == ident) {
+                            if !refs.contains(&i) {
+                                refs.push(i);
+                            }
+                        }
+                    }
+                }
+                refs
+            })
+            .collect::<Vec<_>>();
+
+        let scc = tarjan(&edges);
+
+        Self { names, edges, scc }
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    /// Names of the types `name` refers to.
+    pub fn references(&self, name: &str) -> Vec<&str> {
+        self.index(name)
+            .map(|i| {
+                self.edges[i]
+                    .iter()
+                    .map(|&j| self.names[j].as_str())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Whether `name` refers to itself, directly or through other types.
+    pub fn is_recursive(&self, name: &str) -> bool {
+        match self.index(name) {
+            Some(i) => {
+                self.edges[i].contains(&i)
+                    || (0..self.names.len()).any(|j| j != i && self.scc[j] == self.scc[i])
+            }
+            None => false,
+        }
+    }
+
+    /// Whether a reference from `from` to `to` closes a cycle, i.e. `to` also refers back to
+    /// `from`.
+    pub fn in_same_cycle(&self, from: &str, to: &str) -> bool {
+        match (self.index(from), self.index(to)) {
+            (Some(i), Some(j)) => self.scc[i] == self.scc[j] && self.is_recursive(from),
+            _ => false,
+        }
+    }
+
+    /// The recursive types, grouped into sets that refer to each other.
+    pub fn cycles(&self) -> Vec<Vec<&str>> {
+        let mut cycles: Vec<(usize, Vec<&str>)> = vec![];
+
+        for (i, name) in self.names.iter().enumerate() {
+            if !self.is_recursive(name) {
+                continue;
+            }
+            match cycles.iter_mut().find(|(scc, _)| *scc == self.scc[i]) {
+                Some((_, names)) => names.push(name),
+                None => cycles.push((self.scc[i], vec![name])),
+            }
+        }
+
+        cycles.into_iter().map(|(_, names)| names).collect()
+    }
+
+    /// Every type after the types it refers to, otherwise in spec order. Types on a cycle can't
+    /// all come after each other; they are ordered by first use.
+    pub fn topological_order(&self) -> Vec<&str> {
+        let mut visited = vec![false; self.names.len()];
+        let mut order = vec![];
+
+        for i in 0..self.names.len() {
+            self.visit(i, &mut visited, &mut order);
+        }
+
+        order.into_iter().map(|i| self.names[i].as_str()).collect()
+    }
+
+    fn visit(&self, i: usize, visited: &mut [bool], order: &mut Vec<usize>) {
+        if visited[i] {
+            return;
+        }
+        visited[i] = true;
+
+        for &j in &self.edges[i] {
+            self.visit(j, visited, order);
+        }
+        order.push(i);
+    }
+
+    /// `roots` and every type they refer to, directly or indirectly, in spec order.
+    pub fn dependencies(&self, roots: &[&str]) -> Vec<&str> {
+        let mut visited = vec![false; self.names.len()];
+        let mut order = vec![];
+
+        for root in roots {
+            if let Some(i) = self.index(root) {
+                self.visit(i, &mut visited, &mut order);
+            }
+        }
+
+        self.names
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| visited[*i])
+            .map(|(_, name)| name.as_str())
+            .collect()
+    }
+
+    /// Types that no other type refers to, in spec order. A type referring to itself still
+    /// counts as unreferenced.
+    pub fn unreferenced(&self) -> Vec<&str> {
+        self.names
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| {
+                !self
+                    .edges
+                    .iter()
+                    .enumerate()
+                    .any(|(j, refs)| j != *i && refs.contains(i))
+            })
+            .map(|(_, name)| name.as_str())
+            .collect()
+    }
+
+    /// The graph in Graphviz DOT format, with unreferenced types in bold.
+    pub fn to_dot(&self) -> String {
+        let unreferenced = self.unreferenced();
+
+        let nodes = self.names.iter().map(|name| {
+            if unreferenced.contains(&name.as_str()) {
+                format!("    \"{}\" [style=bold];", name)
+            } else {
+                format!("    \"{}\";", name)
+            }
+        });
+
+        let edges = self.names.iter().enumerate().flat_map(|(i, name)| {
+            self.edges[i]
+                .iter()
+                .map(move |&j| format!("    \"{}\" -> \"{}\";", name, self.names[j]))
+        });
+
+        let lines = nodes.chain(edges).collect::<Vec<_>>();
+
+        format!("digraph types {{\n{}\n}}\n", lines.join("\n"))
+    }
+}
+
+/// Matches a type name against a pattern in which `*` stands for any run of characters and `?`
+/// for any single character.
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match (pattern.first(), name.first()) {
+            (None, None) => true,
+            (Some('*'), _) => {
+                matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..]))
+            }
+            (Some('?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+            (Some(p), Some(n)) if p == n => matches(&pattern[1..], &name[1..]),
+            _ => false,
+        }
+    }
+
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    matches(&pattern, &name)
+}
+
+/// Tarjan's strongly connected components algorithm. Returns the component of each node.
+fn tarjan(edges: &[Vec<usize>]) -> Vec<usize> {
+    struct State<'a> {
+        edges: &'a [Vec<usize>],
+        next_index: usize,
+        index: Vec<Option<usize>>,
+        low_link: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        scc: Vec<usize>,
+        next_scc: usize,
+    }
+
+    fn connect(state: &mut State, v: usize) {
+        state.index[v] = Some(state.next_index);
+        state.low_link[v] = state.next_index;
+        state.next_index += 1;
+        state.stack.push(v);
+        state.on_stack[v] = true;
+
+        for &w in &state.edges[v] {
+            match state.index[w] {
+                None => {
+                    connect(state, w);
+                    state.low_link[v] = state.low_link[v].min(state.low_link[w]);
+                }
+                Some(w_index) if state.on_stack[w] => {
+                    state.low_link[v] = state.low_link[v].min(w_index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(state.low_link[v]) == state.index[v] {
+            while let Some(w) = state.stack.pop() {
+                state.on_stack[w] = false;
+                state.scc[w] = state.next_scc;
+                if w == v {
+                    break;
+                }
+            }
+            state.next_scc += 1;
+        }
+    }
+
+    let n = edges.len();
+    let mut state = State {
+        edges,
+        next_index: 0,
+        index: vec![None; n],
+        low_link: vec![0; n],
+        stack: vec![],
+        on_stack: vec![false; n],
+        scc: vec![0; n],
+        next_scc: 0,
+    };
+
+    for v in 0..n {
+        if state.index[v].is_none() {
+            connect(&mut state, v);
+        }
+    }
+
+    state.scc
+}
+
+impl ApiSpec {
+    pub fn type_graph(&self) -> TypeGraph {
+        TypeGraph::new(&self.types)
+    }
+
+    /// This spec with only the types named in `roots` and the types they depend on. Endpoints,
+    /// channels, ports and upgrades that use any other type of the spec are left out.
+    pub fn subset(&self, roots: &[&str]) -> ApiSpec {
+        let graph = self.type_graph();
+        let keep = graph.dependencies(roots);
+
+        self.with_types(|t| keep.contains(&t.name()))
+    }
+
+    /// This spec without the types whose names match any of `patterns`, and without the
+    /// endpoints, channels, ports and upgrades that use them.
+    pub fn without(&self, patterns: &[&str]) -> ApiSpec {
+        self.with_types(|t| !patterns.iter().any(|p| matches_pattern(p, t.name())))
+    }
+
+    /// This spec with the types `keep` accepts, and the items that only use those.
+    fn with_types(&self, keep: impl Fn(&TypeSpec) -> bool) -> ApiSpec {
+        let dropped = self
+            .types
+            .iter()
+            .filter(|t| !keep(t))
+            .map(TypeSpec::name)
+            .collect::<Vec<_>>();
+        let is_dropped = |name: &str| dropped.contains(&name);
+
+        ApiSpec {
+            types: self.types.iter().filter(|t| keep(t)).cloned().collect(),
+            endpoints: self
+                .endpoints
+                .iter()
+                .filter(|e| {
+                    !e.type_pairs()
+                        .iter()
+                        .any(|(_, (_, elm_type))| elm_idents(elm_type).any(is_dropped))
+                })
+                .cloned()
+                .collect(),
+            channels: self
+                .channels
+                .iter()
+                .filter(|c| !is_dropped(&c.client) && !is_dropped(&c.server))
+                .cloned()
+                .collect(),
+            ports: self
+                .ports
+                .iter()
+                .filter(|p| !is_dropped(&p.payload))
+                .cloned()
+                .collect(),
+            upgrades: self
+                .upgrades
+                .iter()
+                .filter(|u| !is_dropped(&u.from) && !is_dropped(&u.to))
+                .cloned()
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// This spec with every type after the types it depends on.
+    pub fn sorted_topologically(&self) -> ApiSpec {
+        let graph = self.type_graph();
+
+        ApiSpec {
+            types: graph
+                .topological_order()
+                .into_iter()
+                .filter_map(|name| self.types.iter().find(|t| t.name() == name))
+                .cloned()
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// References between just the types that become Elm type aliases: structs and aliases.
+    pub fn elm_alias_graph(&self) -> TypeGraph {
+        let aliases = self
+            .types
+            .iter()
+            .filter(|t| matches!(t, TypeSpec::Struct { .. } | TypeSpec::Alias { .. }))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        TypeGraph::new(&aliases)
+    }
+
+    /// The graphs Elm generation consults, built once so that each type and field doesn't
+    /// rebuild them.
+    pub fn elm_graphs(&self) -> ElmGraphs {
+        let aliases = self.elm_alias_graph();
+        let boxed_structs = self
+            .types
+            .iter()
+            .filter(|t| matches!(t, TypeSpec::Struct { .. }) && aliases.is_recursive(t.name()))
+            .map(|t| t.name().to_string())
+            .collect();
+
+        ElmGraphs {
+            types: self.type_graph(),
+            aliases,
+            boxed_structs,
+        }
+    }
+}
+
+/// The type graphs of a spec that Elm generation and validation look things up in.
+#[derive(Debug, Clone)]
+pub struct ElmGraphs {
+    /// See [`ApiSpec::type_graph`].
+    pub types: TypeGraph,
+    /// See [`ApiSpec::elm_alias_graph`].
+    pub aliases: TypeGraph,
+    boxed_structs: Vec<String>,
+}
+
+impl ElmGraphs {
+    /// Elm forbids type aliases that refer to themselves without going through a custom type.
+    /// Structs on such a cycle are generated as single-constructor custom types instead.
+    pub fn is_boxed_struct(&self, name: &str) -> bool {
+        self.boxed_structs.iter().any(|n| n == name)
+    }
+}
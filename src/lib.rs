@@ -1,6 +1,30 @@
+mod channel;
+mod diff;
+mod dsl;
+pub mod elm;
+mod endpoint;
+mod graph;
+mod port;
+pub mod primitives;
+mod server;
+mod source;
 mod spec;
+mod ts;
+mod upgrade;
+mod validate;
+pub mod well_known;
 
+pub use channel::*;
+pub use diff::*;
+pub use dsl::*;
+pub use endpoint::*;
+pub use graph::*;
+pub use port::*;
+pub use source::*;
 pub use spec::*;
+pub use ts::*;
+pub use upgrade::*;
+pub use validate::*;
 
 #[cfg(test)]
 mod tests {
@@ -20,6 +44,7 @@ mod tests {
         let spec = ApiSpec {
             module: "TestType".into(),
             types: vec![],
+            ..Default::default()
         };
 
         compare_strings("", spec.to_rust());
@@ -30,21 +55,10 @@ mod tests {
         let spec = ApiSpec {
             module: "TestType".into(),
             types: vec![],
+            ..Default::default()
         };
 
-        compare_strings(
-            "\
-module TestType exposing ()
-
-import Json.Decode
-import Json.Decode.Extra
-import Json.Decode.Pipeline
-import Json.Encode
-import Json.Encode.Extra
-
-",
-            spec.to_elm(),
-        );
+        compare_strings("module TestType exposing ()\n", spec.to_elm());
     }
 
     fn create_spec_struct_simple() -> ApiSpec {
@@ -56,13 +70,18 @@ import Json.Encode.Extra
                     StructField {
                         name: "foo".into(),
                         data: ("u32".into(), "Int".into()),
+                        doc: None,
                     },
                     StructField {
                         name: "bar".into(),
                         data: ("String".into(), "String".into()),
+                        doc: None,
                     },
                 ],
+                doc: None,
+                targets: None,
             }],
+            ..Default::default()
         }
     }
 
@@ -84,28 +103,30 @@ pub struct TestStruct {
 module TestType exposing (TestStruct, decodeTestStruct, encodeTestStruct)
 
 import Json.Decode
-import Json.Decode.Extra
 import Json.Decode.Pipeline
 import Json.Encode
-import Json.Encode.Extra
+
 
 type alias TestStruct =
     { foo : Int
     , bar : String
     }
 
+
 decodeTestStruct : Json.Decode.Decoder TestStruct
 decodeTestStruct =
     Json.Decode.succeed TestStruct
         |> Json.Decode.Pipeline.required \"foo\" Json.Decode.int
         |> Json.Decode.Pipeline.required \"bar\" Json.Decode.string
 
+
 encodeTestStruct : TestStruct -> Json.Encode.Value
 encodeTestStruct record =
     Json.Encode.object
-        [ (\"foo\", Json.Encode.int <| record.foo)
-        , (\"bar\", Json.Encode.string <| record.bar)
-        ]";
+        [ ( \"foo\", Json.Encode.int <| record.foo )
+        , ( \"bar\", Json.Encode.string <| record.bar )
+        ]
+";
 
         compare_strings(expected, create_spec_struct_simple().to_elm());
     }
@@ -118,8 +139,12 @@ encodeTestStruct record =
                 fields: vec![StructField {
                     name: "foo".into(),
                     data: ("Vec<u32>".into(), "List Int".into()),
+                    doc: None,
                 }],
+                doc: None,
+                targets: None,
             }],
+            ..Default::default()
         }
     }
 
@@ -140,25 +165,27 @@ pub struct TestStruct {
 module TestType exposing (TestStruct, decodeTestStruct, encodeTestStruct)
 
 import Json.Decode
-import Json.Decode.Extra
 import Json.Decode.Pipeline
 import Json.Encode
-import Json.Encode.Extra
+
 
 type alias TestStruct =
-    { foo : (List Int)
+    { foo : List Int
     }
 
+
 decodeTestStruct : Json.Decode.Decoder TestStruct
 decodeTestStruct =
     Json.Decode.succeed TestStruct
         |> Json.Decode.Pipeline.required \"foo\" (Json.Decode.list Json.Decode.int)
 
+
 encodeTestStruct : TestStruct -> Json.Encode.Value
 encodeTestStruct record =
     Json.Encode.object
-        [ (\"foo\", Json.Encode.list Json.Encode.int <| record.foo)
-        ]";
+        [ ( \"foo\", Json.Encode.list Json.Encode.int <| record.foo )
+        ]
+";
 
         compare_strings(expected, create_spec_struct_with_vec().to_elm());
     }
@@ -171,8 +198,12 @@ encodeTestStruct record =
                 fields: vec![StructField {
                     name: "foo".into(),
                     data: ("Option<u32>".into(), "Maybe Int".into()),
+                    doc: None,
                 }],
+                doc: None,
+                targets: None,
             }],
+            ..Default::default()
         }
     }
 
@@ -193,25 +224,28 @@ pub struct TestStruct {
 module TestType exposing (TestStruct, decodeTestStruct, encodeTestStruct)
 
 import Json.Decode
-import Json.Decode.Extra
 import Json.Decode.Pipeline
 import Json.Encode
 import Json.Encode.Extra
 
+
 type alias TestStruct =
-    { foo : (Maybe Int)
+    { foo : Maybe Int
     }
 
+
 decodeTestStruct : Json.Decode.Decoder TestStruct
 decodeTestStruct =
     Json.Decode.succeed TestStruct
         |> Json.Decode.Pipeline.required \"foo\" (Json.Decode.nullable Json.Decode.int)
 
+
 encodeTestStruct : TestStruct -> Json.Encode.Value
 encodeTestStruct record =
     Json.Encode.object
-        [ (\"foo\", Json.Encode.Extra.maybe Json.Encode.int <| record.foo)
-        ]";
+        [ ( \"foo\", Json.Encode.Extra.maybe Json.Encode.int <| record.foo )
+        ]
+";
 
         compare_strings(expected, create_spec_struct_with_option().to_elm());
     }
@@ -225,17 +259,27 @@ encodeTestStruct record =
                     EnumVariant {
                         name: "Foo".into(),
                         data: EnumVariantData::None,
+                        doc: None,
+                        discriminant: None,
                     },
                     EnumVariant {
                         name: "Bar".into(),
                         data: EnumVariantData::None,
+                        doc: None,
+                        discriminant: None,
                     },
                     EnumVariant {
                         name: "Qux".into(),
                         data: EnumVariantData::None,
+                        doc: None,
+                        discriminant: None,
                     },
                 ],
+                doc: None,
+                targets: None,
+                unknown_fallback: false,
             }],
+            ..Default::default()
         }
     }
 
@@ -243,7 +287,6 @@ encodeTestStruct record =
     fn rust_enum_simple() {
         let expected = "\
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-#[serde(tag = \"var\", content = \"vardata\")]
 pub enum TestEnum {
     Foo,
     Bar,
@@ -256,49 +299,127 @@ pub enum TestEnum {
     #[test]
     fn elm_enum_simple() {
         let expected = "\
-module TestType exposing (TestEnum(..), decodeTestEnum, encodeTestEnum)
+module TestType exposing (TestEnum(..), decodeTestEnum, encodeTestEnum, allTestEnum, testEnumToString, testEnumFromString)
 
 import Json.Decode
-import Json.Decode.Extra
-import Json.Decode.Pipeline
 import Json.Encode
-import Json.Encode.Extra
+
 
 type TestEnum
     = Foo
     | Bar
     | Qux
 
-decodeTestEnum : Json.Decode.Decoder TestEnum
-decodeTestEnum =
-    Json.Decode.oneOf
-        [ Json.Decode.Extra.when (Json.Decode.field \"var\" Json.Decode.string) ((==) \"Foo\") <|
-            Json.Decode.succeed Foo
-        , Json.Decode.Extra.when (Json.Decode.field \"var\" Json.Decode.string) ((==) \"Bar\") <|
-            Json.Decode.succeed Bar
-        , Json.Decode.Extra.when (Json.Decode.field \"var\" Json.Decode.string) ((==) \"Qux\") <|
-            Json.Decode.succeed Qux
-        ]
 
-encodeTestEnum : TestEnum -> Json.Encode.Value
-encodeTestEnum var =
+allTestEnum : List TestEnum
+allTestEnum =
+    [ Foo
+    , Bar
+    , Qux
+    ]
+
+
+testEnumToString : TestEnum -> String
+testEnumToString var =
     case var of
         Foo ->
-            Json.Encode.object
-                [ ( \"var\", Json.Encode.string \"Foo\" )
-                ]
+            \"Foo\"
+
         Bar ->
-            Json.Encode.object
-                [ ( \"var\", Json.Encode.string \"Bar\" )
-                ]
+            \"Bar\"
+
         Qux ->
-            Json.Encode.object
-                [ ( \"var\", Json.Encode.string \"Qux\" )
-                ]";
+            \"Qux\"
+
+
+testEnumFromString : String -> Maybe TestEnum
+testEnumFromString value =
+    case value of
+        \"Foo\" ->
+            Just Foo
+
+        \"Bar\" ->
+            Just Bar
+
+        \"Qux\" ->
+            Just Qux
+
+        _ ->
+            Nothing
+
+
+decodeTestEnum : Json.Decode.Decoder TestEnum
+decodeTestEnum =
+    Json.Decode.string
+        |> Json.Decode.andThen
+            (\\value ->
+                case testEnumFromString value of
+                    Just var ->
+                        Json.Decode.succeed var
+
+                    Nothing ->
+                        Json.Decode.fail (\"Unknown TestEnum variant: \" ++ value)
+            )
+
+
+encodeTestEnum : TestEnum -> Json.Encode.Value
+encodeTestEnum var =
+    Json.Encode.string <| testEnumToString var
+";
 
         compare_strings(expected, create_spec_enum_simple().to_elm());
     }
 
+    #[test]
+    fn elm_enum_simple_string_codec() {
+        let spec = create_spec_enum_simple();
+        let (graphs, uses) = (spec.elm_graphs(), elm::Uses::default());
+
+        // serde writes unit-only enums as plain strings, without a `var` object.
+        assert!(!spec.to_rust().contains("#[serde(tag"));
+        compare_strings(
+            "\
+encodeTestEnum : TestEnum -> Json.Encode.Value
+encodeTestEnum var =
+    Json.Encode.string <| testEnumToString var",
+            spec.types[0].to_elm_encoder(&spec, &graphs, &uses),
+        );
+
+        // Strings that aren't a variant name fail to decode.
+        let decoder = spec.types[0].to_elm_decoder(&spec, &graphs, &uses);
+        assert!(decoder.starts_with(
+            "decodeTestEnum : Json.Decode.Decoder TestEnum\ndecodeTestEnum =\n    Json.Decode.string\n"
+        ));
+        assert!(decoder.contains("case testEnumFromString value of\n"));
+        assert!(decoder.contains(
+            "Nothing ->\n                        Json.Decode.fail (\"Unknown TestEnum variant: \" ++ value)\n"
+        ));
+    }
+
+    #[test]
+    fn validate_empty_enum() {
+        let yaml = "\
+module: TestType
+types:
+  - Enum:
+      name: Empty
+      variants: []
+";
+        let spec = ApiSpec::parse(yaml, SpecFormat::Yaml).unwrap();
+
+        assert_eq!(
+            spec.validate(),
+            vec![Diagnostic::error(
+                "enum `Empty` has no variants, which Elm can't decode or encode"
+            )
+            .at(Some(Span {
+                line: 4,
+                column: 13,
+                len: 5
+            }))]
+        );
+    }
+
     fn create_spec_enum_complex() -> ApiSpec {
         ApiSpec {
             module: "TestType".into(),
@@ -308,10 +429,14 @@ encodeTestEnum var =
                     EnumVariant {
                         name: "Foo".into(),
                         data: EnumVariantData::None,
+                        doc: None,
+                        discriminant: None,
                     },
                     EnumVariant {
                         name: "Bar".into(),
                         data: EnumVariantData::Single(("bool".into(), "Bool".into())),
+                        doc: None,
+                        discriminant: None,
                     },
                     EnumVariant {
                         name: "Qux".into(),
@@ -319,15 +444,23 @@ encodeTestEnum var =
                             StructField {
                                 name: "sub1".into(),
                                 data: ("u32".into(), "Int".into()),
+                                doc: None,
                             },
                             StructField {
                                 name: "sub2".into(),
                                 data: ("String".into(), "String".into()),
+                                doc: None,
                             },
                         ]),
+                        doc: None,
+                        discriminant: None,
                     },
                 ],
+                doc: None,
+                targets: None,
+                unknown_fallback: false,
             }],
+            ..Default::default()
         }
     }
 
@@ -354,37 +487,48 @@ pub enum TestEnum {
 module TestType exposing (TestEnum(..), decodeTestEnum, encodeTestEnum)
 
 import Json.Decode
-import Json.Decode.Extra
 import Json.Decode.Pipeline
 import Json.Encode
-import Json.Encode.Extra
+
 
 type alias TestEnumQux =
     { sub1 : Int
     , sub2 : String
     }
 
+
 decodeTestEnumQux : Json.Decode.Decoder TestEnumQux
 decodeTestEnumQux =
     Json.Decode.succeed TestEnumQux
         |> Json.Decode.Pipeline.required \"sub1\" Json.Decode.int
         |> Json.Decode.Pipeline.required \"sub2\" Json.Decode.string
 
+
 type TestEnum
     = Foo
     | Bar Bool
     | Qux TestEnumQux
 
+
 decodeTestEnum : Json.Decode.Decoder TestEnum
 decodeTestEnum =
-    Json.Decode.oneOf
-        [ Json.Decode.Extra.when (Json.Decode.field \"var\" Json.Decode.string) ((==) \"Foo\") <|
-            Json.Decode.succeed Foo
-        , Json.Decode.Extra.when (Json.Decode.field \"var\" Json.Decode.string) ((==) \"Bar\") <|
-            Json.Decode.map Bar (Json.Decode.field \"vardata\" <| Json.Decode.bool)
-        , Json.Decode.Extra.when (Json.Decode.field \"var\" Json.Decode.string) ((==) \"Qux\") <|
-            Json.Decode.map Qux (Json.Decode.field \"vardata\" <| decodeTestEnumQux)
-        ]
+    Json.Decode.field \"var\" Json.Decode.string
+        |> Json.Decode.andThen
+            (\\tag ->
+                case tag of
+                    \"Foo\" ->
+                        Json.Decode.succeed Foo
+
+                    \"Bar\" ->
+                        Json.Decode.map Bar (Json.Decode.field \"vardata\" <| Json.Decode.bool)
+
+                    \"Qux\" ->
+                        Json.Decode.map Qux (Json.Decode.field \"vardata\" <| decodeTestEnumQux)
+
+                    _ ->
+                        Json.Decode.fail (\"Unknown TestEnum variant: \" ++ tag)
+            )
+
 
 encodeTestEnum : TestEnum -> Json.Encode.Value
 encodeTestEnum var =
@@ -393,23 +537,94 @@ encodeTestEnum var =
             Json.Encode.object
                 [ ( \"var\", Json.Encode.string \"Foo\" )
                 ]
+
         Bar value ->
             Json.Encode.object
                 [ ( \"var\", Json.Encode.string \"Bar\" )
                 , ( \"vardata\", Json.Encode.bool <| value )
                 ]
+
         Qux record ->
             Json.Encode.object
                 [ ( \"var\", Json.Encode.string \"Qux\" )
-                , ( \"vardata\", Json.Encode.object
-                    [ ( \"sub1\", Json.Encode.int <| record.sub1 )
-                    , ( \"sub2\", Json.Encode.string <| record.sub2 )
-                    ] )
-                ]";
+                , ( \"vardata\"
+                  , Json.Encode.object
+                        [ ( \"sub1\", Json.Encode.int <| record.sub1 )
+                        , ( \"sub2\", Json.Encode.string <| record.sub2 )
+                        ]
+                  )
+                ]
+";
 
         compare_strings(expected, create_spec_enum_complex().to_elm());
     }
 
+    #[test]
+    fn elm_tagged_enum_decoder() {
+        let spec = ApiSpec {
+            module: "TestType".into(),
+            types: vec![TypeSpec::Enum {
+                name: "Command".into(),
+                variants: vec![
+                    EnumVariant {
+                        name: "Ping".into(),
+                        data: EnumVariantData::None,
+                        doc: None,
+                        discriminant: None,
+                    },
+                    EnumVariant {
+                        name: "Tag".into(),
+                        data: EnumVariantData::Single(("Vec<String>".into(), "List String".into())),
+                        doc: None,
+                        discriminant: None,
+                    },
+                    EnumVariant {
+                        name: "Move".into(),
+                        data: EnumVariantData::Struct(vec![StructField {
+                            name: "x".into(),
+                            data: ("i32".into(), "Int".into()),
+                            doc: None,
+                        }]),
+                        doc: None,
+                        discriminant: None,
+                    },
+                ],
+                doc: None,
+                targets: None,
+                unknown_fallback: false,
+            }],
+            ..Default::default()
+        };
+
+        // Unit variants have no `vardata`, and unknown tags fail rather than pick a variant.
+        let expected = "\
+decodeCommand : Json.Decode.Decoder Command
+decodeCommand =
+    Json.Decode.field \"var\" Json.Decode.string
+        |> Json.Decode.andThen
+            (\\tag ->
+                case tag of
+                    \"Ping\" ->
+                        Json.Decode.succeed Ping
+
+                    \"Tag\" ->
+                        Json.Decode.map Tag (Json.Decode.field \"vardata\" <| (Json.Decode.list Json.Decode.string))
+
+                    \"Move\" ->
+                        Json.Decode.map Move (Json.Decode.field \"vardata\" <| decodeCommandMove)
+
+                    _ ->
+                        Json.Decode.fail (\"Unknown Command variant: \" ++ tag)
+            )";
+
+        let decoder =
+            spec.types[0].to_elm_decoder(&spec, &spec.elm_graphs(), &elm::Uses::default());
+        compare_strings(expected, decoder);
+        assert!(spec
+            .to_elm()
+            .contains("\ndecodeCommandMove : Json.Decode.Decoder CommandMove\n"));
+    }
+
     fn create_spec_enum_with_vec() -> ApiSpec {
         ApiSpec {
             module: "TestType".into(),
@@ -419,16 +634,25 @@ encodeTestEnum var =
                     EnumVariant {
                         name: "Bar".into(),
                         data: EnumVariantData::Single(("Vec<u32>".into(), "List Int".into())),
+                        doc: None,
+                        discriminant: None,
                     },
                     EnumVariant {
                         name: "Qux".into(),
                         data: EnumVariantData::Struct(vec![StructField {
                             name: "sub1".into(),
                             data: ("Vec<bool>".into(), "List Bool".into()),
+                            doc: None,
                         }]),
+                        doc: None,
+                        discriminant: None,
                     },
                 ],
+                doc: None,
+                targets: None,
+                unknown_fallback: false,
             }],
+            ..Default::default()
         }
     }
 
@@ -453,32 +677,42 @@ pub enum TestEnum {
 module TestType exposing (TestEnum(..), decodeTestEnum, encodeTestEnum)
 
 import Json.Decode
-import Json.Decode.Extra
 import Json.Decode.Pipeline
 import Json.Encode
-import Json.Encode.Extra
+
 
 type alias TestEnumQux =
-    { sub1 : (List Bool)
+    { sub1 : List Bool
     }
 
+
 decodeTestEnumQux : Json.Decode.Decoder TestEnumQux
 decodeTestEnumQux =
     Json.Decode.succeed TestEnumQux
         |> Json.Decode.Pipeline.required \"sub1\" (Json.Decode.list Json.Decode.bool)
 
+
 type TestEnum
     = Bar (List Int)
     | Qux TestEnumQux
 
+
 decodeTestEnum : Json.Decode.Decoder TestEnum
 decodeTestEnum =
-    Json.Decode.oneOf
-        [ Json.Decode.Extra.when (Json.Decode.field \"var\" Json.Decode.string) ((==) \"Bar\") <|
-            Json.Decode.map Bar (Json.Decode.field \"vardata\" <| (Json.Decode.list Json.Decode.int))
-        , Json.Decode.Extra.when (Json.Decode.field \"var\" Json.Decode.string) ((==) \"Qux\") <|
-            Json.Decode.map Qux (Json.Decode.field \"vardata\" <| decodeTestEnumQux)
-        ]
+    Json.Decode.field \"var\" Json.Decode.string
+        |> Json.Decode.andThen
+            (\\tag ->
+                case tag of
+                    \"Bar\" ->
+                        Json.Decode.map Bar (Json.Decode.field \"vardata\" <| (Json.Decode.list Json.Decode.int))
+
+                    \"Qux\" ->
+                        Json.Decode.map Qux (Json.Decode.field \"vardata\" <| decodeTestEnumQux)
+
+                    _ ->
+                        Json.Decode.fail (\"Unknown TestEnum variant: \" ++ tag)
+            )
+
 
 encodeTestEnum : TestEnum -> Json.Encode.Value
 encodeTestEnum var =
@@ -488,13 +722,17 @@ encodeTestEnum var =
                 [ ( \"var\", Json.Encode.string \"Bar\" )
                 , ( \"vardata\", Json.Encode.list Json.Encode.int <| value )
                 ]
+
         Qux record ->
             Json.Encode.object
                 [ ( \"var\", Json.Encode.string \"Qux\" )
-                , ( \"vardata\", Json.Encode.object
-                    [ ( \"sub1\", Json.Encode.list Json.Encode.bool <| record.sub1 )
-                    ] )
-                ]";
+                , ( \"vardata\"
+                  , Json.Encode.object
+                        [ ( \"sub1\", Json.Encode.list Json.Encode.bool <| record.sub1 )
+                        ]
+                  )
+                ]
+";
 
         compare_strings(expected, create_spec_enum_with_vec().to_elm());
     }
@@ -508,16 +746,25 @@ encodeTestEnum var =
                     EnumVariant {
                         name: "Bar".into(),
                         data: EnumVariantData::Single(("Option<u32>".into(), "Maybe Int".into())),
+                        doc: None,
+                        discriminant: None,
                     },
                     EnumVariant {
                         name: "Qux".into(),
                         data: EnumVariantData::Struct(vec![StructField {
                             name: "sub1".into(),
                             data: ("Option<bool>".into(), "Maybe Bool".into()),
+                            doc: None,
                         }]),
+                        doc: None,
+                        discriminant: None,
                     },
                 ],
+                doc: None,
+                targets: None,
+                unknown_fallback: false,
             }],
+            ..Default::default()
         }
     }
 
@@ -542,32 +789,43 @@ pub enum TestEnum {
 module TestType exposing (TestEnum(..), decodeTestEnum, encodeTestEnum)
 
 import Json.Decode
-import Json.Decode.Extra
 import Json.Decode.Pipeline
 import Json.Encode
 import Json.Encode.Extra
 
+
 type alias TestEnumQux =
-    { sub1 : (Maybe Bool)
+    { sub1 : Maybe Bool
     }
 
+
 decodeTestEnumQux : Json.Decode.Decoder TestEnumQux
 decodeTestEnumQux =
     Json.Decode.succeed TestEnumQux
         |> Json.Decode.Pipeline.required \"sub1\" (Json.Decode.nullable Json.Decode.bool)
 
+
 type TestEnum
     = Bar (Maybe Int)
     | Qux TestEnumQux
 
+
 decodeTestEnum : Json.Decode.Decoder TestEnum
 decodeTestEnum =
-    Json.Decode.oneOf
-        [ Json.Decode.Extra.when (Json.Decode.field \"var\" Json.Decode.string) ((==) \"Bar\") <|
-            Json.Decode.map Bar (Json.Decode.field \"vardata\" <| (Json.Decode.nullable Json.Decode.int))
-        , Json.Decode.Extra.when (Json.Decode.field \"var\" Json.Decode.string) ((==) \"Qux\") <|
-            Json.Decode.map Qux (Json.Decode.field \"vardata\" <| decodeTestEnumQux)
-        ]
+    Json.Decode.field \"var\" Json.Decode.string
+        |> Json.Decode.andThen
+            (\\tag ->
+                case tag of
+                    \"Bar\" ->
+                        Json.Decode.map Bar (Json.Decode.field \"vardata\" <| (Json.Decode.nullable Json.Decode.int))
+
+                    \"Qux\" ->
+                        Json.Decode.map Qux (Json.Decode.field \"vardata\" <| decodeTestEnumQux)
+
+                    _ ->
+                        Json.Decode.fail (\"Unknown TestEnum variant: \" ++ tag)
+            )
+
 
 encodeTestEnum : TestEnum -> Json.Encode.Value
 encodeTestEnum var =
@@ -577,14 +835,3859 @@ encodeTestEnum var =
                 [ ( \"var\", Json.Encode.string \"Bar\" )
                 , ( \"vardata\", Json.Encode.Extra.maybe Json.Encode.int <| value )
                 ]
+
         Qux record ->
             Json.Encode.object
                 [ ( \"var\", Json.Encode.string \"Qux\" )
-                , ( \"vardata\", Json.Encode.object
-                    [ ( \"sub1\", Json.Encode.Extra.maybe Json.Encode.bool <| record.sub1 )
-                    ] )
-                ]";
+                , ( \"vardata\"
+                  , Json.Encode.object
+                        [ ( \"sub1\", Json.Encode.Extra.maybe Json.Encode.bool <| record.sub1 )
+                        ]
+                  )
+                ]
+";
 
         compare_strings(expected, create_spec_enum_with_option().to_elm());
     }
+
+    #[test]
+    fn elm_json_only() {
+        let mut spec = create_spec_enum_with_option();
+        spec.elm.json_only = true;
+
+        let expected = "\
+module TestType exposing (TestEnum(..), decodeTestEnum, encodeTestEnum)
+
+import Json.Decode
+import Json.Encode
+
+
+type alias TestEnumQux =
+    { sub1 : Maybe Bool
+    }
+
+
+decodeTestEnumQux : Json.Decode.Decoder TestEnumQux
+decodeTestEnumQux =
+    Json.Decode.succeed TestEnumQux
+        |> required \"sub1\" (Json.Decode.nullable Json.Decode.bool)
+
+
+type TestEnum
+    = Bar (Maybe Int)
+    | Qux TestEnumQux
+
+
+decodeTestEnum : Json.Decode.Decoder TestEnum
+decodeTestEnum =
+    Json.Decode.field \"var\" Json.Decode.string
+        |> Json.Decode.andThen
+            (\\tag ->
+                case tag of
+                    \"Bar\" ->
+                        Json.Decode.map Bar (Json.Decode.field \"vardata\" <| (Json.Decode.nullable Json.Decode.int))
+
+                    \"Qux\" ->
+                        Json.Decode.map Qux (Json.Decode.field \"vardata\" <| decodeTestEnumQux)
+
+                    _ ->
+                        Json.Decode.fail (\"Unknown TestEnum variant: \" ++ tag)
+            )
+
+
+encodeTestEnum : TestEnum -> Json.Encode.Value
+encodeTestEnum var =
+    case var of
+        Bar value ->
+            Json.Encode.object
+                [ ( \"var\", Json.Encode.string \"Bar\" )
+                , ( \"vardata\", encodeMaybe Json.Encode.int <| value )
+                ]
+
+        Qux record ->
+            Json.Encode.object
+                [ ( \"var\", Json.Encode.string \"Qux\" )
+                , ( \"vardata\"
+                  , Json.Encode.object
+                        [ ( \"sub1\", encodeMaybe Json.Encode.bool <| record.sub1 )
+                        ]
+                  )
+                ]
+
+
+required : String -> Json.Decode.Decoder a -> Json.Decode.Decoder (a -> b) -> Json.Decode.Decoder b
+required key valueDecoder =
+    Json.Decode.map2 (|>) (Json.Decode.field key valueDecoder)
+
+
+encodeMaybe : (a -> Json.Encode.Value) -> Maybe a -> Json.Encode.Value
+encodeMaybe encoder =
+    Maybe.map encoder >> Maybe.withDefault Json.Encode.null
+";
+
+        compare_strings(expected, spec.to_elm());
+    }
+
+    #[test]
+    fn elm_decoder_map_n() {
+        let mut spec = create_spec_struct_simple();
+        spec.elm.decoder_style = DecoderStyle::MapN;
+        spec.types.push(TypeSpec::Struct {
+            name: "TestWide".into(),
+            fields: (1..=10)
+                .map(|i| StructField {
+                    name: format!("f{}", i),
+                    data: ("u32".into(), "Int".into()),
+                    doc: None,
+                })
+                .collect(),
+            doc: None,
+            targets: None,
+        });
+
+        let expected = "\
+module TestType exposing (TestStruct, decodeTestStruct, encodeTestStruct, TestWide, decodeTestWide, encodeTestWide)
+
+import Json.Decode
+import Json.Decode.Extra
+import Json.Encode
+
+
+type alias TestStruct =
+    { foo : Int
+    , bar : String
+    }
+
+
+decodeTestStruct : Json.Decode.Decoder TestStruct
+decodeTestStruct =
+    Json.Decode.map2 TestStruct
+        (Json.Decode.field \"foo\" Json.Decode.int)
+        (Json.Decode.field \"bar\" Json.Decode.string)
+
+
+encodeTestStruct : TestStruct -> Json.Encode.Value
+encodeTestStruct record =
+    Json.Encode.object
+        [ ( \"foo\", Json.Encode.int <| record.foo )
+        , ( \"bar\", Json.Encode.string <| record.bar )
+        ]
+
+
+type alias TestWide =
+    { f1 : Int
+    , f2 : Int
+    , f3 : Int
+    , f4 : Int
+    , f5 : Int
+    , f6 : Int
+    , f7 : Int
+    , f8 : Int
+    , f9 : Int
+    , f10 : Int
+    }
+
+
+decodeTestWide : Json.Decode.Decoder TestWide
+decodeTestWide =
+    Json.Decode.map8 TestWide
+        (Json.Decode.field \"f1\" Json.Decode.int)
+        (Json.Decode.field \"f2\" Json.Decode.int)
+        (Json.Decode.field \"f3\" Json.Decode.int)
+        (Json.Decode.field \"f4\" Json.Decode.int)
+        (Json.Decode.field \"f5\" Json.Decode.int)
+        (Json.Decode.field \"f6\" Json.Decode.int)
+        (Json.Decode.field \"f7\" Json.Decode.int)
+        (Json.Decode.field \"f8\" Json.Decode.int)
+        |> Json.Decode.Extra.andMap (Json.Decode.field \"f9\" Json.Decode.int)
+        |> Json.Decode.Extra.andMap (Json.Decode.field \"f10\" Json.Decode.int)
+
+
+encodeTestWide : TestWide -> Json.Encode.Value
+encodeTestWide record =
+    Json.Encode.object
+        [ ( \"f1\", Json.Encode.int <| record.f1 )
+        , ( \"f2\", Json.Encode.int <| record.f2 )
+        , ( \"f3\", Json.Encode.int <| record.f3 )
+        , ( \"f4\", Json.Encode.int <| record.f4 )
+        , ( \"f5\", Json.Encode.int <| record.f5 )
+        , ( \"f6\", Json.Encode.int <| record.f6 )
+        , ( \"f7\", Json.Encode.int <| record.f7 )
+        , ( \"f8\", Json.Encode.int <| record.f8 )
+        , ( \"f9\", Json.Encode.int <| record.f9 )
+        , ( \"f10\", Json.Encode.int <| record.f10 )
+        ]
+";
+
+        compare_strings(expected, spec.to_elm());
+    }
+
+    #[test]
+    fn rust_rustfmt_stable() {
+        let spec = ApiSpec {
+            module: "TestType".into(),
+            types: vec![
+                TypeSpec::Struct {
+                    name: "TestEmpty".into(),
+                    fields: vec![],
+                    doc: None,
+                    targets: None,
+                },
+                TypeSpec::Struct {
+                    name: "TestStruct".into(),
+                    fields: vec![
+                        StructField {
+                            name: "foo".into(),
+                            data: ("HashMap<String,Vec < u8 >>".into(), "List Int".into()),
+                            doc: None,
+                        },
+                        StructField {
+                            name: "bar".into(),
+                            data: ("( u8,[u8;4] )".into(), "List Int".into()),
+                            doc: None,
+                        },
+                    ],
+                    doc: None,
+                    targets: None,
+                },
+                TypeSpec::Enum {
+                    name: "TestEnum".into(),
+                    variants: vec![
+                        EnumVariant {
+                            name: "Foo".into(),
+                            data: EnumVariantData::Struct(vec![]),
+                            doc: None,
+                            discriminant: None,
+                        },
+                        EnumVariant {
+                            name: "Bar".into(),
+                            data: EnumVariantData::Single((
+                                "std :: string :: String".into(),
+                                "String".into(),
+                            )),
+                            doc: None,
+                            discriminant: None,
+                        },
+                    ],
+                    doc: None,
+                    targets: None,
+                    unknown_fallback: false,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let expected = "\
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TestEmpty {}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TestStruct {
+    pub foo: HashMap<String, Vec<u8>>,
+    pub bar: (u8, [u8; 4]),
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = \"var\", content = \"vardata\")]
+pub enum TestEnum {
+    Foo {},
+    Bar(std::string::String),
+}";
+
+        compare_strings(expected, spec.to_rust());
+    }
+
+    fn create_spec_recursive() -> ApiSpec {
+        ApiSpec {
+            module: "TestType".into(),
+            types: vec![
+                TypeSpec::Struct {
+                    name: "Folder".into(),
+                    fields: vec![
+                        StructField {
+                            name: "name".into(),
+                            data: ("String".into(), "String".into()),
+                            doc: None,
+                        },
+                        StructField {
+                            name: "children".into(),
+                            data: ("Vec<Folder>".into(), "List Folder".into()),
+                            doc: None,
+                        },
+                    ],
+                    doc: None,
+                    targets: None,
+                },
+                TypeSpec::Struct {
+                    name: "Comment".into(),
+                    fields: vec![StructField {
+                        name: "replies".into(),
+                        data: ("Vec<Reply>".into(), "List Reply".into()),
+                        doc: None,
+                    }],
+                    doc: None,
+                    targets: None,
+                },
+                TypeSpec::Enum {
+                    name: "Reply".into(),
+                    variants: vec![
+                        EnumVariant {
+                            name: "Deleted".into(),
+                            data: EnumVariantData::None,
+                            doc: None,
+                            discriminant: None,
+                        },
+                        EnumVariant {
+                            name: "Posted".into(),
+                            data: EnumVariantData::Single(("Comment".into(), "Comment".into())),
+                            doc: None,
+                            discriminant: None,
+                        },
+                    ],
+                    doc: None,
+                    targets: None,
+                    unknown_fallback: false,
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn elm_recursive() {
+        let expected = "\
+module TestType exposing (Folder(..), decodeFolder, encodeFolder, folderName, folderChildren, Comment, decodeComment, encodeComment, Reply(..), decodeReply, encodeReply)
+
+import Json.Decode
+import Json.Decode.Pipeline
+import Json.Encode
+
+
+type Folder
+    = Folder
+        { name : String
+        , children : List Folder
+        }
+
+
+folderName : Folder -> String
+folderName (Folder record) =
+    record.name
+
+
+folderChildren : Folder -> List Folder
+folderChildren (Folder record) =
+    record.children
+
+
+decodeFolder : Json.Decode.Decoder Folder
+decodeFolder =
+    Json.Decode.succeed (\\name children -> Folder { name = name, children = children })
+        |> Json.Decode.Pipeline.required \"name\" Json.Decode.string
+        |> Json.Decode.Pipeline.required \"children\" (Json.Decode.list (Json.Decode.lazy (\\_ -> decodeFolder)))
+
+
+encodeFolder : Folder -> Json.Encode.Value
+encodeFolder (Folder record) =
+    Json.Encode.object
+        [ ( \"name\", Json.Encode.string <| record.name )
+        , ( \"children\", Json.Encode.list encodeFolder <| record.children )
+        ]
+
+
+type alias Comment =
+    { replies : List Reply
+    }
+
+
+decodeComment : Json.Decode.Decoder Comment
+decodeComment =
+    Json.Decode.succeed Comment
+        |> Json.Decode.Pipeline.required \"replies\" (Json.Decode.list (Json.Decode.lazy (\\_ -> decodeReply)))
+
+
+encodeComment : Comment -> Json.Encode.Value
+encodeComment record =
+    Json.Encode.object
+        [ ( \"replies\", Json.Encode.list encodeReply <| record.replies )
+        ]
+
+
+type Reply
+    = Deleted
+    | Posted Comment
+
+
+decodeReply : Json.Decode.Decoder Reply
+decodeReply =
+    Json.Decode.field \"var\" Json.Decode.string
+        |> Json.Decode.andThen
+            (\\tag ->
+                case tag of
+                    \"Deleted\" ->
+                        Json.Decode.succeed Deleted
+
+                    \"Posted\" ->
+                        Json.Decode.map Posted (Json.Decode.field \"vardata\" <| (Json.Decode.lazy (\\_ -> decodeComment)))
+
+                    _ ->
+                        Json.Decode.fail (\"Unknown Reply variant: \" ++ tag)
+            )
+
+
+encodeReply : Reply -> Json.Encode.Value
+encodeReply var =
+    case var of
+        Deleted ->
+            Json.Encode.object
+                [ ( \"var\", Json.Encode.string \"Deleted\" )
+                ]
+
+        Posted value ->
+            Json.Encode.object
+                [ ( \"var\", Json.Encode.string \"Posted\" )
+                , ( \"vardata\", encodeComment <| value )
+                ]
+";
+
+        compare_strings(expected, create_spec_recursive().to_elm());
+    }
+
+    #[test]
+    fn validate_recursive() {
+        let mut spec = create_spec_recursive();
+        assert_eq!(spec.validate(), vec![]);
+
+        spec.types.push(TypeSpec::Alias {
+            name: "Forest".into(),
+            data: ("Vec<Forest>".into(), "List Forest".into()),
+            doc: None,
+            targets: None,
+        });
+
+        assert_eq!(
+            spec.validate(),
+            vec![Diagnostic::error(
+                "the aliases `Forest` refer to themselves, which Elm doesn't allow"
+            )
+            .with_hint("make one of them a Newtype or Struct")]
+        );
+    }
+
+    #[test]
+    fn type_graph() {
+        let mut spec = create_spec_recursive();
+        spec.types.insert(
+            0,
+            TypeSpec::Alias {
+                name: "Folders".into(),
+                data: ("Vec<Folder>".into(), "List Folder".into()),
+                doc: None,
+                targets: None,
+            },
+        );
+        let graph = spec.type_graph();
+
+        assert_eq!(graph.references("Folders"), vec!["Folder"]);
+        assert_eq!(
+            graph.cycles(),
+            vec![vec!["Folder"], vec!["Comment", "Reply"]]
+        );
+        assert_eq!(
+            graph.topological_order(),
+            vec!["Folder", "Folders", "Reply", "Comment"]
+        );
+        assert_eq!(graph.unreferenced(), vec!["Folders"]);
+
+        let names = |spec: ApiSpec| {
+            spec.types
+                .iter()
+                .map(|t| t.name().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(spec.subset(&["Comment"])), vec!["Comment", "Reply"]);
+        assert_eq!(
+            names(spec.sorted_topologically()),
+            vec!["Folder", "Folders", "Reply", "Comment"]
+        );
+
+        let expected = "\
+digraph types {
+    \"Folders\" [style=bold];
+    \"Folder\";
+    \"Comment\";
+    \"Reply\";
+    \"Folders\" -> \"Folder\";
+    \"Folder\" -> \"Folder\";
+    \"Comment\" -> \"Reply\";
+    \"Reply\" -> \"Comment\";
+}
+";
+
+        compare_strings(expected, graph.to_dot());
+    }
+
+    #[test]
+    fn targets() {
+        let mut spec = create_spec_struct_simple();
+        spec.types.push(TypeSpec::Newtype {
+            name: "Secret".into(),
+            data: ("String".into(), "String".into()),
+            doc: None,
+            targets: Some(vec![Target::Rust]),
+        });
+
+        assert!(spec.to_rust().contains("pub struct Secret("));
+        assert!(!spec.to_elm().contains("Secret"));
+        assert_eq!(spec.validate(), vec![]);
+
+        if let TypeSpec::Struct { fields, .. } = &mut spec.types[0] {
+            fields[1].data = ("Secret".into(), "Secret".into());
+        }
+
+        assert_eq!(
+            spec.validate(),
+            vec![Diagnostic::error(
+                "`TestStruct` is generated for Elm, but refers to `Secret`, which isn't"
+            )]
+        );
+
+        spec.ports.push(Port {
+            name: "save".into(),
+            direction: PortDirection::Outgoing,
+            payload: "TestStruct".into(),
+            doc: None,
+        });
+
+        assert_eq!(
+            spec.validate(),
+            vec![
+                Diagnostic::error(
+                    "`TestStruct` is generated for Elm, but refers to `Secret`, which isn't"
+                ),
+                Diagnostic::error(
+                    "`TestStruct` is generated for TypeScript, but refers to `Secret`, which isn't"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn targets_without_ts() {
+        let mut spec = create_spec_struct_simple();
+        spec.types.push(TypeSpec::Newtype {
+            name: "Secret".into(),
+            data: ("String".into(), "String".into()),
+            doc: None,
+            targets: Some(vec![Target::Rust, Target::Elm]),
+        });
+        if let TypeSpec::Struct { fields, .. } = &mut spec.types[0] {
+            fields[1].data = ("Secret".into(), "Secret".into());
+        }
+
+        // No port carries `TestStruct`, so no TypeScript is generated for it.
+        assert_eq!(spec.validate(), vec![]);
+        assert!(spec.to_elm().contains("bar : Secret"));
+    }
+
+    #[test]
+    fn type_patterns() {
+        assert!(matches_pattern("User", "User"));
+        assert!(matches_pattern("User*", "UserId"));
+        assert!(matches_pattern("*Id", "UserId"));
+        assert!(matches_pattern("U?er", "User"));
+        assert!(!matches_pattern("User", "UserId"));
+        assert!(!matches_pattern("*Id", "Identity"));
+
+        let names = create_spec_recursive()
+            .without(&["C*", "Folder"])
+            .types
+            .iter()
+            .map(|t| t.name().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Reply"]);
+    }
+
+    fn create_spec_endpoints() -> ApiSpec {
+        ApiSpec {
+            module: "Api".into(),
+            types: vec![
+                TypeSpec::Newtype {
+                    name: "UserId".into(),
+                    data: ("u32".into(), "Int".into()),
+                    doc: None,
+                    targets: None,
+                },
+                TypeSpec::Enum {
+                    name: "Role".into(),
+                    variants: vec![
+                        EnumVariant {
+                            name: "Admin".into(),
+                            data: EnumVariantData::None,
+                            doc: None,
+                            discriminant: None,
+                        },
+                        EnumVariant {
+                            name: "Member".into(),
+                            data: EnumVariantData::None,
+                            doc: None,
+                            discriminant: None,
+                        },
+                    ],
+                    doc: None,
+                    targets: None,
+                    unknown_fallback: false,
+                },
+                TypeSpec::Struct {
+                    name: "User".into(),
+                    fields: vec![
+                        StructField {
+                            name: "id".into(),
+                            data: ("UserId".into(), "UserId".into()),
+                            doc: None,
+                        },
+                        StructField {
+                            name: "name".into(),
+                            data: ("String".into(), "String".into()),
+                            doc: None,
+                        },
+                    ],
+                    doc: None,
+                    targets: None,
+                },
+            ],
+            endpoints: vec![
+                Endpoint {
+                    name: "get_user".into(),
+                    method: HttpMethod::Get,
+                    path: "/users/{user_id}".into(),
+                    params: vec![StructField {
+                        name: "user_id".into(),
+                        data: ("UserId".into(), "UserId".into()),
+                        doc: None,
+                    }],
+                    query: vec![],
+                    body: None,
+                    response: Some(("User".into(), "User".into())),
+                    error: Some(("String".into(), "String".into())),
+                    doc: Some("Fetches a single user.".into()),
+                },
+                Endpoint {
+                    name: "list_users".into(),
+                    method: HttpMethod::Get,
+                    path: "/users".into(),
+                    params: vec![],
+                    query: vec![
+                        StructField {
+                            name: "role".into(),
+                            data: ("Option<Role>".into(), "Maybe Role".into()),
+                            doc: None,
+                        },
+                        StructField {
+                            name: "search".into(),
+                            data: ("String".into(), "String".into()),
+                            doc: None,
+                        },
+                    ],
+                    body: None,
+                    response: Some(("Vec<User>".into(), "List User".into())),
+                    error: None,
+                    doc: None,
+                },
+                Endpoint {
+                    name: "update_user".into(),
+                    method: HttpMethod::Put,
+                    path: "/users/{user_id}".into(),
+                    params: vec![StructField {
+                        name: "user_id".into(),
+                        data: ("UserId".into(), "UserId".into()),
+                        doc: None,
+                    }],
+                    query: vec![],
+                    body: Some(("User".into(), "User".into())),
+                    response: None,
+                    error: None,
+                    doc: None,
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn elm_endpoints() {
+        let spec = create_spec_endpoints();
+
+        let expected = "\
+module Api exposing (UserId, decodeUserId, encodeUserId, unwrapUserId, Role(..), decodeRole, encodeRole, allRole, roleToString, roleFromString, User, decodeUser, encodeUser, ApiError(..), getUser, listUsers, updateUser)
+
+import Http
+import Json.Decode
+import Json.Decode.Pipeline
+import Json.Encode
+import Url.Builder
+
+
+type UserId
+    = UserId Int
+
+
+unwrapUserId : UserId -> Int
+unwrapUserId (UserId value) =
+    value
+
+
+decodeUserId : Json.Decode.Decoder UserId
+decodeUserId =
+    Json.Decode.map UserId Json.Decode.int
+
+
+encodeUserId : UserId -> Json.Encode.Value
+encodeUserId (UserId value) =
+    Json.Encode.int <| value
+
+
+type Role
+    = Admin
+    | Member
+
+
+allRole : List Role
+allRole =
+    [ Admin
+    , Member
+    ]
+
+
+roleToString : Role -> String
+roleToString var =
+    case var of
+        Admin ->
+            \"Admin\"
+
+        Member ->
+            \"Member\"
+
+
+roleFromString : String -> Maybe Role
+roleFromString value =
+    case value of
+        \"Admin\" ->
+            Just Admin
+
+        \"Member\" ->
+            Just Member
+
+        _ ->
+            Nothing
+
+
+decodeRole : Json.Decode.Decoder Role
+decodeRole =
+    Json.Decode.string
+        |> Json.Decode.andThen
+            (\\value ->
+                case roleFromString value of
+                    Just var ->
+                        Json.Decode.succeed var
+
+                    Nothing ->
+                        Json.Decode.fail (\"Unknown Role variant: \" ++ value)
+            )
+
+
+encodeRole : Role -> Json.Encode.Value
+encodeRole var =
+    Json.Encode.string <| roleToString var
+
+
+type alias User =
+    { id : UserId
+    , name : String
+    }
+
+
+decodeUser : Json.Decode.Decoder User
+decodeUser =
+    Json.Decode.succeed User
+        |> Json.Decode.Pipeline.required \"id\" decodeUserId
+        |> Json.Decode.Pipeline.required \"name\" Json.Decode.string
+
+
+encodeUser : User -> Json.Encode.Value
+encodeUser record =
+    Json.Encode.object
+        [ ( \"id\", encodeUserId <| record.id )
+        , ( \"name\", Json.Encode.string <| record.name )
+        ]
+
+
+{-| Fetches a single user.
+-}
+getUser : String -> UserId -> (Result (ApiError String) User -> msg) -> Cmd msg
+getUser baseUrl userId toMsg =
+    Http.request
+        { method = \"GET\"
+        , headers = []
+        , url = Url.Builder.crossOrigin baseUrl [ \"users\", String.fromInt (unwrapUserId userId) ] []
+        , body = Http.emptyBody
+        , expect = expectJsonOrError toMsg Json.Decode.string decodeUser
+        , timeout = Nothing
+        , tracker = Nothing
+        }
+
+
+listUsers : String -> Maybe Role -> String -> (Result Http.Error (List User) -> msg) -> Cmd msg
+listUsers baseUrl role search toMsg =
+    Http.request
+        { method = \"GET\"
+        , headers = []
+        , url = Url.Builder.crossOrigin baseUrl [ \"users\" ] (List.filterMap identity [ Maybe.map (\\value -> Url.Builder.string \"role\" (roleToString value)) role, Just (Url.Builder.string \"search\" search) ])
+        , body = Http.emptyBody
+        , expect = Http.expectJson toMsg (Json.Decode.list decodeUser)
+        , timeout = Nothing
+        , tracker = Nothing
+        }
+
+
+updateUser : String -> UserId -> User -> (Result Http.Error () -> msg) -> Cmd msg
+updateUser baseUrl userId body toMsg =
+    Http.request
+        { method = \"PUT\"
+        , headers = []
+        , url = Url.Builder.crossOrigin baseUrl [ \"users\", String.fromInt (unwrapUserId userId) ] []
+        , body = Http.jsonBody (encodeUser body)
+        , expect = Http.expectWhatever toMsg
+        , timeout = Nothing
+        , tracker = Nothing
+        }
+
+
+{-| The ways a request to an endpoint with a declared error type can fail.
+-}
+type ApiError e
+    = HttpError Http.Error
+    | ErrorResponse e
+
+
+expectJsonOrError : (Result (ApiError e) a -> msg) -> Json.Decode.Decoder e -> Json.Decode.Decoder a -> Http.Expect msg
+expectJsonOrError toMsg errorDecoder decoder =
+    Http.expectStringResponse toMsg <|
+        \\response ->
+            case response of
+                Http.BadUrl_ url ->
+                    Err (HttpError (Http.BadUrl url))
+
+                Http.Timeout_ ->
+                    Err (HttpError Http.Timeout)
+
+                Http.NetworkError_ ->
+                    Err (HttpError Http.NetworkError)
+
+                Http.BadStatus_ metadata body ->
+                    case Json.Decode.decodeString errorDecoder body of
+                        Ok error ->
+                            Err (ErrorResponse error)
+
+                        Err _ ->
+                            Err (HttpError (Http.BadStatus metadata.statusCode))
+
+                Http.GoodStatus_ _ body ->
+                    let
+                        json =
+                            if String.isEmpty body then
+                                \"null\"
+
+                            else
+                                body
+                    in
+                    Json.Decode.decodeString decoder json
+                        |> Result.mapError (HttpError << Http.BadBody << Json.Decode.errorToString)
+";
+
+        compare_strings(expected, spec.to_elm());
+    }
+
+    #[test]
+    fn rust_endpoints() {
+        let spec = create_spec_endpoints();
+
+        let expected = "\
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct UserId(pub u32);
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum Role {
+    Admin,
+    Member,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct User {
+    pub id: UserId,
+    pub name: String,
+}
+
+/// An endpoint of the API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    pub name: &'static str,
+    pub method: &'static str,
+    pub path: &'static str,
+}
+
+pub const ROUTES: &[Route] = &[
+    Route {
+        name: \"get_user\",
+        method: \"GET\",
+        path: \"/users/{user_id}\",
+    },
+    Route {
+        name: \"list_users\",
+        method: \"GET\",
+        path: \"/users\",
+    },
+    Route {
+        name: \"update_user\",
+        method: \"PUT\",
+        path: \"/users/{user_id}\",
+    },
+];";
+
+        compare_strings(expected, spec.to_rust());
+    }
+
+    #[test]
+    fn rust_axum_server() {
+        let mut spec = create_spec_endpoints();
+        spec.types.clear();
+        spec.rust.server = Some(ServerFramework::Axum);
+
+        let expected = "\
+/// An endpoint of the API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    pub name: &'static str,
+    pub method: &'static str,
+    pub path: &'static str,
+}
+
+pub const ROUTES: &[Route] = &[
+    Route {
+        name: \"get_user\",
+        method: \"GET\",
+        path: \"/users/{user_id}\",
+    },
+    Route {
+        name: \"list_users\",
+        method: \"GET\",
+        path: \"/users\",
+    },
+    Route {
+        name: \"update_user\",
+        method: \"PUT\",
+        path: \"/users/{user_id}\",
+    },
+];
+
+/// Handlers for the endpoints of the API. Serve an implementation with [`router`].
+pub trait Server: Send + Sync + 'static {
+    /// Fetches a single user.
+    fn get_user(
+        &self,
+        user_id: UserId,
+    ) -> impl std::future::Future<Output = Result<User, (axum::http::StatusCode, String)>> + Send;
+
+    fn list_users(
+        &self,
+        role: Option<Role>,
+        search: String,
+    ) -> impl std::future::Future<Output = Result<Vec<User>, axum::http::StatusCode>> + Send;
+
+    fn update_user(
+        &self,
+        user_id: UserId,
+        body: User,
+    ) -> impl std::future::Future<Output = Result<(), axum::http::StatusCode>> + Send;
+}
+
+/// An axum router that serves the endpoints with `server`.
+pub fn router<S: Server>(server: S) -> axum::Router {
+    axum::Router::new()
+        .route(
+            \"/users/{user_id}\",
+            axum::routing::get(handlers::get_user::<S>).put(handlers::update_user::<S>),
+        )
+        .route(\"/users\", axum::routing::get(handlers::list_users::<S>))
+        .with_state(std::sync::Arc::new(server))
+}
+
+mod handlers {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(serde::Deserialize)]
+    pub(super) struct ListUsersQuery {
+        role: Option<Role>,
+        search: String,
+    }
+
+    pub(super) async fn get_user<S: Server>(
+        axum::extract::State(server): axum::extract::State<Arc<S>>,
+        axum::extract::Path(user_id): axum::extract::Path<UserId>,
+    ) -> Result<axum::Json<User>, (axum::http::StatusCode, axum::Json<String>)> {
+        server
+            .get_user(user_id)
+            .await
+            .map(axum::Json)
+            .map_err(|(status, error)| (status, axum::Json(error)))
+    }
+
+    pub(super) async fn list_users<S: Server>(
+        axum::extract::State(server): axum::extract::State<Arc<S>>,
+        axum::extract::Query(query): axum::extract::Query<ListUsersQuery>,
+    ) -> Result<axum::Json<Vec<User>>, axum::http::StatusCode> {
+        server.list_users(query.role, query.search).await.map(axum::Json)
+    }
+
+    pub(super) async fn update_user<S: Server>(
+        axum::extract::State(server): axum::extract::State<Arc<S>>,
+        axum::extract::Path(user_id): axum::extract::Path<UserId>,
+        axum::Json(body): axum::Json<User>,
+    ) -> Result<(), axum::http::StatusCode> {
+        server.update_user(user_id, body).await
+    }
+}";
+
+        compare_strings(expected, spec.to_rust());
+    }
+
+    #[test]
+    fn rust_axum_server_long_names() {
+        let mut spec = create_spec_endpoints();
+        spec.types.clear();
+        spec.endpoints.truncate(2);
+        spec.endpoints[1].name = "list_users_with_their_roles_and_matching_the_search".into();
+        spec.endpoints[1].error = Some(("String".into(), "String".into()));
+        spec.rust.server = Some(ServerFramework::Axum);
+
+        let expected = "\
+/// An endpoint of the API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    pub name: &'static str,
+    pub method: &'static str,
+    pub path: &'static str,
+}
+
+pub const ROUTES: &[Route] = &[
+    Route {
+        name: \"get_user\",
+        method: \"GET\",
+        path: \"/users/{user_id}\",
+    },
+    Route {
+        name: \"list_users_with_their_roles_and_matching_the_search\",
+        method: \"GET\",
+        path: \"/users\",
+    },
+];
+
+/// Handlers for the endpoints of the API. Serve an implementation with [`router`].
+pub trait Server: Send + Sync + 'static {
+    /// Fetches a single user.
+    fn get_user(
+        &self,
+        user_id: UserId,
+    ) -> impl std::future::Future<Output = Result<User, (axum::http::StatusCode, String)>> + Send;
+
+    fn list_users_with_their_roles_and_matching_the_search(
+        &self,
+        role: Option<Role>,
+        search: String,
+    ) -> impl std::future::Future<Output = Result<Vec<User>, (axum::http::StatusCode, String)>> + Send;
+}
+
+/// An axum router that serves the endpoints with `server`.
+pub fn router<S: Server>(server: S) -> axum::Router {
+    axum::Router::new()
+        .route(
+            \"/users/{user_id}\",
+            axum::routing::get(handlers::get_user::<S>),
+        )
+        .route(
+            \"/users\",
+            axum::routing::get(handlers::list_users_with_their_roles_and_matching_the_search::<S>),
+        )
+        .with_state(std::sync::Arc::new(server))
+}
+
+mod handlers {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(serde::Deserialize)]
+    pub(super) struct ListUsersWithTheirRolesAndMatchingTheSearchQuery {
+        role: Option<Role>,
+        search: String,
+    }
+
+    pub(super) async fn get_user<S: Server>(
+        axum::extract::State(server): axum::extract::State<Arc<S>>,
+        axum::extract::Path(user_id): axum::extract::Path<UserId>,
+    ) -> Result<axum::Json<User>, (axum::http::StatusCode, axum::Json<String>)> {
+        server
+            .get_user(user_id)
+            .await
+            .map(axum::Json)
+            .map_err(|(status, error)| (status, axum::Json(error)))
+    }
+
+    pub(super) async fn list_users_with_their_roles_and_matching_the_search<S: Server>(
+        axum::extract::State(server): axum::extract::State<Arc<S>>,
+        axum::extract::Query(query): axum::extract::Query<
+            ListUsersWithTheirRolesAndMatchingTheSearchQuery,
+        >,
+    ) -> Result<axum::Json<Vec<User>>, (axum::http::StatusCode, axum::Json<String>)> {
+        server
+            .list_users_with_their_roles_and_matching_the_search(query.role, query.search)
+            .await
+            .map(axum::Json)
+            .map_err(|(status, error)| (status, axum::Json(error)))
+    }
+}";
+
+        compare_strings(expected, spec.to_rust());
+    }
+
+    #[test]
+    fn validate_endpoints() {
+        let mut spec = create_spec_endpoints();
+        assert_eq!(spec.validate(), vec![]);
+
+        spec.endpoints[0].path = "users/{id}".into();
+        spec.endpoints[1].query[0].data = ("User".into(), "User".into());
+
+        assert_eq!(
+            spec.validate(),
+            vec![
+                Diagnostic::error("the path of endpoint `get_user` must start with `/`"),
+                Diagnostic::error(
+                    "the path of endpoint `get_user` has a placeholder `{id}`, but no param of \
+                    that name"
+                )
+                .with_hint("add a param named `id`"),
+                Diagnostic::error("`get_user.user_id` doesn't appear in the path `users/{id}`")
+                    .with_hint("add `{user_id}` to the path, or make it a query param"),
+                Diagnostic::error(
+                    "`list_users.role` is an Elm `User`, which can't be put in a query string"
+                ),
+            ]
+        );
+
+        let mut spec = create_spec_endpoints();
+        spec.rust.server = Some(ServerFramework::Axum);
+        if let TypeSpec::Struct { name, .. } = &mut spec.types[2] {
+            *name = "Server".into();
+        }
+        assert!(spec.validate().contains(&Diagnostic::error(
+            "the type `Server` clashes with the Rust code generated for the endpoints"
+        )));
+
+        let mut spec = create_spec_endpoints();
+        spec.rust.server = Some(ServerFramework::Axum);
+        spec.endpoints[1].query[1].name = "body".into();
+        spec.endpoints[2].path = "/users/{query}".into();
+        spec.endpoints[2].params[0].name = "query".into();
+        spec.endpoints[2].query = spec.endpoints[1].query.clone();
+        if let TypeSpec::Newtype { name, .. } = &mut spec.types[0] {
+            *name = "handlers".into();
+        }
+        let clashes = spec
+            .validate()
+            .into_iter()
+            .filter(|d| d.message.contains("clashes"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            clashes,
+            vec![
+                Diagnostic::error(
+                    "`update_user.body` clashes with the `body` of the generated Elm client"
+                )
+                .with_hint("rename the param"),
+                Diagnostic::error(
+                    "`update_user.query` clashes with the `query` of the generated server code"
+                )
+                .with_hint("rename the param"),
+                Diagnostic::error(
+                    "`update_user.body` clashes with the `body` of the generated server code"
+                )
+                .with_hint("rename the param"),
+                Diagnostic::error(
+                    "the type `handlers` clashes with the Rust code generated for the endpoints"
+                ),
+            ]
+        );
+
+        let mut spec = create_spec_endpoints();
+        spec.endpoints[0].path = "/users/{to_msg}".into();
+        spec.endpoints[0].params[0].name = "to_msg".into();
+        spec.endpoints[1].query[1].name = "value".into();
+        assert_eq!(
+            spec.validate(),
+            vec![
+                Diagnostic::error(
+                    "`get_user.to_msg` clashes with the `toMsg` of the generated Elm client"
+                )
+                .with_hint("rename the param"),
+                Diagnostic::error(
+                    "`list_users.value` clashes with the `value` of the generated Elm client"
+                )
+                .with_hint("rename the param"),
+            ]
+        );
+    }
+
+    #[test]
+    fn subset_drops_items() {
+        let endpoint_names = |spec: &ApiSpec| {
+            spec.endpoints
+                .iter()
+                .map(|e| e.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        let spec = create_spec_endpoints();
+        let subset = spec.subset(&["User"]);
+        assert_eq!(endpoint_names(&subset), "get_user, update_user");
+        assert!(!subset.to_elm().contains("Role"));
+        assert_eq!(subset.validate(), vec![]);
+        assert_eq!(endpoint_names(&spec.without(&["UserId"])), "list_users");
+
+        let mut spec = create_spec_upgrades();
+        spec.channels.push(Channel {
+            name: "users".into(),
+            client: "UserV1".into(),
+            server: "UserV2".into(),
+            doc: None,
+        });
+        spec.ports.push(Port {
+            name: "user".into(),
+            direction: PortDirection::Outgoing,
+            payload: "UserV2".into(),
+            doc: None,
+        });
+        let subset = spec.without(&["UserV2"]);
+        assert!(subset.upgrades.is_empty() && subset.channels.is_empty());
+        assert!(subset.ports.is_empty());
+        assert!(!subset.to_elm().contains("UserV2"));
+        assert_eq!(spec.subset(&["UserV1", "UserV2"]).upgrades.len(), 1);
+    }
+
+    #[test]
+    fn diff_endpoints() {
+        let old = create_spec_endpoints();
+        assert_eq!(old.diff(&old), vec![]);
+
+        let mut new = create_spec_endpoints();
+        if let TypeSpec::Enum { variants, .. } = &mut new.types[1] {
+            variants[1].name = "Guest".into();
+        }
+        if let TypeSpec::Struct { fields, .. } = &mut new.types[2] {
+            fields[1].data = ("Option<String>".into(), "Maybe String".into());
+            fields.push(StructField {
+                name: "email".into(),
+                data: ("String".into(), "String".into()),
+                doc: None,
+            });
+        }
+        new.endpoints[1].query.remove(1);
+        new.endpoints.remove(2);
+
+        let changes = old.diff(&new);
+        assert_eq!(
+            changes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "breaking: Role::Member: variant renamed to `Guest` \
+                (breaks old clients and old servers)",
+                "breaking: User.name: type is now optional, as `Option<String>` \
+                (breaks old clients and old servers)",
+                "breaking: User.email: required field added (breaks old clients and old servers)",
+                "breaking: list_users.search: query parameter removed (breaks old servers)",
+                "breaking: update_user: endpoint removed (breaks old clients)",
+            ]
+        );
+
+        let with_fallback = || {
+            let mut spec = create_spec_endpoints();
+            if let TypeSpec::Enum {
+                unknown_fallback, ..
+            } = &mut spec.types[1]
+            {
+                *unknown_fallback = true;
+            }
+            spec
+        };
+        let old = with_fallback();
+        let mut new = with_fallback();
+        if let TypeSpec::Enum { variants, .. } = &mut new.types[1] {
+            variants.remove(0);
+        }
+        new.endpoints[1].query.remove(0);
+
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                SpecChange {
+                    path: "Role::Admin".into(),
+                    message: "variant removed".into(),
+                    breaks_old_clients: false,
+                    breaks_old_servers: false,
+                },
+                SpecChange {
+                    path: "list_users.role".into(),
+                    message: "query parameter removed".into(),
+                    breaks_old_clients: false,
+                    breaks_old_servers: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_elm_client_clashes() {
+        let yaml = "\
+module: Api
+types: []
+endpoints:
+  - name: search
+    method: GET
+    path: /search/{to_msg}
+    params:
+      - name: to_msg
+        data: [String, String]
+    query:
+      - name: value
+        data: [Option<String>, Maybe String]
+";
+        let spec = ApiSpec::parse(yaml, SpecFormat::Yaml).unwrap();
+        let span = |line, column, len| Some(Span { line, column, len });
+
+        assert_eq!(
+            spec.validate(),
+            vec![
+                Diagnostic::error(
+                    "`search.to_msg` clashes with the `toMsg` of the generated Elm client"
+                )
+                .at(span(8, 15, 6))
+                .with_hint("rename the param"),
+                Diagnostic::error(
+                    "`search.value` clashes with the `value` of the generated Elm client"
+                )
+                .at(span(11, 15, 5))
+                .with_hint("rename the param"),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_server_param_clashes() {
+        let yaml = "\
+module: Api
+rust:
+  server: Axum
+types: []
+endpoints:
+  - name: update
+    method: PUT
+    path: /items/{server}
+    params:
+      - name: server
+        data: [String, String]
+    query:
+      - name: body
+        data: [String, String]
+    body: [String, String]
+";
+        let spec = ApiSpec::parse(yaml, SpecFormat::Yaml).unwrap();
+        let span = |line, column, len| Some(Span { line, column, len });
+
+        let clashes = spec
+            .validate()
+            .into_iter()
+            .filter(|d| d.message.ends_with("of the generated server code"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            clashes,
+            vec![
+                Diagnostic::error(
+                    "`update.server` clashes with the `server` of the generated server code"
+                )
+                .at(span(10, 15, 6))
+                .with_hint("rename the param"),
+                Diagnostic::error(
+                    "`update.body` clashes with the `body` of the generated server code"
+                )
+                .at(span(13, 15, 4))
+                .with_hint("rename the param"),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_server_item_clashes() {
+        let yaml = "\
+module: Api
+rust:
+  server: Axum
+types:
+  - Alias:
+      name: router
+      data: [String, String]
+endpoints:
+  - name: ping
+    method: GET
+    path: /ping
+";
+        let spec = ApiSpec::parse(yaml, SpecFormat::Yaml).unwrap();
+
+        assert_eq!(
+            spec.validate(),
+            vec![Diagnostic::error(
+                "the type `router` clashes with the Rust code generated for the endpoints"
+            )
+            .at(Some(Span {
+                line: 6,
+                column: 13,
+                len: 6
+            }))]
+        );
+    }
+
+    fn diff_strings(old: &ApiSpec, new: &ApiSpec) -> Vec<String> {
+        old.diff(new).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn diff_field_renames() {
+        let old = create_spec_struct_simple();
+        let field = |name: &str, rust: &str, elm: &str| StructField {
+            name: name.into(),
+            data: (rust.into(), elm.into()),
+            doc: None,
+        };
+        let with_fields = |fields: Vec<StructField>| {
+            let mut spec = create_spec_struct_simple();
+            if let TypeSpec::Struct { fields: f, .. } = &mut spec.types[0] {
+                *f = fields;
+            }
+            spec
+        };
+
+        // Same position and contents: a rename.
+        let new = with_fields(vec![
+            field("foo", "u32", "Int"),
+            field("title", "String", "String"),
+        ]);
+        assert_eq!(
+            diff_strings(&old, &new),
+            vec![
+                "breaking: TestStruct.bar: field renamed to `title` \
+                (breaks old clients and old servers)"
+            ]
+        );
+
+        // Same position, other contents: a removal and an addition.
+        let new = with_fields(vec![
+            field("foo", "u32", "Int"),
+            field("title", "Option<String>", "Maybe String"),
+        ]);
+        assert_eq!(
+            diff_strings(&old, &new),
+            vec![
+                "breaking: TestStruct.bar: field removed (breaks old clients and old servers)",
+                "breaking: TestStruct.title: optional field added (breaks old servers)",
+            ]
+        );
+
+        // Same contents at another position isn't taken for a rename.
+        let new = with_fields(vec![
+            field("title", "String", "String"),
+            field("foo", "u32", "Int"),
+        ]);
+        assert_eq!(
+            diff_strings(&old, &new),
+            vec![
+                "breaking: TestStruct.bar: field removed (breaks old clients and old servers)",
+                "breaking: TestStruct.title: required field added \
+                (breaks old clients and old servers)",
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_variants_and_fallback() {
+        let spec = |fallback: bool, names: &[&str]| {
+            let mut spec = create_spec_enum_simple();
+            if let TypeSpec::Enum {
+                variants,
+                unknown_fallback,
+                ..
+            } = &mut spec.types[0]
+            {
+                *variants = names
+                    .iter()
+                    .map(|name| EnumVariant {
+                        name: name.to_string(),
+                        data: EnumVariantData::None,
+                        doc: None,
+                        discriminant: None,
+                    })
+                    .collect();
+                *unknown_fallback = fallback;
+            }
+            spec
+        };
+
+        let all = ["Foo", "Bar", "Qux"];
+        let added = ["Foo", "Bar", "Qux", "Baz"];
+        let removed = ["Foo", "Bar"];
+
+        assert_eq!(
+            diff_strings(&spec(false, &all), &spec(false, &added)),
+            vec!["breaking: TestEnum::Baz: variant added (breaks old clients and old servers)"]
+        );
+        assert_eq!(
+            diff_strings(&spec(false, &all), &spec(false, &removed)),
+            vec!["breaking: TestEnum::Qux: variant removed (breaks old clients and old servers)"]
+        );
+
+        // Sides with an `Unknown` fallback read variants they don't know into it.
+        assert_eq!(
+            diff_strings(&spec(true, &all), &spec(true, &added)),
+            vec!["compatible: TestEnum::Baz: variant added"]
+        );
+        assert_eq!(
+            diff_strings(&spec(true, &all), &spec(true, &removed)),
+            vec!["compatible: TestEnum::Qux: variant removed"]
+        );
+
+        // Only the old version's fallback helps with variants it doesn't know, and only the new
+        // version's with variants that are gone.
+        assert_eq!(
+            diff_strings(&spec(false, &all), &spec(true, &added)),
+            vec!["breaking: TestEnum::Baz: variant added (breaks old clients and old servers)"]
+        );
+        assert_eq!(
+            diff_strings(&spec(false, &all), &spec(true, &removed)),
+            vec!["compatible: TestEnum::Qux: variant removed"]
+        );
+    }
+
+    #[test]
+    fn diff_query_params() {
+        let old = create_spec_endpoints();
+        let mut new = create_spec_endpoints();
+        new.endpoints[1].query = vec![
+            StructField {
+                name: "page".into(),
+                data: ("Option<u32>".into(), "Maybe Int".into()),
+                doc: None,
+            },
+            StructField {
+                name: "limit".into(),
+                data: ("u32".into(), "Int".into()),
+                doc: None,
+            },
+        ];
+
+        // Servers ignore unknown parameters, but fail on missing required ones.
+        assert_eq!(
+            diff_strings(&old, &new),
+            vec![
+                "compatible: list_users.role: query parameter removed",
+                "breaking: list_users.search: query parameter removed (breaks old servers)",
+                "compatible: list_users.page: optional query parameter added",
+                "breaking: list_users.limit: required query parameter added (breaks old clients)",
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_enum_repr() {
+        let old = create_spec_enum_simple();
+
+        let mut tagged = create_spec_enum_simple();
+        let mut int = create_spec_enum_simple();
+        if let TypeSpec::Enum { variants, .. } = &mut tagged.types[0] {
+            variants[0].data = EnumVariantData::Single(("u32".into(), "Int".into()));
+        }
+        if let TypeSpec::Enum { variants, .. } = &mut int.types[0] {
+            for (i, var) in variants.iter_mut().enumerate() {
+                var.discriminant = Some(i as i64);
+            }
+        }
+
+        // A change of representation is reported once, rather than per variant.
+        assert_eq!(
+            diff_strings(&old, &tagged),
+            vec![
+                "breaking: TestEnum: now sent as tagged objects instead of strings \
+                (breaks old clients and old servers)"
+            ]
+        );
+        assert_eq!(
+            diff_strings(&old, &int),
+            vec![
+                "breaking: TestEnum: now sent as integers instead of strings \
+                (breaks old clients and old servers)"
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_endpoint_bodies() {
+        let old = create_spec_endpoints();
+        let mut new = create_spec_endpoints();
+        // `get_user` loses its response, `update_user` gains one and loses its request body.
+        new.endpoints[0].response = None;
+        new.endpoints[0].error = Some(("Option<String>".into(), "Maybe String".into()));
+        new.endpoints[1].body = Some(("User".into(), "User".into()));
+        new.endpoints[2].body = None;
+        new.endpoints[2].response = Some(("User".into(), "User".into()));
+
+        assert_eq!(
+            old.diff(&new)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "breaking: get_user: response removed (breaks old clients)",
+                "breaking: get_user: error response is now optional, as `Option<String>` \
+                (breaks old clients)",
+                "breaking: list_users: request body added (breaks old clients)",
+                "breaking: update_user: request body removed (breaks old servers)",
+                "breaking: update_user: response added (breaks old servers)",
+            ]
+        );
+
+        // Back again, each change breaks the other side.
+        assert_eq!(
+            new.diff(&old)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "breaking: get_user: response added (breaks old servers)",
+                "breaking: get_user: error response is now required, as `String` \
+                (breaks old servers)",
+                "breaking: list_users: request body removed (breaks old servers)",
+                "breaking: update_user: request body added (breaks old clients)",
+                "breaking: update_user: response removed (breaks old clients)",
+            ]
+        );
+    }
+
+    fn create_spec_channel() -> ApiSpec {
+        let mut spec = create_spec_enum_simple();
+        spec.channels.push(Channel {
+            name: "live_updates".into(),
+            client: "TestEnum".into(),
+            server: "TestEnum".into(),
+            doc: Some("Pushes changes as they happen.".into()),
+        });
+        spec
+    }
+
+    #[test]
+    fn elm_channel() {
+        let spec = create_spec_channel();
+
+        let expected = "\
+module TestType exposing (TestEnum(..), decodeTestEnum, encodeTestEnum, allTestEnum, testEnumToString, testEnumFromString, decodeLiveUpdatesFrame, encodeLiveUpdatesFrame, subscribeLiveUpdates, sendLiveUpdates)
+
+import Json.Decode
+import Json.Encode
+
+
+type TestEnum
+    = Foo
+    | Bar
+    | Qux
+
+
+allTestEnum : List TestEnum
+allTestEnum =
+    [ Foo
+    , Bar
+    , Qux
+    ]
+
+
+testEnumToString : TestEnum -> String
+testEnumToString var =
+    case var of
+        Foo ->
+            \"Foo\"
+
+        Bar ->
+            \"Bar\"
+
+        Qux ->
+            \"Qux\"
+
+
+testEnumFromString : String -> Maybe TestEnum
+testEnumFromString value =
+    case value of
+        \"Foo\" ->
+            Just Foo
+
+        \"Bar\" ->
+            Just Bar
+
+        \"Qux\" ->
+            Just Qux
+
+        _ ->
+            Nothing
+
+
+decodeTestEnum : Json.Decode.Decoder TestEnum
+decodeTestEnum =
+    Json.Decode.string
+        |> Json.Decode.andThen
+            (\\value ->
+                case testEnumFromString value of
+                    Just var ->
+                        Json.Decode.succeed var
+
+                    Nothing ->
+                        Json.Decode.fail (\"Unknown TestEnum variant: \" ++ value)
+            )
+
+
+encodeTestEnum : TestEnum -> Json.Encode.Value
+encodeTestEnum var =
+    Json.Encode.string <| testEnumToString var
+
+
+{-| Pushes changes as they happen.
+
+Reads a frame received on the `live_updates` channel.
+-}
+decodeLiveUpdatesFrame : String -> Result Json.Decode.Error TestEnum
+decodeLiveUpdatesFrame =
+    Json.Decode.decodeString decodeTestEnum
+
+
+{-| Writes a frame to send on the `live_updates` channel.
+-}
+encodeLiveUpdatesFrame : TestEnum -> String
+encodeLiveUpdatesFrame message =
+    Json.Encode.encode 0 (encodeTestEnum message)
+
+
+{-| Listens for messages on the `live_updates` channel through an incoming port.
+-}
+subscribeLiveUpdates : ((String -> msg) -> Sub msg) -> (Result Json.Decode.Error TestEnum -> msg) -> Sub msg
+subscribeLiveUpdates listen toMsg =
+    listen (toMsg << decodeLiveUpdatesFrame)
+
+
+{-| Sends a message on the `live_updates` channel through an outgoing port.
+-}
+sendLiveUpdates : (String -> Cmd msg) -> TestEnum -> Cmd msg
+sendLiveUpdates toPort message =
+    toPort (encodeLiveUpdatesFrame message)
+";
+
+        compare_strings(expected, spec.to_elm());
+    }
+
+    #[test]
+    fn rust_channel() {
+        let spec = create_spec_channel();
+
+        let expected = "\
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum TestEnum {
+    Foo,
+    Bar,
+    Qux,
+}
+
+/// A frame that doesn't hold a valid message for its channel.
+#[derive(Debug)]
+pub struct FrameError {
+    pub channel: &'static str,
+    pub error: serde_json::Error,
+}
+
+impl std::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, \"invalid `{}` frame: {}\", self.channel, self.error)
+    }
+}
+
+impl std::error::Error for FrameError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Pushes changes as they happen.
+///
+/// Parses a frame the client sent on the `live_updates` channel.
+pub fn parse_live_updates_frame(frame: &str) -> Result<TestEnum, FrameError> {
+    serde_json::from_str(frame).map_err(|error| FrameError {
+        channel: \"live_updates\",
+        error,
+    })
+}
+
+/// Writes a message to send to the client on the `live_updates` channel.
+pub fn live_updates_frame(message: &TestEnum) -> String {
+    serde_json::to_string(message).expect(\"messages serialize to JSON\")
+}";
+
+        compare_strings(expected, spec.to_rust());
+    }
+
+    #[test]
+    fn validate_channel() {
+        let mut spec = create_spec_channel();
+        assert_eq!(spec.validate(), vec![]);
+
+        spec.channels[0].server = "Update".into();
+        assert_eq!(
+            spec.validate(),
+            vec![Diagnostic::error(
+                "channel `live_updates` carries `Update`, which isn't a type in the spec"
+            )]
+        );
+    }
+
+    fn create_spec_ports() -> ApiSpec {
+        let mut spec = create_spec_enum_simple();
+        spec.ports.push(Port {
+            name: "save_choice".into(),
+            direction: PortDirection::Outgoing,
+            payload: "TestEnum".into(),
+            doc: Some("Persists the choice in local storage.".into()),
+        });
+        spec.ports.push(Port {
+            name: "choice_changed".into(),
+            direction: PortDirection::Incoming,
+            payload: "TestEnum".into(),
+            doc: None,
+        });
+        spec
+    }
+
+    #[test]
+    fn elm_ports() {
+        let spec = create_spec_ports();
+
+        let expected = "\
+port module TestType exposing (TestEnum(..), decodeTestEnum, encodeTestEnum, allTestEnum, testEnumToString, testEnumFromString, saveChoice, choiceChanged)
+
+import Json.Decode
+import Json.Encode
+
+
+type TestEnum
+    = Foo
+    | Bar
+    | Qux
+
+
+allTestEnum : List TestEnum
+allTestEnum =
+    [ Foo
+    , Bar
+    , Qux
+    ]
+
+
+testEnumToString : TestEnum -> String
+testEnumToString var =
+    case var of
+        Foo ->
+            \"Foo\"
+
+        Bar ->
+            \"Bar\"
+
+        Qux ->
+            \"Qux\"
+
+
+testEnumFromString : String -> Maybe TestEnum
+testEnumFromString value =
+    case value of
+        \"Foo\" ->
+            Just Foo
+
+        \"Bar\" ->
+            Just Bar
+
+        \"Qux\" ->
+            Just Qux
+
+        _ ->
+            Nothing
+
+
+decodeTestEnum : Json.Decode.Decoder TestEnum
+decodeTestEnum =
+    Json.Decode.string
+        |> Json.Decode.andThen
+            (\\value ->
+                case testEnumFromString value of
+                    Just var ->
+                        Json.Decode.succeed var
+
+                    Nothing ->
+                        Json.Decode.fail (\"Unknown TestEnum variant: \" ++ value)
+            )
+
+
+encodeTestEnum : TestEnum -> Json.Encode.Value
+encodeTestEnum var =
+    Json.Encode.string <| testEnumToString var
+
+
+port saveChoicePort : Json.Encode.Value -> Cmd msg
+
+
+{-| Persists the choice in local storage.
+-}
+saveChoice : TestEnum -> Cmd msg
+saveChoice value =
+    saveChoicePort (encodeTestEnum value)
+
+
+port choiceChangedPort : (Json.Decode.Value -> msg) -> Sub msg
+
+
+choiceChanged : (Result Json.Decode.Error TestEnum -> msg) -> Sub msg
+choiceChanged toMsg =
+    choiceChangedPort (toMsg << Json.Decode.decodeValue decodeTestEnum)
+";
+
+        compare_strings(expected, spec.to_elm());
+    }
+
+    #[test]
+    fn ts_ports() {
+        let spec = create_spec_ports();
+
+        let expected = "\
+export type TestEnum = \"Foo\" | \"Bar\" | \"Qux\";
+
+/** The ports of the Elm app, `app.ports`. */
+export interface Ports {
+    /** Persists the choice in local storage. */
+    saveChoicePort: {
+        subscribe(callback: (value: TestEnum) => void): void;
+        unsubscribe(callback: (value: TestEnum) => void): void;
+    };
+    choiceChangedPort: {
+        send(value: TestEnum): void;
+    };
+}
+";
+
+        compare_strings(expected, spec.ports_to_ts());
+    }
+
+    #[test]
+    fn validate_ports() {
+        let mut spec = create_spec_ports();
+        assert_eq!(spec.validate(), vec![]);
+
+        spec.ports[1].payload = "Choice".into();
+        assert_eq!(
+            spec.validate(),
+            vec![Diagnostic::error(
+                "port `choice_changed` carries `Choice`, which isn't a type in the spec"
+            )]
+        );
+    }
+
+    fn create_spec_upgrades() -> ApiSpec {
+        let field = |name: &str, rust: &str, elm: &str| StructField {
+            name: name.into(),
+            data: (rust.into(), elm.into()),
+            doc: None,
+        };
+
+        ApiSpec {
+            module: "TestType".into(),
+            types: vec![
+                TypeSpec::Struct {
+                    name: "UserV1".into(),
+                    fields: vec![field("id", "u32", "Int"), field("name", "String", "String")],
+                    doc: None,
+                    targets: None,
+                },
+                TypeSpec::Struct {
+                    name: "UserV2".into(),
+                    fields: vec![
+                        field("id", "u32", "Int"),
+                        field("full_name", "String", "String"),
+                        field("email", "Option<String>", "Maybe String"),
+                    ],
+                    doc: None,
+                    targets: None,
+                },
+            ],
+            upgrades: vec![Upgrade {
+                from: "UserV1".into(),
+                to: "UserV2".into(),
+                fields: vec![
+                    FieldUpgrade {
+                        name: "full_name".into(),
+                        from: Some("name".into()),
+                        default: None,
+                    },
+                    FieldUpgrade {
+                        name: "email".into(),
+                        from: None,
+                        default: Some(("None".into(), "Nothing".into())),
+                    },
+                ],
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rust_upgrades() {
+        let expected = "\
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UserV1 {
+    pub id: u32,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UserV2 {
+    pub id: u32,
+    pub full_name: String,
+    pub email: Option<String>,
+}
+
+impl From<UserV1> for UserV2 {
+    fn from(old: UserV1) -> Self {
+        Self {
+            id: old.id,
+            full_name: old.name,
+            email: None,
+        }
+    }
+}";
+
+        compare_strings(expected, create_spec_upgrades().to_rust());
+    }
+
+    #[test]
+    fn elm_upgrades() {
+        let expected = "\
+module TestType exposing (UserV1, decodeUserV1, encodeUserV1, UserV2, decodeUserV2, encodeUserV2, upgradeUserV1, decodeAnyUserV2)
+
+import Json.Decode
+import Json.Decode.Pipeline
+import Json.Encode
+import Json.Encode.Extra
+
+
+type alias UserV1 =
+    { id : Int
+    , name : String
+    }
+
+
+decodeUserV1 : Json.Decode.Decoder UserV1
+decodeUserV1 =
+    Json.Decode.succeed UserV1
+        |> Json.Decode.Pipeline.required \"id\" Json.Decode.int
+        |> Json.Decode.Pipeline.required \"name\" Json.Decode.string
+
+
+encodeUserV1 : UserV1 -> Json.Encode.Value
+encodeUserV1 record =
+    Json.Encode.object
+        [ ( \"id\", Json.Encode.int <| record.id )
+        , ( \"name\", Json.Encode.string <| record.name )
+        ]
+
+
+type alias UserV2 =
+    { id : Int
+    , full_name : String
+    , email : Maybe String
+    }
+
+
+decodeUserV2 : Json.Decode.Decoder UserV2
+decodeUserV2 =
+    Json.Decode.succeed UserV2
+        |> Json.Decode.Pipeline.required \"id\" Json.Decode.int
+        |> Json.Decode.Pipeline.required \"full_name\" Json.Decode.string
+        |> Json.Decode.Pipeline.required \"email\" (Json.Decode.nullable Json.Decode.string)
+
+
+encodeUserV2 : UserV2 -> Json.Encode.Value
+encodeUserV2 record =
+    Json.Encode.object
+        [ ( \"id\", Json.Encode.int <| record.id )
+        , ( \"full_name\", Json.Encode.string <| record.full_name )
+        , ( \"email\", Json.Encode.Extra.maybe Json.Encode.string <| record.email )
+        ]
+
+
+{-| Upgrades `UserV1` to `UserV2`.
+-}
+upgradeUserV1 : UserV1 -> UserV2
+upgradeUserV1 old =
+    { id = old.id
+    , full_name = old.name
+    , email = Nothing
+    }
+
+
+{-| Decodes any version of `UserV2`, upgrading older ones.
+-}
+decodeAnyUserV2 : Json.Decode.Decoder UserV2
+decodeAnyUserV2 =
+    Json.Decode.oneOf
+        [ decodeUserV2
+        , Json.Decode.map upgradeUserV1 decodeUserV1
+        ]
+";
+
+        compare_strings(expected, create_spec_upgrades().to_elm());
+    }
+
+    #[test]
+    fn validate_upgrades() {
+        let mut spec = create_spec_upgrades();
+        assert_eq!(spec.validate(), vec![]);
+
+        spec.upgrades[0].fields.remove(0);
+        spec.upgrades[0].fields[0].name = "mail".into();
+        assert_eq!(
+            spec.validate(),
+            vec![Diagnostic::error(
+                "upgrade from `UserV1` to `UserV2`: `UserV2.full_name` can't be copied from \
+                `UserV1.full_name`, which doesn't exist; give it a `from` or a `default`"
+            )]
+        );
+
+        let mut spec = create_spec_upgrades();
+        spec.upgrades[0].fields[1].name = "mail".into();
+        spec.upgrades.push(spec.upgrades[0].clone());
+        assert_eq!(
+            spec.validate(),
+            vec![
+                Diagnostic::error(
+                    "upgrade from `UserV1` to `UserV2`: `UserV2.email` can't be copied from \
+                    `UserV1.email`, which doesn't exist; give it a `from` or a `default`"
+                ),
+                Diagnostic::error(
+                    "upgrade from `UserV1` to `UserV2`: `UserV2.email` can't be copied from \
+                    `UserV1.email`, which doesn't exist; give it a `from` or a `default`"
+                ),
+                Diagnostic::error("`UserV1` is upgraded more than once"),
+            ]
+        );
+
+        let mut spec = create_spec_upgrades();
+        spec.upgrades[0].fields.push(FieldUpgrade {
+            name: "phone".into(),
+            from: Some("name".into()),
+            default: None,
+        });
+        assert_eq!(
+            spec.validate(),
+            vec![Diagnostic::error(
+                "upgrade from `UserV1` to `UserV2`: `UserV2` has no field `phone`"
+            )]
+        );
+
+        let mut spec = create_spec_upgrades();
+        spec.upgrades.push(Upgrade {
+            from: "UserV2".into(),
+            to: "UserV1".into(),
+            fields: vec![FieldUpgrade {
+                name: "name".into(),
+                from: Some("full_name".into()),
+                default: None,
+            }],
+        });
+        assert_eq!(
+            spec.validate(),
+            vec![
+                Diagnostic::error("upgrading `UserV1` leads back to `UserV1`")
+                    .with_hint("upgrades must go from older to newer versions")
+            ]
+        );
+    }
+
+    #[test]
+    fn elm_upgrades_to_same_version() {
+        let mut spec = create_spec_upgrades();
+        let user_v1 = spec.types[0].clone();
+        if let TypeSpec::Struct { name, .. } = &mut spec.types[0] {
+            *name = "UserV0".into();
+        }
+        spec.types.insert(1, user_v1);
+        let mut upgrade = spec.upgrades[0].clone();
+        upgrade.from = "UserV0".into();
+        spec.upgrades.push(upgrade);
+        assert_eq!(spec.validate(), vec![]);
+
+        let expected = "\
+{-| Decodes any version of `UserV2`, upgrading older ones.
+-}
+decodeAnyUserV2 : Json.Decode.Decoder UserV2
+decodeAnyUserV2 =
+    Json.Decode.oneOf
+        [ decodeUserV2
+        , Json.Decode.map upgradeUserV1 decodeUserV1
+        , Json.Decode.map upgradeUserV0 decodeUserV0
+        ]
+";
+
+        let elm = spec.to_elm();
+        assert_eq!(elm.matches("decodeAnyUserV2 =").count(), 1);
+        assert!(elm.contains("upgradeUserV1, upgradeUserV0, decodeAnyUserV2)"));
+        assert!(elm.contains(expected), "{}", elm);
+    }
+
+    fn create_spec_with_docs() -> ApiSpec {
+        ApiSpec {
+            module: "TestType".into(),
+            types: vec![
+                TypeSpec::Struct {
+                    name: "TestStruct".into(),
+                    fields: vec![
+                        StructField {
+                            name: "foo".into(),
+                            data: ("u32".into(), "Int".into()),
+                            doc: Some("The foo.".into()),
+                        },
+                        StructField {
+                            name: "bar".into(),
+                            data: ("String".into(), "String".into()),
+                            doc: None,
+                        },
+                    ],
+                    doc: Some("A test struct.\n\nSpans two paragraphs.".into()),
+                    targets: None,
+                },
+                TypeSpec::Enum {
+                    name: "TestEnum".into(),
+                    variants: vec![
+                        EnumVariant {
+                            name: "Foo".into(),
+                            data: EnumVariantData::None,
+                            doc: Some("The foo variant.".into()),
+                            discriminant: None,
+                        },
+                        EnumVariant {
+                            name: "Bar".into(),
+                            data: EnumVariantData::Single(("bool".into(), "Bool".into())),
+                            doc: None,
+                            discriminant: None,
+                        },
+                    ],
+                    doc: Some("A test enum.".into()),
+                    targets: None,
+                    unknown_fallback: false,
+                },
+            ],
+            doc: Some("Test types.".into()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rust_docs() {
+        let expected = "\
+//! Test types.
+
+/// A test struct.
+///
+/// Spans two paragraphs.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TestStruct {
+    /// The foo.
+    pub foo: u32,
+    pub bar: String,
+}
+
+/// A test enum.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = \"var\", content = \"vardata\")]
+pub enum TestEnum {
+    /// The foo variant.
+    Foo,
+    Bar(bool),
+}";
+
+        compare_strings(expected, create_spec_with_docs().to_rust());
+    }
+
+    #[test]
+    fn elm_docs() {
+        let expected = "\
+module TestType exposing (TestStruct, decodeTestStruct, encodeTestStruct, TestEnum(..), decodeTestEnum, encodeTestEnum)
+
+{-| Test types.
+
+@docs TestStruct, decodeTestStruct, encodeTestStruct, TestEnum, decodeTestEnum, encodeTestEnum
+
+-}
+
+import Json.Decode
+import Json.Decode.Pipeline
+import Json.Encode
+
+
+{-| A test struct.
+
+Spans two paragraphs.
+
+  - `foo`: The foo.
+
+-}
+type alias TestStruct =
+    { foo : Int
+    , bar : String
+    }
+
+
+decodeTestStruct : Json.Decode.Decoder TestStru";
+
+        let actual = create_spec_with_docs().to_elm();
+        compare_strings(expected, actual[..expected.len()].to_string());
+
+        let expected_enum = "\
+{-| A test enum.
+
+  - `Foo`: The foo variant.
+
+-}
+type TestEnum
+    = Foo
+    | Bar Bool
+";
+        assert!(actual.contains(expected_enum));
+    }
+
+    #[test]
+    fn rust_crate_visibility() {
+        let mut spec = create_spec_struct_simple();
+        spec.rust.visibility = Visibility::Crate;
+
+        let expected = "\
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub(crate) struct TestStruct {
+    pub(crate) foo: u32,
+    pub(crate) bar: String,
+}";
+
+        compare_strings(expected, spec.to_rust());
+    }
+
+    #[test]
+    fn rust_module_with_prelude() {
+        let mut spec = create_spec_enum_complex();
+        spec.doc = Some("Test types.".into());
+        spec.rust = RustOptions {
+            visibility: Visibility::Crate,
+            module: Some("api".into()),
+            prelude: vec![
+                "#![allow(dead_code)]".into(),
+                "use std::collections::HashMap;".into(),
+            ],
+            server: None,
+        };
+
+        let expected = "\
+pub(crate) mod api {
+    //! Test types.
+
+    #![allow(dead_code)]
+    use std::collections::HashMap;
+
+    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+    #[serde(tag = \"var\", content = \"vardata\")]
+    pub(crate) enum TestEnum {
+        Foo,
+        Bar(bool),
+        Qux {
+            sub1: u32,
+            sub2: String,
+        },
+    }
+}";
+
+        compare_strings(expected, spec.to_rust());
+    }
+
+    fn create_spec_newtype_and_alias() -> ApiSpec {
+        ApiSpec {
+            module: "TestType".into(),
+            types: vec![
+                TypeSpec::Newtype {
+                    name: "UserId".into(),
+                    data: ("u64".into(), "Int".into()),
+                    doc: None,
+                    targets: None,
+                },
+                TypeSpec::Alias {
+                    name: "UserIds".into(),
+                    data: ("Vec<UserId>".into(), "List UserId".into()),
+                    doc: None,
+                    targets: None,
+                },
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rust_newtype_and_alias() {
+        let expected = "\
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct UserId(pub u64);
+
+pub type UserIds = Vec<UserId>;";
+
+        compare_strings(expected, create_spec_newtype_and_alias().to_rust());
+    }
+
+    #[test]
+    fn elm_newtype_and_alias() {
+        let expected = "\
+module TestType exposing (UserId, decodeUserId, encodeUserId, unwrapUserId, UserIds, decodeUserIds, encodeUserIds)
+
+import Json.Decode
+import Json.Encode
+
+
+type UserId
+    = UserId Int
+
+
+unwrapUserId : UserId -> Int
+unwrapUserId (UserId value) =
+    value
+
+
+decodeUserId : Json.Decode.Decoder UserId
+decodeUserId =
+    Json.Decode.map UserId Json.Decode.int
+
+
+encodeUserId : UserId -> Json.Encode.Value
+encodeUserId (UserId value) =
+    Json.Encode.int <| value
+
+
+type alias UserIds =
+    List UserId
+
+
+decodeUserIds : Json.Decode.Decoder UserIds
+decodeUserIds =
+    Json.Decode.list decodeUserId
+
+
+encodeUserIds : UserIds -> Json.Encode.Value
+encodeUserIds =
+    Json.Encode.list encodeUserId
+";
+
+        compare_strings(expected, create_spec_newtype_and_alias().to_elm());
+    }
+
+    fn create_spec_enum_int() -> ApiSpec {
+        ApiSpec {
+            module: "TestType".into(),
+            types: vec![TypeSpec::Enum {
+                name: "TestEnum".into(),
+                variants: vec![
+                    EnumVariant {
+                        name: "Foo".into(),
+                        data: EnumVariantData::None,
+                        doc: None,
+                        discriminant: Some(1),
+                    },
+                    EnumVariant {
+                        name: "Bar".into(),
+                        data: EnumVariantData::None,
+                        doc: None,
+                        discriminant: Some(-2),
+                    },
+                ],
+                doc: None,
+                targets: None,
+                unknown_fallback: false,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rust_enum_int() {
+        let expected = "\
+#[derive(Debug, Clone, serde_repr::Serialize_repr, serde_repr::Deserialize_repr)]
+#[repr(i32)]
+pub enum TestEnum {
+    Foo = 1,
+    Bar = -2,
+}";
+
+        compare_strings(expected, create_spec_enum_int().to_rust());
+    }
+
+    #[test]
+    fn elm_enum_int() {
+        let expected = "\
+module TestType exposing (TestEnum(..), decodeTestEnum, encodeTestEnum, allTestEnum, testEnumToInt, testEnumFromInt)
+
+import Json.Decode
+import Json.Encode
+
+
+type TestEnum
+    = Foo
+    | Bar
+
+
+allTestEnum : List TestEnum
+allTestEnum =
+    [ Foo
+    , Bar
+    ]
+
+
+testEnumToInt : TestEnum -> Int
+testEnumToInt var =
+    case var of
+        Foo ->
+            1
+
+        Bar ->
+            -2
+
+
+testEnumFromInt : Int -> Maybe TestEnum
+testEnumFromInt value =
+    case value of
+        1 ->
+            Just Foo
+
+        -2 ->
+            Just Bar
+
+        _ ->
+            Nothing
+
+
+decodeTestEnum : Json.Decode.Decoder TestEnum
+decodeTestEnum =
+    Json.Decode.int
+        |> Json.Decode.andThen
+            (\\value ->
+                case testEnumFromInt value of
+                    Just var ->
+                        Json.Decode.succeed var
+
+                    Nothing ->
+                        Json.Decode.fail (\"Unknown TestEnum variant: \" ++ String.fromInt value)
+            )
+
+
+encodeTestEnum : TestEnum -> Json.Encode.Value
+encodeTestEnum var =
+    Json.Encode.int <| testEnumToInt var
+";
+
+        compare_strings(expected, create_spec_enum_int().to_elm());
+    }
+
+    #[test]
+    fn validate_enum_int() {
+        assert_eq!(create_spec_enum_int().validate(), vec![]);
+
+        let mut spec = create_spec_enum_int();
+        if let TypeSpec::Enum { variants, .. } = &mut spec.types[0] {
+            variants[1].discriminant = None;
+        }
+
+        assert_eq!(
+            spec.validate(),
+            vec![Diagnostic::error(
+                "enum `TestEnum` must have a discriminant on every variant or on none"
+            )]
+        );
+
+        let mut spec = create_spec_enum_simple();
+        if let TypeSpec::Enum { variants, .. } = &mut spec.types[0] {
+            variants.clear();
+        }
+        assert_eq!(
+            spec.validate(),
+            vec![Diagnostic::error(
+                "enum `TestEnum` has no variants, which Elm can't decode or encode"
+            )]
+        );
+    }
+
+    /// A tagged enum and a string enum, both with an `unknown_fallback`.
+    fn create_spec_unknown_fallback() -> ApiSpec {
+        let mut spec = create_spec_enum_complex();
+        let mut choice = create_spec_enum_simple().types.remove(0);
+        if let TypeSpec::Enum { name, variants, .. } = &mut choice {
+            *name = "TestChoice".into();
+            for (var, new_name) in variants.iter_mut().zip(&["Small", "Medium", "Large"]) {
+                var.name = new_name.to_string();
+            }
+        }
+        spec.types.push(choice);
+
+        for t in &mut spec.types {
+            if let TypeSpec::Enum {
+                unknown_fallback, ..
+            } = t
+            {
+                *unknown_fallback = true;
+            }
+        }
+        spec
+    }
+
+    #[test]
+    fn rust_unknown_fallback() {
+        let expected = "\
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = \"var\", content = \"vardata\")]
+pub enum TestEnum {
+    Foo,
+    Bar(bool),
+    Qux {
+        sub1: u32,
+        sub2: String,
+    },
+    /// A variant this version doesn't know, as it was received.
+    #[serde(untagged)]
+    Unknown(serde_json::Value),
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum TestChoice {
+    Small,
+    Medium,
+    Large,
+    /// A variant this version doesn't know, as it was received.
+    #[serde(untagged)]
+    Unknown(String),
+}";
+
+        compare_strings(expected, create_spec_unknown_fallback().to_rust());
+    }
+
+    #[test]
+    fn elm_unknown_fallback() {
+        let expected = "\
+module TestType exposing (TestEnum(..), decodeTestEnum, encodeTestEnum, TestChoice(..), decodeTestChoice, encodeTestChoice, allTestChoice, testChoiceToString, testChoiceFromString)
+
+import Json.Decode
+import Json.Decode.Pipeline
+import Json.Encode
+
+
+type alias TestEnumQux =
+    { sub1 : Int
+    , sub2 : String
+    }
+
+
+decodeTestEnumQux : Json.Decode.Decoder TestEnumQux
+decodeTestEnumQux =
+    Json.Decode.succeed TestEnumQux
+        |> Json.Decode.Pipeline.required \"sub1\" Json.Decode.int
+        |> Json.Decode.Pipeline.required \"sub2\" Json.Decode.string
+
+
+type TestEnum
+    = Foo
+    | Bar Bool
+    | Qux TestEnumQux
+    | UnknownTestEnum Json.Decode.Value
+
+
+decodeTestEnum : Json.Decode.Decoder TestEnum
+decodeTestEnum =
+    Json.Decode.field \"var\" Json.Decode.string
+        |> Json.Decode.andThen
+            (\\tag ->
+                case tag of
+                    \"Foo\" ->
+                        Json.Decode.succeed Foo
+
+                    \"Bar\" ->
+                        Json.Decode.map Bar (Json.Decode.field \"vardata\" <| Json.Decode.bool)
+
+                    \"Qux\" ->
+                        Json.Decode.map Qux (Json.Decode.field \"vardata\" <| decodeTestEnumQux)
+
+                    _ ->
+                        Json.Decode.map UnknownTestEnum Json.Decode.value
+            )
+
+
+encodeTestEnum : TestEnum -> Json.Encode.Value
+encodeTestEnum var =
+    case var of
+        Foo ->
+            Json.Encode.object
+                [ ( \"var\", Json.Encode.string \"Foo\" )
+                ]
+
+        Bar value ->
+            Json.Encode.object
+                [ ( \"var\", Json.Encode.string \"Bar\" )
+                , ( \"vardata\", Json.Encode.bool <| value )
+                ]
+
+        Qux record ->
+            Json.Encode.object
+                [ ( \"var\", Json.Encode.string \"Qux\" )
+                , ( \"vardata\"
+                  , Json.Encode.object
+                        [ ( \"sub1\", Json.Encode.int <| record.sub1 )
+                        , ( \"sub2\", Json.Encode.string <| record.sub2 )
+                        ]
+                  )
+                ]
+
+        UnknownTestEnum value ->
+            value
+
+
+type TestChoice
+    = Small
+    | Medium
+    | Large
+    | UnknownTestChoice String
+
+
+allTestChoice : List TestChoice
+allTestChoice =
+    [ Small
+    , Medium
+    , Large
+    ]
+
+
+testChoiceToString : TestChoice -> String
+testChoiceToString var =
+    case var of
+        Small ->
+            \"Small\"
+
+        Medium ->
+            \"Medium\"
+
+        Large ->
+            \"Large\"
+
+        UnknownTestChoice value ->
+            value
+
+
+testChoiceFromString : String -> Maybe TestChoice
+testChoiceFromString value =
+    case value of
+        \"Small\" ->
+            Just Small
+
+        \"Medium\" ->
+            Just Medium
+
+        \"Large\" ->
+            Just Large
+
+        _ ->
+            Nothing
+
+
+decodeTestChoice : Json.Decode.Decoder TestChoice
+decodeTestChoice =
+    Json.Decode.string
+        |> Json.Decode.andThen
+            (\\value ->
+                case testChoiceFromString value of
+                    Just var ->
+                        Json.Decode.succeed var
+
+                    Nothing ->
+                        Json.Decode.succeed (UnknownTestChoice value)
+            )
+
+
+encodeTestChoice : TestChoice -> Json.Encode.Value
+encodeTestChoice var =
+    Json.Encode.string <| testChoiceToString var
+";
+
+        compare_strings(expected, create_spec_unknown_fallback().to_elm());
+    }
+
+    #[test]
+    fn validate_unknown_fallback() {
+        let mut spec = create_spec_unknown_fallback();
+        assert_eq!(spec.validate(), vec![]);
+
+        if let TypeSpec::Enum { variants, .. } = &mut spec.types[1] {
+            variants[1].name = "Unknown".into();
+        }
+        assert_eq!(
+            spec.validate(),
+            vec![Diagnostic::error(
+                "variant `TestChoice::Unknown` clashes with the variant added by `unknown_fallback`"
+            )]
+        );
+
+        let mut spec = create_spec_enum_int();
+        if let TypeSpec::Enum {
+            unknown_fallback, ..
+        } = &mut spec.types[0]
+        {
+            *unknown_fallback = true;
+        }
+        assert_eq!(
+            spec.validate(),
+            vec![Diagnostic::error(
+                "enum `TestEnum` has discriminants, so it can't have an `unknown_fallback`"
+            )]
+        );
+    }
+
+    #[test]
+    fn serialize_unknown_fallback() {
+        let json = serde_json::to_string(&create_spec_enum_simple()).unwrap();
+        assert!(!json.contains("unknown_fallback"));
+
+        let json = serde_json::to_string(&create_spec_unknown_fallback()).unwrap();
+        assert_eq!(json.matches("\"unknown_fallback\":true").count(), 2);
+    }
+
+    fn create_spec_large_int() -> ApiSpec {
+        ApiSpec {
+            module: "TestType".into(),
+            json: JsonOptions {
+                large_int_as_string: true,
+                ..Default::default()
+            },
+            types: vec![TypeSpec::Struct {
+                name: "TestStruct".into(),
+                fields: vec![
+                    StructField {
+                        name: "foo".into(),
+                        data: ("u64".into(), "Int".into()),
+                        doc: None,
+                    },
+                    StructField {
+                        name: "bar".into(),
+                        data: ("u32".into(), "Int".into()),
+                        doc: None,
+                    },
+                ],
+                doc: None,
+                targets: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rust_large_int_as_string() {
+        let expected = "\
+mod large_int_as_string {
+    use std::{fmt::Display, str::FromStr};
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Display,
+        S: serde::Serializer,
+    {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: serde::Deserializer<'de>,
+    {
+        let s = <String as serde::Deserialize>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TestStruct {
+    #[serde(with = \"large_int_as_string\")]
+    pub foo: u64,
+    pub bar: u32,
+}";
+
+        compare_strings(expected, create_spec_large_int().to_rust());
+    }
+
+    #[test]
+    fn elm_large_int_as_string() {
+        let expected = "\
+module TestType exposing (TestStruct, decodeTestStruct, encodeTestStruct)
+
+import Json.Decode
+import Json.Decode.Pipeline
+import Json.Encode
+
+
+type alias TestStruct =
+    { foo : Int
+    , bar : Int
+    }
+
+
+decodeTestStruct : Json.Decode.Decoder TestStruct
+decodeTestStruct =
+    Json.Decode.succeed TestStruct
+        |> Json.Decode.Pipeline.required \"foo\" decodeStringifiedInt
+        |> Json.Decode.Pipeline.required \"bar\" Json.Decode.int
+
+
+encodeTestStruct : TestStruct -> Json.Encode.Value
+encodeTestStruct record =
+    Json.Encode.object
+        [ ( \"foo\", encodeStringifiedInt <| record.foo )
+        , ( \"bar\", Json.Encode.int <| record.bar )
+        ]
+
+
+decodeStringifiedInt : Json.Decode.Decoder Int
+decodeStringifiedInt =
+    Json.Decode.string
+        |> Json.Decode.andThen
+            (\\value ->
+                case String.toInt value of
+                    Just int ->
+                        Json.Decode.succeed int
+
+                    Nothing ->
+                        Json.Decode.fail (\"Invalid integer: \" ++ value)
+            )
+
+
+encodeStringifiedInt : Int -> Json.Encode.Value
+encodeStringifiedInt =
+    Json.Encode.string << String.fromInt
+";
+
+        compare_strings(expected, create_spec_large_int().to_elm());
+    }
+
+    #[test]
+    fn large_int_as_elm_string() {
+        let mut spec = create_spec_large_int();
+        if let TypeSpec::Struct { fields, .. } = &mut spec.types[0] {
+            fields[0].data = ("u64".into(), "String".into());
+        }
+        assert_eq!(spec.validate(), vec![]);
+
+        let rust = spec.to_rust();
+        assert!(rust.contains("#[serde(with = \"large_int_as_string\")]\n    pub foo: u64,"));
+
+        // Kept as the string that was sent, rather than parsed into a lossy `Int`.
+        let elm = spec.to_elm();
+        assert!(elm.contains("required \"foo\" Json.Decode.string"));
+        assert!(elm.contains("( \"foo\", Json.Encode.string <| record.foo )"));
+        assert!(!elm.contains("decodeStringifiedInt"));
+    }
+
+    #[test]
+    fn validate_large_int() {
+        assert_eq!(create_spec_large_int().validate(), vec![]);
+
+        let mut spec = create_spec_large_int();
+        spec.json.large_int_as_string = false;
+
+        assert_eq!(
+            spec.validate(),
+            vec![Diagnostic::warning(
+                "`TestStruct.foo` is a Rust `u64`, which can exceed 2^53 and lose precision in \
+                JavaScript"
+            )
+            .with_hint("consider setting `json.large_int_as_string`")]
+        );
+    }
+
+    fn create_spec_well_known() -> ApiSpec {
+        ApiSpec {
+            module: "TestType".into(),
+            types: vec![TypeSpec::Struct {
+                name: "TestStruct".into(),
+                fields: vec![
+                    StructField {
+                        name: "id".into(),
+                        data: ("uuid::Uuid".into(), "Uuid".into()),
+                        doc: None,
+                    },
+                    StructField {
+                        name: "created".into(),
+                        data: ("time::OffsetDateTime".into(), "Time.Posix".into()),
+                        doc: None,
+                    },
+                    StructField {
+                        name: "updated".into(),
+                        data: ("Option<DateTime<Utc>>".into(), "Maybe Time.Posix".into()),
+                        doc: None,
+                    },
+                    StructField {
+                        name: "homepage".into(),
+                        data: ("url::Url".into(), "Url.Url".into()),
+                        doc: None,
+                    },
+                ],
+                doc: None,
+                targets: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rust_well_known() {
+        let expected = "\
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TestStruct {
+    pub id: uuid::Uuid,
+    #[serde(with = \"time::serde::rfc3339\")]
+    pub created: time::OffsetDateTime,
+    pub updated: Option<DateTime<Utc>>,
+    pub homepage: url::Url,
+}";
+
+        compare_strings(expected, create_spec_well_known().to_rust());
+    }
+
+    #[test]
+    fn rust_well_known_epoch_millis() {
+        let mut spec = create_spec_well_known();
+        spec.json.datetime = DateTimeFormat::EpochMillis;
+
+        let expected = "\
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TestStruct {
+    pub id: uuid::Uuid,
+    #[serde(with = \"time::serde::timestamp::milliseconds\")]
+    pub created: time::OffsetDateTime,
+    #[serde(with = \"chrono::serde::ts_milliseconds_option\")]
+    pub updated: Option<DateTime<Utc>>,
+    pub homepage: url::Url,
+}";
+
+        compare_strings(expected, spec.to_rust());
+    }
+
+    #[test]
+    fn elm_well_known() {
+        let expected = "\
+module TestType exposing (TestStruct, decodeTestStruct, encodeTestStruct, Uuid, decodeUuid, encodeUuid, unwrapUuid)
+
+import Iso8601
+import Json.Decode
+import Json.Decode.Pipeline
+import Json.Encode
+import Json.Encode.Extra
+import Time
+import Url
+
+
+type alias TestStruct =
+    { id : Uuid
+    , created : Time.Posix
+    , updated : Maybe Time.Posix
+    , homepage : Url.Url
+    }
+
+
+decodeTestStruct : Json.Decode.Decoder TestStruct
+decodeTestStruct =
+    Json.Decode.succeed TestStruct
+        |> Json.Decode.Pipeline.required \"id\" decodeUuid
+        |> Json.Decode.Pipeline.required \"created\" Iso8601.decoder
+        |> Json.Decode.Pipeline.required \"updated\" (Json.Decode.nullable Iso8601.decoder)
+        |> Json.Decode.Pipeline.required \"homepage\" decodeUrl
+
+
+encodeTestStruct : TestStruct -> Json.Encode.Value
+encodeTestStruct record =
+    Json.Encode.object
+        [ ( \"id\", encodeUuid <| record.id )
+        , ( \"created\", Iso8601.encode <| record.created )
+        , ( \"updated\", Json.Encode.Extra.maybe Iso8601.encode <| record.updated )
+        , ( \"homepage\", encodeUrl <| record.homepage )
+        ]
+
+
+{-| A UUID, kept as its string representation.
+-}
+type Uuid
+    = Uuid String
+
+
+unwrapUuid : Uuid -> String
+unwrapUuid (Uuid value) =
+    value
+
+
+decodeUuid : Json.Decode.Decoder Uuid
+decodeUuid =
+    Json.Decode.map Uuid Json.Decode.string
+
+
+encodeUuid : Uuid -> Json.Encode.Value
+encodeUuid (Uuid value) =
+    Json.Encode.string <| value
+
+
+decodeUrl : Json.Decode.Decoder Url.Url
+decodeUrl =
+    Json.Decode.string
+        |> Json.Decode.andThen
+            (\\value ->
+                case Url.fromString value of
+                    Just url ->
+                        Json.Decode.succeed url
+
+                    Nothing ->
+                        Json.Decode.fail (\"Invalid URL: \" ++ value)
+            )
+
+
+encodeUrl : Url.Url -> Json.Encode.Value
+encodeUrl =
+    Json.Encode.string << Url.toString
+";
+
+        compare_strings(expected, create_spec_well_known().to_elm());
+    }
+
+    #[test]
+    fn elm_imports_ignore_docs() {
+        let mut spec = create_spec_struct_simple();
+        spec.doc = Some("Paths go through Url.percentEncode and times are a Time.Posix.".into());
+        if let TypeSpec::Struct { fields, doc, .. } = &mut spec.types[0] {
+            *doc = Some("Not a Uuid, nor an ApiError.".into());
+            fields[0].doc = Some("Decoded with Iso8601.decoder and decodeUrl.".into());
+        }
+
+        let elm = spec.to_elm();
+        assert!(elm.contains("import Json.Decode\n"));
+        for unused in &[
+            "import Time",
+            "import Url",
+            "import Iso8601",
+            "type Uuid",
+            "decodeUrl :",
+            "ApiError(..)",
+        ] {
+            assert!(!elm.contains(unused), "{}", unused);
+        }
+    }
+
+    #[test]
+    fn elm_imports_from_spec_code() {
+        let mut spec = create_spec_upgrades();
+        spec.upgrades[0].fields[1].default =
+            Some(("None".into(), "Dict.get \"email\" Dict.empty".into()));
+
+        let elm = spec.to_elm();
+        assert!(elm.contains(", email = Dict.get \"email\" Dict.empty\n"));
+        assert!(elm.contains("\nimport Dict\nimport Json.Decode\n"));
+        assert!(!elm.contains("import Maybe"));
+    }
+
+    #[test]
+    fn elm_well_known_epoch_millis() {
+        let mut spec = create_spec_well_known();
+        spec.json.datetime = DateTimeFormat::EpochMillis;
+
+        let expected = "\
+module TestType exposing (TestStruct, decodeTestStruct, encodeTestStruct, Uuid, decodeUuid, encodeUuid, unwrapUuid)
+
+import Json.Decode
+import Json.Decode.Pipeline
+import Json.Encode
+import Json.Encode.Extra
+import Time
+import Url
+
+
+type alias TestStruct =
+    { id : Uuid
+    , created : Time.Posix
+    , updated : Maybe Time.Posix
+    , homepage : Url.Url
+    }
+
+
+decodeTestStruct : Json.Decode.Decoder TestStruct
+decodeTestStruct =
+    Json.Decode.succeed TestStruct
+        |> Json.Decode.Pipeline.required \"id\" decodeUuid
+        |> Json.Decode.Pipeline.required \"created\" (Json.Decode.map Time.millisToPosix Json.Decode.int)
+        |> Json.Decode.Pipeline.required \"updated\" (Json.Decode.nullable (Json.Decode.map Time.millisToPosix Json.Decode.int))
+        |> Json.Decode.Pipeline.required \"homepage\" decodeUrl
+
+
+encodeTestStruct : TestStruct -> Json.Encode.Value
+encodeTestStruct record =
+    Json.Encode.object
+        [ ( \"id\", encodeUuid <| record.id )
+        , ( \"created\", (Json.Encode.int << Time.posixToMillis) <| record.created )
+        , ( \"updated\", Json.Encode.Extra.maybe (Json.Encode.int << Time.posixToMillis) <| record.updated )
+        , ( \"homepage\", encodeUrl <| record.homepage )
+        ]
+
+
+{-| A UUID, kept as its string representation.
+-}
+type Uuid
+    = Uuid String
+
+
+unwrapUuid : Uuid -> String
+unwrapUuid (Uuid value) =
+    value
+
+
+decodeUuid : Json.Decode.Decoder Uuid
+decodeUuid =
+    Json.Decode.map Uuid Json.Decode.string
+
+
+encodeUuid : Uuid -> Json.Encode.Value
+encodeUuid (Uuid value) =
+    Json.Encode.string <| value
+
+
+decodeUrl : Json.Decode.Decoder Url.Url
+decodeUrl =
+    Json.Decode.string
+        |> Json.Decode.andThen
+            (\\value ->
+                case Url.fromString value of
+                    Just url ->
+                        Json.Decode.succeed url
+
+                    Nothing ->
+                        Json.Decode.fail (\"Invalid URL: \" ++ value)
+            )
+
+
+encodeUrl : Url.Url -> Json.Encode.Value
+encodeUrl =
+    Json.Encode.string << Url.toString
+";
+
+        compare_strings(expected, spec.to_elm());
+    }
+
+    fn create_spec_external() -> ApiSpec {
+        ApiSpec {
+            module: "TestType".into(),
+            externals: vec![ExternalType {
+                rust: "money::Money".into(),
+                elm: "Money.Money".into(),
+                elm_module: Some("Money".into()),
+                decoder: "Money.decoder".into(),
+                encoder: "Money.encode".into(),
+            }],
+            types: vec![TypeSpec::Struct {
+                name: "TestStruct".into(),
+                fields: vec![
+                    StructField {
+                        name: "price".into(),
+                        data: ("money::Money".into(), "Money.Money".into()),
+                        doc: None,
+                    },
+                    StructField {
+                        name: "discount".into(),
+                        data: ("Option<money::Money>".into(), "Maybe Money.Money".into()),
+                        doc: None,
+                    },
+                ],
+                doc: None,
+                targets: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn elm_external() {
+        let expected = "\
+module TestType exposing (TestStruct, decodeTestStruct, encodeTestStruct)
+
+import Json.Decode
+import Json.Decode.Pipeline
+import Json.Encode
+import Json.Encode.Extra
+import Money
+
+
+type alias TestStruct =
+    { price : Money.Money
+    , discount : Maybe Money.Money
+    }
+
+
+decodeTestStruct : Json.Decode.Decoder TestStruct
+decodeTestStruct =
+    Json.Decode.succeed TestStruct
+        |> Json.Decode.Pipeline.required \"price\" Money.decoder
+        |> Json.Decode.Pipeline.required \"discount\" (Json.Decode.nullable Money.decoder)
+
+
+encodeTestStruct : TestStruct -> Json.Encode.Value
+encodeTestStruct record =
+    Json.Encode.object
+        [ ( \"price\", Money.encode <| record.price )
+        , ( \"discount\", Json.Encode.Extra.maybe Money.encode <| record.discount )
+        ]
+";
+
+        compare_strings(expected, create_spec_external().to_elm());
+    }
+
+    #[test]
+    fn validate_external() {
+        assert_eq!(create_spec_external().validate(), vec![]);
+
+        let mut spec = create_spec_external();
+        spec.externals[0].elm = "Money".into();
+
+        assert_eq!(
+            spec.validate(),
+            vec![Diagnostic::warning(
+                "`TestStruct.price` is the external type `money::Money`, which is declared as \
+                Elm `Money`, not `Money.Money`"
+            )]
+        );
+    }
+
+    const DSL_SPEC: &str = "\
+/// Test API.
+module TestType
+
+/// A user.
+type User {
+    id: UserId,
+    /// Display name.
+    name: String,
+    email: Option<String>, // optional
+    created: chrono::DateTime<chrono::Utc>,
+}
+
+type UserId(u32)
+type UserIds = Vec<UserId>
+
+enum Shape = Circle { r: f64 } | Square(f64) | Empty
+enum Level =
+    | Low = 1
+    | High = -2
+";
+
+    #[test]
+    fn rust_from_dsl() {
+        let expected = "\
+//! Test API.
+
+/// A user.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct User {
+    pub id: UserId,
+    /// Display name.
+    pub name: String,
+    pub email: Option<String>,
+    pub created: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct UserId(pub u32);
+
+pub type UserIds = Vec<UserId>;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(tag = \"var\", content = \"vardata\")]
+pub enum Shape {
+    Circle {
+        r: f64,
+    },
+    Square(f64),
+    Empty,
+}
+
+#[derive(Debug, Clone, serde_repr::Serialize_repr, serde_repr::Deserialize_repr)]
+#[repr(i32)]
+pub enum Level {
+    Low = 1,
+    High = -2,
+}";
+
+        compare_strings(expected, ApiSpec::from_dsl(DSL_SPEC).unwrap().to_rust());
+    }
+
+    #[test]
+    fn dsl_type_pairs() {
+        let spec = ApiSpec::from_dsl(DSL_SPEC).unwrap();
+        assert_eq!(spec.doc.as_deref(), Some("Test API."));
+
+        let pairs = spec
+            .types
+            .iter()
+            .flat_map(|t| t.type_pairs())
+            .map(|(path, (rust, elm))| format!("{}: {} / {}", path, rust, elm))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pairs,
+            vec![
+                "User.id: UserId / UserId",
+                "User.name: String / String",
+                "User.email: Option<String> / Maybe String",
+                "User.created: chrono::DateTime<chrono::Utc> / Time.Posix",
+                "UserId: u32 / Int",
+                "UserIds: Vec<UserId> / List UserId",
+                "Shape::Circle.r: f64 / Float",
+                "Shape::Square: f64 / Float",
+            ]
+        );
+        assert_eq!(spec.validate(), vec![]);
+    }
+
+    #[test]
+    fn dsl_elm_types() {
+        let spec = ApiSpec::from_dsl(
+            "module Api\n\
+            type Counts = HashMap<String, u32>\n\
+            type Stamp(chrono::DateTime<chrono::Utc> as Time.Posix)\n\
+            type Ids { all: Vec<Vec<u32>> as List (List Int), day: chrono::NaiveDate as String }\n",
+        )
+        .unwrap();
+
+        let pairs = spec
+            .types
+            .iter()
+            .flat_map(|t| t.type_pairs())
+            .map(|(path, (rust, elm))| format!("{}: {} / {}", path, rust, elm))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pairs,
+            vec![
+                "Counts: HashMap<String, u32> / Dict String Int",
+                "Stamp: chrono::DateTime<chrono::Utc> / Time.Posix",
+                "Ids.all: Vec<Vec<u32>> / List (List Int)",
+                "Ids.day: chrono::NaiveDate / String",
+            ]
+        );
+    }
+
+    #[test]
+    fn elm_nested_types_from_dsl() {
+        let spec = ApiSpec::from_dsl(
+            "module Api\n\
+            type Tags { all: Option<Vec<String>>, some: Vec<Option<u32>> }\n\
+            type Counts = BTreeMap<String, Vec<Option<u32>>>\n",
+        )
+        .unwrap();
+        assert_eq!(spec.validate(), vec![]);
+
+        let elm = spec.to_elm();
+        for expected in &[
+            "\nimport Dict exposing (Dict)\n",
+            "    { all : Maybe (List String)\n    , some : List (Maybe Int)\n",
+            "required \"all\" (Json.Decode.nullable (Json.Decode.list Json.Decode.string))\n",
+            "required \"some\" (Json.Decode.list (Json.Decode.nullable Json.Decode.int))\n",
+            "( \"all\", Json.Encode.Extra.maybe (Json.Encode.list Json.Encode.string) <| record.all )",
+            "( \"some\", Json.Encode.list (Json.Encode.Extra.maybe Json.Encode.int) <| record.some )",
+            "type alias Counts =\n    Dict String (List (Maybe Int))\n",
+            "    Json.Decode.dict (Json.Decode.list (Json.Decode.nullable Json.Decode.int))\n",
+            "    Json.Encode.dict identity (Json.Encode.list (Json.Encode.Extra.maybe Json.Encode.int))",
+        ] {
+            assert!(elm.contains(expected), "{}\n{}", expected, elm);
+        }
+        assert_eq!(
+            ts_type("Dict String (List (Maybe Int))", &spec),
+            "Record<string, Array<number | null>>"
+        );
+    }
+
+    #[test]
+    fn validate_elm_codecs() {
+        let mut spec = create_spec_struct_simple();
+        if let TypeSpec::Struct { fields, .. } = &mut spec.types[0] {
+            fields[0].data = ("HashMap<u32, String>".into(), "Dict Int String".into());
+            fields[1].data = ("Vec<(u32, u32)>".into(), "List (Int, Int)".into());
+        }
+
+        assert_eq!(
+            spec.validate(),
+            vec![
+                Diagnostic::error(
+                    "`TestStruct.foo` is an Elm `Dict Int String`, but JSON objects only have \
+                    `String` keys"
+                ),
+                Diagnostic::error(
+                    "`TestStruct.bar` is an Elm `List (Int, Int)`, and tuples and records have no \
+                    generated JSON codecs"
+                )
+                .with_hint("use a type of the spec instead"),
+            ]
+        );
+    }
+
+    #[test]
+    fn dsl_errors() {
+        let error = |source: &str| ApiSpec::from_dsl(source).unwrap_err().to_string();
+
+        assert_eq!(
+            error("type User { id: u32 }"),
+            "1:1: expected `module`, found `type`"
+        );
+        assert_eq!(
+            error("module Api\ntype User { id: u32, name: String\n"),
+            "2:34: expected `}`, found the end of the file"
+        );
+        assert_eq!(
+            error("module Api\n\ntype User { id u32 }"),
+            "3:16: expected `:`, found `u32`"
+        );
+        assert_eq!(
+            error("module Api\ntype Ids = HashMap<u32, String>"),
+            "2:12: `HashMap<u32, String>` has no Elm equivalent, give one with `as`"
+        );
+        assert_eq!(
+            error("module Api\ntype Ids = HashMap<u32, String> as\n"),
+            "2:35: expected an Elm type, found the end of the file"
+        );
+        assert_eq!(
+            error("module Api\ntype Ids = HashMap<u32, String> as (List Int\n"),
+            "2:45: expected `)`, found the end of the file"
+        );
+        assert_eq!(
+            error("module Api\ntype Pair = Pair as (Int, String)\n"),
+            "2:25: expected `)`, found `,`"
+        );
+        assert_eq!(
+            error("module Api\nenum Level = Low = 1 | High = x"),
+            "2:31: expected a discriminant, found `x`"
+        );
+        assert_eq!(
+            error("module Api\n  enum Shape = Circle; Square"),
+            "2:22: unexpected character `;`"
+        );
+        assert_eq!(
+            error("module Api\nstruct User {}"),
+            "2:1: expected `type` or `enum`, found `struct`"
+        );
+    }
+
+    const YAML_SPEC: &str = "\
+module: TestType
+types:
+  - Struct:
+      name: User
+      fields:
+        - name: id
+          data: [u64, Int]
+  - Enum:
+      name: Shape
+      variants:
+        - name: Circle
+          data:
+            Struct:
+              - name: r
+                data: [f64, Float]
+        - name: Square
+          data: None
+          discriminant: 2
+endpoints:
+  - name: get_user
+    method: GET
+    path: \"/users/{id}\"
+    params:
+      - name: \"id\"
+        data: [u64, Int]
+";
+
+    #[test]
+    fn parse_spans() {
+        let spec = ApiSpec::parse(YAML_SPEC, SpecFormat::Yaml).unwrap();
+        let span = |path| spec.span(path).map(|s| (s.line, s.column, s.len));
+
+        assert_eq!(span("User"), Some((4, 13, 4)));
+        assert_eq!(span("User.id"), Some((6, 17, 2)));
+        assert_eq!(span("Shape::Circle"), Some((11, 17, 6)));
+        assert_eq!(span("Shape::Circle.r"), Some((14, 23, 1)));
+        assert_eq!(span("get_user.id"), Some((24, 16, 2)));
+        assert_eq!(span("Missing"), None);
+
+        let toml = "module = \"TestType\"\n\n[[types]]\n[types.Newtype]\nname = \"UserId\"\n\
+            data = [\"u32\", \"Int\"]\n";
+        let spec = ApiSpec::parse(toml, SpecFormat::Toml).unwrap();
+        assert_eq!(
+            spec.span("UserId"),
+            Some(Span {
+                line: 5,
+                column: 9,
+                len: 6
+            })
+        );
+
+        let spec = ApiSpec::parse(DSL_SPEC, SpecFormat::Dsl).unwrap();
+        assert_eq!(
+            spec.span("Shape::Circle.r"),
+            Some(Span {
+                line: 16,
+                column: 23,
+                len: 1
+            })
+        );
+    }
+
+    #[test]
+    fn render_validation_errors() {
+        let expected = "\
+warning: `User.id` is a Rust `u64`, which can exceed 2^53 and lose precision in JavaScript
+ --> api.yaml:6:17
+  |
+6 |         - name: id
+  |                 ^^
+  = hint: consider setting `json.large_int_as_string`
+
+error: enum `Shape` must have a discriminant on every variant or on none
+ --> api.yaml:9:13
+  |
+9 |       name: Shape
+  |             ^^^^^
+
+warning: `get_user.id` is a Rust `u64`, which can exceed 2^53 and lose precision in JavaScript
+  --> api.yaml:24:16
+   |
+24 |       - name: \"id\"
+   |                ^^
+   = hint: consider setting `json.large_int_as_string`";
+
+        let spec = ApiSpec::parse(YAML_SPEC, SpecFormat::Yaml).unwrap();
+        let rendered = spec
+            .validate()
+            .iter()
+            .map(|d| d.render("api.yaml", YAML_SPEC))
+            .collect::<Vec<_>>();
+        compare_strings(expected, rendered.join("\n\n"));
+    }
+
+    #[test]
+    fn render_parse_errors() {
+        let expected = "\
+error: endpoints[0].method: unknown variant `Get`, expected one of `GET`, `POST`, `PUT`, `PATCH`, `DELETE`
+  --> api.yaml:21:13
+   |
+21 |     method: Get
+   |             ^^^
+   = hint: did you mean `GET`?
+
+error: unexpected keys in table: nmae, available keys: name, data, doc, targets
+ --> api.toml:5:1
+  |
+5 | nmae = \"Ids\"
+  | ^^^^
+  = hint: did you mean `name`?
+
+error: expected `,` or `}`
+ --> api.json:4:3
+  |
+4 |   \"endpoints\": []
+  |   ^
+
+error: `HashMap<u32, String>` has no Elm equivalent, give one with `as`
+ --> api.types:3:12
+  |
+3 | type Ids = HashMap<u32, String>
+  |            ^^^^^^^^^^^^^^^^^^^^";
+
+        let sources = [
+            (
+                "api.yaml",
+                SpecFormat::Yaml,
+                YAML_SPEC.replace("method: GET", "method: Get"),
+            ),
+            (
+                "api.toml",
+                SpecFormat::Toml,
+                "module = \"TestType\"\n\n[[types]]\n[types.Alias]\nnmae = \"Ids\"\n".to_string(),
+            ),
+            (
+                "api.json",
+                SpecFormat::Json,
+                "{\n  \"module\": \"TestType\",\n  \"types\": []\n  \"endpoints\": []\n}"
+                    .to_string(),
+            ),
+            (
+                "api.types",
+                SpecFormat::Dsl,
+                "module TestType\n\ntype Ids = HashMap<u32, String>\n".to_string(),
+            ),
+        ];
+        let rendered = sources
+            .iter()
+            .map(|(file, format, source)| {
+                ApiSpec::parse(source, *format)
+                    .unwrap_err()
+                    .render(file, source)
+            })
+            .collect::<Vec<_>>();
+        compare_strings(expected, rendered.join("\n\n"));
+    }
+
+    #[test]
+    fn elm_layout_pipeline_with_case() {
+        use elm::Expr;
+
+        let decoder = Expr::pipeline(
+            Expr::line("Json.Decode.int"),
+            vec![
+                Expr::line("Json.Decode.map abs"),
+                Expr::call(
+                    "Json.Decode.andThen",
+                    vec![Expr::parens(Expr::lambda(
+                        "value",
+                        Expr::case(
+                            "value",
+                            vec![
+                                ("0".into(), Expr::line("Json.Decode.fail \"zero\"")),
+                                ("_".into(), Expr::line("Json.Decode.succeed value")),
+                            ],
+                        ),
+                    ))],
+                ),
+            ],
+        );
+
+        let expected = "\
+decodeCount : Json.Decode.Decoder Int
+decodeCount =
+    Json.Decode.int
+        |> Json.Decode.map abs
+        |> Json.Decode.andThen
+            (\\value ->
+                case value of
+                    0 ->
+                        Json.Decode.fail \"zero\"
+
+                    _ ->
+                        Json.Decode.succeed value
+            )";
+
+        compare_strings(
+            expected,
+            elm::definition(
+                "decodeCount : Json.Decode.Decoder Int",
+                "decodeCount",
+                &decoder,
+            ),
+        );
+
+        // Lambdas and parens whose body fits on a line stay on it.
+        let short = Expr::parens(Expr::lambda("_", Expr::line("decodeCount")));
+        compare_strings("(\\_ -> decodeCount)", short.to_elm());
+    }
+
+    #[test]
+    fn elm_layout_nested_lists_and_records() {
+        use elm::Expr;
+
+        let pair = |key: &str, value: Expr| Expr::Tuple(vec![Expr::line(key), value]);
+        let encoder = Expr::call(
+            "Json.Encode.object",
+            vec![Expr::List(vec![
+                pair("\"var\"", Expr::line("Json.Encode.string \"Move\"")),
+                pair(
+                    "\"vardata\"",
+                    Expr::call(
+                        "Json.Encode.object",
+                        vec![Expr::List(vec![
+                            pair("\"x\"", Expr::line("Json.Encode.int <| record.x")),
+                            pair("\"y\"", Expr::line("Json.Encode.int <| record.y")),
+                        ])],
+                    ),
+                ),
+                pair("\"tags\"", Expr::List(vec![])),
+            ])],
+        );
+
+        let expected = "\
+Json.Encode.object
+    [ ( \"var\", Json.Encode.string \"Move\" )
+    , ( \"vardata\"
+      , Json.Encode.object
+            [ ( \"x\", Json.Encode.int <| record.x )
+            , ( \"y\", Json.Encode.int <| record.y )
+            ]
+      )
+    , ( \"tags\", [] )
+    ]";
+        compare_strings(expected, encoder.to_elm());
+
+        let record = Expr::Record(vec![
+            ("id".into(), Expr::line("old.id")),
+            (
+                "tags".into(),
+                Expr::pipeline(
+                    Expr::line("old.tags"),
+                    vec![Expr::line("List.map String.trim")],
+                ),
+            ),
+            ("extra".into(), Expr::Record(vec![])),
+        ]);
+
+        let expected = "\
+upgrade : Old -> New
+upgrade old =
+    { id = old.id
+    , tags =
+        old.tags
+            |> List.map String.trim
+    , extra = {}
+    }";
+        compare_strings(
+            expected,
+            elm::definition("upgrade : Old -> New", "upgrade old", &record),
+        );
+    }
 }
@@ -0,0 +1,156 @@
+//! Elm ports that carry generated types, with typed wrappers and TypeScript declarations for
+//! the JavaScript side.
+
+use crate::{
+    elm::{self, Expr, Uses},
+    endpoint::elm_camel_case,
+    graph::ElmGraphs,
+    spec::*,
+    ts::ts_type,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum PortDirection {
+    /// From JavaScript to Elm: a subscription.
+    Incoming,
+    /// From Elm to JavaScript: a command.
+    Outgoing,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Port {
+    /// `snake_case` name, e.g. `save_user`. The typed wrapper is named in `camelCase`, and the
+    /// port itself, as JavaScript sees it, gets a `Port` suffix: `app.ports.saveUserPort`.
+    pub name: String,
+    pub direction: PortDirection,
+    /// Type of the values sent through the port.
+    pub payload: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+}
+
+impl Port {
+    pub fn elm_name(&self) -> String {
+        elm_camel_case(&self.name)
+    }
+
+    fn elm_port_name(&self) -> String {
+        format!("{}Port", self.elm_name())
+    }
+
+    pub fn to_elm(&self, spec: &ApiSpec, graphs: &ElmGraphs, uses: &Uses) -> String {
+        uses.module(match self.direction {
+            PortDirection::Outgoing => "Json.Encode",
+            PortDirection::Incoming => "Json.Decode",
+        });
+        uses.code(&self.payload);
+
+        let name = self.elm_name();
+        let port = self.elm_port_name();
+        let payload = (self.payload.clone(), self.payload.clone());
+
+        let (port_decl, wrapper) = match self.direction {
+            PortDirection::Outgoing => (
+                format!("port {} : Json.Encode.Value -> Cmd msg", port),
+                elm::definition(
+                    &format!("{} : {} -> Cmd msg", name, elm_type_arg(&self.payload)),
+                    &format!("{} value", name),
+                    &Expr::line(format!(
+                        "{} ({} value)",
+                        port,
+                        elm_encoder(&payload, spec, uses)
+                    )),
+                ),
+            ),
+            PortDirection::Incoming => (
+                format!("port {} : (Json.Decode.Value -> msg) -> Sub msg", port),
+                elm::definition(
+                    &format!(
+                        "{} : (Result Json.Decode.Error {} -> msg) -> Sub msg",
+                        name,
+                        elm_type_arg(&self.payload)
+                    ),
+                    &format!("{} toMsg", name),
+                    &Expr::line(format!(
+                        "{} (toMsg << Json.Decode.decodeValue {})",
+                        port,
+                        elm_decoder(&payload, "", spec, graphs, uses)
+                    )),
+                ),
+            ),
+        };
+
+        format!(
+            "{}{}{}{}",
+            port_decl,
+            elm::DECLARATION_SEPARATOR,
+            elm_doc_comment(&self.doc, &[]),
+            wrapper
+        )
+    }
+
+    fn to_ts(&self, spec: &ApiSpec) -> String {
+        let callback = format!("(value: {}) => void", ts_type(&self.payload, spec));
+
+        let methods = match self.direction {
+            PortDirection::Outgoing => vec![
+                format!("subscribe(callback: {}): void;", callback),
+                format!("unsubscribe(callback: {}): void;", callback),
+            ],
+            PortDirection::Incoming => vec![format!(
+                "send(value: {}): void;",
+                ts_type(&self.payload, spec)
+            )],
+        };
+
+        let doc = match &self.doc {
+            Some(doc) => format!("{}/** {} */\n", INDENT, doc.trim().replace('\n', " ")),
+            None => "".into(),
+        };
+
+        format!(
+            "{}{}{}: {{\n{}\n{}}};",
+            doc,
+            INDENT,
+            self.elm_port_name(),
+            methods
+                .iter()
+                .map(|method| format!("{}{}", INDENT.repeat(2), method))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            INDENT
+        )
+    }
+}
+
+impl ApiSpec {
+    /// TypeScript declarations of the ports, as `app.ports` of the Elm app, and of the types
+    /// that go through them.
+    pub fn ports_to_ts(&self) -> String {
+        let roots = self
+            .ports
+            .iter()
+            .map(|port| port.payload.as_str())
+            .collect::<Vec<_>>();
+
+        let ports = self
+            .ports
+            .iter()
+            .map(|port| port.to_ts(self))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let ports = if ports.is_empty() {
+            "export interface Ports {}".to_string()
+        } else {
+            format!("export interface Ports {{\n{}\n}}", ports)
+        };
+        let ports = format!("/** The ports of the Elm app, `app.ports`. */\n{}\n", ports);
+
+        match self.ts_types(&roots) {
+            types if types.is_empty() => ports,
+            types => format!("{}\n\n{}", types, ports),
+        }
+    }
+}
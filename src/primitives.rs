@@ -0,0 +1,46 @@
+//! Rust primitive types and how they map onto Elm and JSON.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Primitive {
+    pub rust: &'static str,
+    pub elm: &'static str,
+    /// Every value survives a round trip through a JavaScript number (at most 2^53 in magnitude).
+    pub json_safe: bool,
+}
+
+const fn primitive(rust: &'static str, elm: &'static str, json_safe: bool) -> Primitive {
+    Primitive {
+        rust,
+        elm,
+        json_safe,
+    }
+}
+
+pub const PRIMITIVES: &[Primitive] = &[
+    primitive("bool", "Bool", true),
+    primitive("u8", "Int", true),
+    primitive("u16", "Int", true),
+    primitive("u32", "Int", true),
+    primitive("u64", "Int", false),
+    primitive("u128", "Int", false),
+    primitive("usize", "Int", false),
+    primitive("i8", "Int", true),
+    primitive("i16", "Int", true),
+    primitive("i32", "Int", true),
+    primitive("i64", "Int", false),
+    primitive("i128", "Int", false),
+    primitive("isize", "Int", false),
+    primitive("f32", "Float", true),
+    primitive("f64", "Float", true),
+    primitive("char", "String", true),
+    primitive("String", "String", true),
+];
+
+pub fn lookup(rust_type: &str) -> Option<&'static Primitive> {
+    PRIMITIVES.iter().find(|p| p.rust == rust_type.trim())
+}
+
+/// Integer types whose values may not fit in a JavaScript number.
+pub fn is_large_int(rust_type: &str) -> bool {
+    matches!(lookup(rust_type), Some(p) if p.elm == "Int" && !p.json_safe)
+}
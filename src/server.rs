@@ -0,0 +1,410 @@
+//! Server stubs for the endpoints: a trait to implement and a router that calls it.
+
+use crate::{endpoint::*, spec::*};
+
+/// rustfmt's `max_width`.
+const MAX_WIDTH: usize = 100;
+/// rustfmt's `chain_width` and `fn_call_width`.
+const CALL_WIDTH: usize = 60;
+
+/// `head(params)tail` on one line if it fits, or with one parameter per line as rustfmt would
+/// write it.
+fn rust_fn(indent: usize, head: &str, params: &[String], tail: &str) -> String {
+    let pad = INDENT.repeat(indent);
+    let single = format!("{}{}({}){}", pad, head, params.join(", "), tail);
+
+    if single.chars().count() <= MAX_WIDTH {
+        single
+    } else {
+        let params = params
+            .iter()
+            .map(|param| {
+                format!(
+                    "{}\n",
+                    rust_generic_line(indent + 1, &format!("{},", param))
+                )
+            })
+            .collect::<String>();
+        format!(
+            "{}{}(\n{}{}",
+            pad,
+            head,
+            params,
+            rust_generic_line(indent, &format!("){}", tail))
+        )
+    }
+}
+
+/// `line` at `indent` if it fits, otherwise with the arguments of its first generic type on lines
+/// of their own, as rustfmt breaks e.g. `x: Query<LongName>` and `-> Result<A, B>`. A generic type
+/// followed by bounds, as in `impl Future<..> + Send`, is left on one line: rustfmt lets those run
+/// past `MAX_WIDTH` before it breaks them, in ways this doesn't follow.
+fn rust_generic_line(indent: usize, line: &str) -> String {
+    let pad = INDENT.repeat(indent);
+    let single = format!("{}{}", pad, line);
+    if single.chars().count() <= MAX_WIDTH {
+        return single;
+    }
+
+    let open = match line.find('<') {
+        Some(open) => open,
+        None => return single,
+    };
+    let mut depth = 0;
+    let mut start = open + 1;
+    let mut args = vec![];
+    for (i, c) in line.char_indices().skip_while(|(i, _)| *i <= open) {
+        match c {
+            '<' | '(' => depth += 1,
+            ')' => depth -= 1,
+            '>' if depth > 0 => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(line[start..i].trim());
+                start = i + 1;
+            }
+            '>' if line[i + 1..].starts_with(" + ") => return single,
+            '>' => {
+                args.push(line[start..i].trim());
+                let args = args
+                    .iter()
+                    .map(|arg| format!("{}{}{},\n", pad, INDENT, arg))
+                    .collect::<String>();
+                return format!("{}{}\n{}{}{}", pad, &line[..=open], args, pad, &line[i..]);
+            }
+            _ => {}
+        }
+    }
+
+    single
+}
+
+/// `.method(args)` as part of a vertical chain at `indent`.
+fn rust_chain_call(indent: usize, method: &str, args: &[String]) -> String {
+    let pad = INDENT.repeat(indent);
+    let single = format!("{}.{}({})", pad, method, args.join(", "));
+
+    if args.join(", ").chars().count() <= CALL_WIDTH && single.chars().count() <= MAX_WIDTH {
+        single
+    } else {
+        let args = args
+            .iter()
+            .map(|arg| format!("{}{}{},\n", pad, INDENT, arg))
+            .collect::<String>();
+        format!("{}.{}(\n{}{})", pad, method, args, pad)
+    }
+}
+
+/// `first.a().b()` on one line if the calls fit in rustfmt's `chain_width`, otherwise with each
+/// call on its own line.
+fn rust_chain(indent: usize, first: &str, calls: &[String]) -> String {
+    let single = format!("{}{}", first, calls.concat());
+
+    if calls.concat().chars().count() <= CALL_WIDTH
+        && INDENT.len() * indent + single.chars().count() < MAX_WIDTH
+    {
+        single
+    } else {
+        let pad = INDENT.repeat(indent + 1);
+        let calls = calls
+            .iter()
+            .map(|call| format!("\n{}{}", pad, call))
+            .collect::<String>();
+        format!("{}{}", first, calls)
+    }
+}
+
+impl HttpMethod {
+    /// The `axum::routing` function for this method.
+    fn axum_routing(self) -> &'static str {
+        match self {
+            Self::Get => "get",
+            Self::Post => "post",
+            Self::Put => "put",
+            Self::Patch => "patch",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+impl Endpoint {
+    /// Path parameters in the order they appear in the path, which is the order axum extracts
+    /// them in.
+    fn path_params(&self) -> Vec<&StructField> {
+        self.path_segments()
+            .into_iter()
+            .filter_map(|segment| match segment {
+                PathSegment::Param(name) => self.params.iter().find(|param| param.name == name),
+                PathSegment::Literal(_) => None,
+            })
+            .collect()
+    }
+
+    fn rust_query_struct_name(&self) -> String {
+        let mut name = String::new();
+        for word in self.name.split('_') {
+            let mut chars = word.chars();
+            if let Some(c) = chars.next() {
+                name.extend(c.to_uppercase());
+                name.push_str(chars.as_str());
+            }
+        }
+        name + "Query"
+    }
+
+    /// The arguments of the server method: path params, query params, then the body.
+    fn rust_args(&self) -> Vec<(String, String)> {
+        self.path_params()
+            .into_iter()
+            .chain(&self.query)
+            .map(|param| (param.name.clone(), rust_type(&param.data.0)))
+            .chain(
+                self.body
+                    .iter()
+                    .map(|data| ("body".to_string(), rust_type(&data.0))),
+            )
+            .collect()
+    }
+
+    fn rust_response_type(&self) -> String {
+        self.response
+            .as_ref()
+            .map_or("()".into(), |data| rust_type(&data.0))
+    }
+
+    pub fn to_rust_server_method(&self) -> String {
+        let error = match &self.error {
+            Some(data) => format!("(axum::http::StatusCode, {})", rust_type(&data.0)),
+            None => "axum::http::StatusCode".into(),
+        };
+
+        let params = std::iter::once("&self".to_string())
+            .chain(
+                self.rust_args()
+                    .into_iter()
+                    .map(|(name, ty)| format!("{}: {}", name, ty)),
+            )
+            .collect::<Vec<_>>();
+
+        format!(
+            "{}{}",
+            rust_doc_comment(&self.doc, 1),
+            rust_fn(
+                1,
+                &format!("fn {}", self.name),
+                &params,
+                &format!(
+                    " -> impl std::future::Future<Output = Result<{}, {}>> + Send;",
+                    self.rust_response_type(),
+                    error
+                )
+            )
+        )
+    }
+
+    /// The `Deserialize` struct the query string is extracted into, if there are query params.
+    pub fn to_rust_query_struct(&self) -> Option<String> {
+        if self.query.is_empty() {
+            return None;
+        }
+
+        let fields = self
+            .query
+            .iter()
+            .map(|param| {
+                format!(
+                    "{}{}: {},\n",
+                    INDENT.repeat(2),
+                    param.name,
+                    rust_type(&param.data.0)
+                )
+            })
+            .collect::<String>();
+
+        Some(format!(
+            "{pad}#[derive(serde::Deserialize)]\n{pad}pub(super) struct {} {}",
+            self.rust_query_struct_name(),
+            rust_braces(&fields, 1),
+            pad = INDENT
+        ))
+    }
+
+    pub fn to_rust_handler(&self) -> String {
+        let mut extractors =
+            vec!["axum::extract::State(server): axum::extract::State<Arc<S>>".to_string()];
+
+        let path_params = self.path_params();
+        match path_params.as_slice() {
+            [] => {}
+            [param] => extractors.push(format!(
+                "axum::extract::Path({}): axum::extract::Path<{}>",
+                param.name,
+                rust_type(&param.data.0)
+            )),
+            params => extractors.push(format!(
+                "axum::extract::Path(({})): axum::extract::Path<({})>",
+                params
+                    .iter()
+                    .map(|param| param.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                params
+                    .iter()
+                    .map(|param| rust_type(&param.data.0))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+
+        if !self.query.is_empty() {
+            extractors.push(format!(
+                "axum::extract::Query(query): axum::extract::Query<{}>",
+                self.rust_query_struct_name()
+            ));
+        }
+
+        if let Some(data) = &self.body {
+            extractors.push(format!(
+                "axum::Json(body): axum::Json<{}>",
+                rust_type(&data.0)
+            ));
+        }
+
+        let ok = match &self.response {
+            Some(data) => format!("axum::Json<{}>", rust_type(&data.0)),
+            None => "()".into(),
+        };
+        let error = match &self.error {
+            Some(data) => format!(
+                "(axum::http::StatusCode, axum::Json<{}>)",
+                rust_type(&data.0)
+            ),
+            None => "axum::http::StatusCode".into(),
+        };
+
+        let args = self
+            .path_params()
+            .into_iter()
+            .map(|param| param.name.clone())
+            .chain(
+                self.query
+                    .iter()
+                    .map(|param| format!("query.{}", param.name)),
+            )
+            .chain(self.body.iter().map(|_| "body".to_string()))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut calls = vec![format!(".{}({})", self.name, args), ".await".into()];
+        if self.response.is_some() {
+            calls.push(".map(axum::Json)".into());
+        }
+        if self.error.is_some() {
+            calls.push(".map_err(|(status, error)| (status, axum::Json(error)))".into());
+        }
+
+        format!(
+            "{}\n{}{}\n{}}}",
+            rust_fn(
+                1,
+                &format!("pub(super) async fn {}<S: Server>", self.name),
+                &extractors,
+                &format!(" -> Result<{}, {}> {{", ok, error)
+            ),
+            INDENT.repeat(2),
+            rust_chain(2, "server", &calls),
+            INDENT
+        )
+    }
+}
+
+impl ApiSpec {
+    /// Server trait, router and handlers for the endpoints, if a server framework is set.
+    pub fn server_to_rust(&self) -> Option<String> {
+        match self.rust.server {
+            Some(ServerFramework::Axum) if !self.endpoints.is_empty() => Some(self.axum_server()),
+            _ => None,
+        }
+    }
+
+    fn axum_server(&self) -> String {
+        let vis = self.rust.visibility.to_rust();
+
+        let methods = self
+            .endpoints
+            .iter()
+            .map(Endpoint::to_rust_server_method)
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let server = format!(
+            "\
+/// Handlers for the endpoints of the API. Serve an implementation with [`router`].
+{}trait Server: Send + Sync + 'static {{
+{}
+}}",
+            vis, methods
+        );
+
+        // axum wants every method on a path in a single `route` call.
+        let mut paths: Vec<(&str, Vec<&Endpoint>)> = vec![];
+        for endpoint in &self.endpoints {
+            match paths.iter_mut().find(|(path, _)| *path == endpoint.path) {
+                Some((_, endpoints)) => endpoints.push(endpoint),
+                None => paths.push((&endpoint.path, vec![endpoint])),
+            }
+        }
+
+        let routes = paths
+            .iter()
+            .map(|(path, endpoints)| {
+                let handlers = endpoints
+                    .iter()
+                    .map(|e| format!("{}(handlers::{}::<S>)", e.method.axum_routing(), e.name))
+                    .collect::<Vec<_>>();
+                let method_router = rust_chain(
+                    3,
+                    &format!("axum::routing::{}", handlers[0]),
+                    &handlers[1..]
+                        .iter()
+                        .map(|handler| format!(".{}", handler))
+                        .collect::<Vec<_>>(),
+                );
+
+                rust_chain_call(2, "route", &[format!("\"{}\"", path), method_router])
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let router = format!(
+            "\
+/// An axum router that serves the endpoints with `server`.
+{}fn router<S: Server>(server: S) -> axum::Router {{
+    axum::Router::new()
+{}
+        .with_state(std::sync::Arc::new(server))
+}}",
+            vis, routes
+        );
+
+        let handlers = self
+            .endpoints
+            .iter()
+            .filter_map(Endpoint::to_rust_query_struct)
+            .chain(self.endpoints.iter().map(Endpoint::to_rust_handler))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let handlers = format!(
+            "\
+mod handlers {{
+    use super::*;
+    use std::sync::Arc;
+
+{}
+}}",
+            handlers
+        );
+
+        [server, router, handlers].join("\n\n")
+    }
+}
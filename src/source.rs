@@ -0,0 +1,437 @@
+//! Where the nodes of a spec are in its source file, and errors that point at them, in the style
+//! of rustc and the Elm compiler.
+
+use crate::{spec::*, validate::*};
+use std::collections::HashMap;
+use toml::{
+    de::{DeTable, DeValue},
+    Spanned,
+};
+use yaml_rust::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::{Marker, TScalarStyle},
+};
+
+/// A stretch of one line of a spec file. `line` and `column` are 1-based, `len` is in characters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub len: usize,
+}
+
+impl Span {
+    /// The span of the `len` bytes at byte `index` of `source`, cut off at the end of the line.
+    fn at_index(source: &str, index: usize, len: usize) -> Self {
+        let index = index.min(source.len());
+        let line_start = source[..index].rfind('\n').map_or(0, |i| i + 1);
+        let rest = &source[index..(index + len).min(source.len())];
+
+        Self {
+            line: source[..index].matches('\n').count() + 1,
+            column: source[line_start..index].chars().count() + 1,
+            len: rest.lines().next().unwrap_or("").chars().count().max(1),
+        }
+    }
+
+    /// The span of the word at a 1-based `line` and `column` of `source`, or of the one character
+    /// there if it isn't a word.
+    fn word_at(source: &str, line: usize, column: usize) -> Self {
+        let len = source
+            .lines()
+            .nth(line.saturating_sub(1))
+            .map(|text| {
+                text.chars()
+                    .skip(column.saturating_sub(1))
+                    .take_while(|&c| c.is_alphanumeric() || c == '_')
+                    .count()
+            })
+            .unwrap_or(0);
+
+        Self {
+            line,
+            column,
+            len: len.max(1),
+        }
+    }
+}
+
+/// The formats a spec can be written in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpecFormat {
+    Yaml,
+    Json,
+    Toml,
+    /// The compact language of [`ApiSpec::from_dsl`].
+    Dsl,
+}
+
+impl SpecFormat {
+    /// The format of a file with the extension `ext`: YAML unless it's `toml`, `json` or `types`.
+    pub fn from_extension(ext: Option<&str>) -> Self {
+        match ext {
+            Some("toml") => SpecFormat::Toml,
+            Some("json") => SpecFormat::Json,
+            Some("types") => SpecFormat::Dsl,
+            _ => SpecFormat::Yaml,
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Renders the diagnostic with the line of `source` it points at, e.g.
+    ///
+    /// ```text
+    /// warning: `User.big` is a Rust `i64`, which can exceed 2^53 and lose precision in JavaScript
+    ///   --> api.yaml:17:17
+    ///    |
+    /// 17 |         - name: big
+    ///    |                 ^^^
+    ///    = hint: consider setting `json.large_int_as_string`
+    /// ```
+    pub fn render(&self, file: &str, source: &str) -> String {
+        let label = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        let line = self
+            .span
+            .and_then(|span| Some((span, source.lines().nth(span.line.checked_sub(1)?)?)));
+        let gutter = line.map_or(0, |(span, _)| span.line.to_string().len());
+        let pad = " ".repeat(gutter);
+
+        let mut out = format!("{}: {}\n", label, self.message);
+        match line {
+            Some((span, text)) => {
+                out += &format!("{}--> {}:{}:{}\n", pad, file, span.line, span.column);
+                out += &format!("{} |\n", pad);
+                out += &format!("{} | {}\n", span.line, text);
+                out += &format!(
+                    "{} | {}{}\n",
+                    pad,
+                    " ".repeat(span.column - 1),
+                    "^".repeat(span.len.max(1))
+                );
+            }
+            None => out += &format!("{}--> {}\n", pad, file),
+        }
+        if let Some(hint) = &self.hint {
+            out += &format!("{} = hint: {}\n", pad, hint);
+        }
+
+        out.trim_end().to_string()
+    }
+}
+
+/// `message` without the ` at line 3 column 5` that serde_yaml and serde_json put at the end.
+fn without_location(message: String) -> String {
+    match message.rfind(" at line ") {
+        Some(i) => message[..i].to_string(),
+        None => message,
+    }
+}
+
+/// The error for a spec that couldn't be deserialized, with a hint if one can be guessed from
+/// the message.
+fn parse_error(message: String, span: Option<Span>) -> Diagnostic {
+    let hint = parse_hint(&message);
+    let diagnostic = Diagnostic::error(message).at(span);
+
+    match hint {
+        Some(hint) => diagnostic.with_hint(hint),
+        None => diagnostic,
+    }
+}
+
+/// Suggests the closest name for an unknown field or variant, and what to add for a missing one.
+fn parse_hint(message: &str) -> Option<String> {
+    let quoted = |s: &str| {
+        s.split('`')
+            .skip(1)
+            .step_by(2)
+            .map(str::to_string)
+            .collect::<Vec<_>>()
+    };
+
+    let (unknown, expected) = if let Some(i) = message.find("missing field `") {
+        let field = quoted(&message[i..]).into_iter().next()?;
+        return Some(format!("add `{}`", field));
+    } else if let Some(i) = message
+        .find("unknown field `")
+        .or_else(|| message.find("unknown variant `"))
+    {
+        // serde: unknown field `nmae`, expected one of `name`, `data`
+        let names = quoted(&message[i..]);
+        (names.first()?.clone(), names[1..].to_vec())
+    } else if let Some(i) = message.find("unexpected keys in table: ") {
+        // toml: unexpected keys in table: nmae, available keys: name, data
+        let rest = &message[i + "unexpected keys in table: ".len()..];
+        let (unknown, available) = rest.split_once(", available keys: ")?;
+        let split = |s: &str| s.split(", ").map(str::to_string).collect::<Vec<_>>();
+        (split(unknown).into_iter().next()?, split(available))
+    } else {
+        return None;
+    };
+
+    let closest = expected
+        .iter()
+        .map(|name| {
+            (
+                edit_distance(&unknown.to_lowercase(), &name.to_lowercase()),
+                name,
+            )
+        })
+        .min()?;
+    if closest.0 <= unknown.chars().count().div_ceil(3) {
+        Some(format!("did you mean `{}`?", closest.1))
+    } else {
+        None
+    }
+}
+
+/// The number of characters to insert, delete or replace to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let replaced = diagonal + (ca != cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// A parsed YAML, JSON or TOML document, with just enough of it kept to find the spec nodes.
+enum Node {
+    Scalar(String, Span),
+    Seq(Vec<Node>),
+    Map(Vec<(String, Node)>),
+}
+
+impl Node {
+    fn get(&self, key: &str) -> Option<&Node> {
+        match self {
+            Node::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn items(&self) -> &[Node] {
+        match self {
+            Node::Seq(items) => items,
+            _ => &[],
+        }
+    }
+
+    fn scalar(&self) -> Option<(&str, Span)> {
+        match self {
+            Node::Scalar(value, span) => Some((value, *span)),
+            _ => None,
+        }
+    }
+
+    fn yaml(source: &str) -> Option<Node> {
+        let mut builder = YamlBuilder::default();
+        Parser::new(source.chars()).load(&mut builder, false).ok()?;
+        builder.root
+    }
+
+    fn toml(source: &str, value: &Spanned<DeValue>) -> Node {
+        let span = value.span();
+        match value.get_ref() {
+            DeValue::Table(table) => Node::toml_table(source, table),
+            DeValue::Array(items) => {
+                Node::Seq(items.iter().map(|item| Node::toml(source, item)).collect())
+            }
+            // Without the quotes.
+            DeValue::String(s) => Node::Scalar(
+                s.to_string(),
+                Span::at_index(source, span.start + 1, span.len().saturating_sub(2)),
+            ),
+            _ => Node::Scalar(
+                String::new(),
+                Span::at_index(source, span.start, span.len()),
+            ),
+        }
+    }
+
+    fn toml_table(source: &str, table: &DeTable) -> Node {
+        Node::Map(
+            table
+                .iter()
+                .map(|(key, value)| (key.get_ref().to_string(), Node::toml(source, value)))
+                .collect(),
+        )
+    }
+}
+
+#[derive(Default)]
+struct YamlBuilder {
+    stack: Vec<Node>,
+    /// For each map on the stack, the key waiting for its value.
+    keys: Vec<Option<String>>,
+    root: Option<Node>,
+}
+
+impl YamlBuilder {
+    fn add(&mut self, node: Node) {
+        match self.stack.last_mut() {
+            None => self.root = Some(node),
+            Some(Node::Seq(items)) => items.push(node),
+            Some(Node::Map(entries)) => {
+                let key = self.keys.last_mut().expect("every map has a key slot");
+                match key.take() {
+                    Some(key) => entries.push((key, node)),
+                    None => *key = Some(node.scalar().map_or("", |(k, _)| k).to_string()),
+                }
+            }
+            Some(Node::Scalar(..)) => unreachable!("scalars are never on the stack"),
+        }
+    }
+}
+
+impl MarkedEventReceiver for YamlBuilder {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(value, style, ..) => {
+                let quoted = matches!(
+                    style,
+                    TScalarStyle::SingleQuoted | TScalarStyle::DoubleQuoted
+                );
+                let span = Span {
+                    line: mark.line(),
+                    column: mark.col() + 1 + quoted as usize,
+                    len: value.chars().count().max(1),
+                };
+                self.add(Node::Scalar(value, span));
+            }
+            Event::Alias(_) => self.add(Node::Seq(vec![])),
+            Event::SequenceStart(_) => self.stack.push(Node::Seq(vec![])),
+            Event::MappingStart(_) => {
+                self.stack.push(Node::Map(vec![]));
+                self.keys.push(None);
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                if let Some(node) = self.stack.pop() {
+                    if let Node::Map(_) = node {
+                        self.keys.pop();
+                    }
+                    self.add(node);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Records the span of every named node in `root`, a whole spec, by its path.
+fn record_spans(root: &Node, spans: &mut HashMap<String, Span>) {
+    for section in &["types", "endpoints", "channels", "ports"] {
+        for item in root.get(section).map_or(&[][..], Node::items) {
+            record_named(item, None, spans);
+        }
+    }
+
+    for upgrade in root.get("upgrades").map_or(&[][..], Node::items) {
+        let from = upgrade.get("from").and_then(Node::scalar);
+        let to = upgrade.get("to").and_then(Node::scalar);
+        if let (Some((from, span)), Some((to, _))) = (from, to) {
+            spans.entry(format!("{} -> {}", from, to)).or_insert(span);
+        }
+    }
+}
+
+/// Records the span of the name of `node`, e.g. a type or a field, then of the nodes in it.
+/// `parent` is the path of the node it's in and the separator that goes after it.
+fn record_named(node: &Node, parent: Option<(&str, &str)>, spans: &mut HashMap<String, Span>) {
+    // Types are written `Struct: { name: ... }`.
+    let node = match node {
+        Node::Map(entries) if entries.len() == 1 && node.get("name").is_none() => &entries[0].1,
+        _ => node,
+    };
+    let (name, span) = match node.get("name").and_then(Node::scalar) {
+        Some(name) => name,
+        None => return,
+    };
+
+    let path = match parent {
+        Some((parent, separator)) => format!("{}{}{}", parent, separator, name),
+        None => name.to_string(),
+    };
+    spans.entry(path.clone()).or_insert(span);
+
+    let children = [
+        ("fields", "."),
+        ("params", "."),
+        ("query", "."),
+        ("variants", "::"),
+    ];
+    for (key, separator) in &children {
+        for child in node.get(key).map_or(&[][..], Node::items) {
+            record_named(child, Some((&path, separator)), spans);
+        }
+    }
+
+    // Struct variants are written `data: { Struct: [...] }`.
+    if let Some(fields) = node.get("data").and_then(|data| data.get("Struct")) {
+        for child in fields.items() {
+            record_named(child, Some((&path, ".")), spans);
+        }
+    }
+}
+
+impl ApiSpec {
+    /// Parses a spec, recording where each of its nodes is in `source`. A spec that can't be
+    /// parsed comes back as an error pointing at the problem.
+    pub fn parse(source: &str, format: SpecFormat) -> Result<Self, Diagnostic> {
+        let (mut spec, root): (ApiSpec, _) = match format {
+            SpecFormat::Yaml => {
+                let spec = serde_yaml::from_str(source).map_err(|err| {
+                    let span = err
+                        .location()
+                        .map(|location| Span::word_at(source, location.line(), location.column()));
+                    parse_error(without_location(err.to_string()), span)
+                })?;
+                (spec, Node::yaml(source))
+            }
+            SpecFormat::Json => {
+                let spec = serde_json::from_str(source).map_err(|err| {
+                    let span = Span::word_at(source, err.line(), err.column().max(1));
+                    parse_error(without_location(err.to_string()), Some(span))
+                })?;
+                // JSON is YAML, as far as finding the nodes goes.
+                (spec, Node::yaml(source))
+            }
+            SpecFormat::Toml => {
+                let spec = toml::from_str(source).map_err(|err| {
+                    let span = err
+                        .span()
+                        .map(|range| Span::at_index(source, range.start, range.len()));
+                    parse_error(err.message().trim_end().to_string(), span)
+                })?;
+                let root = DeTable::parse(source)
+                    .ok()
+                    .map(|table| Node::toml_table(source, table.get_ref()));
+                (spec, root)
+            }
+            SpecFormat::Dsl => return ApiSpec::from_dsl(source).map_err(Diagnostic::from),
+        };
+
+        if let Some(root) = root {
+            record_spans(&root, &mut spec.spans);
+        }
+
+        Ok(spec)
+    }
+
+    /// Where the node at `path`, e.g. `User.name`, is in the source the spec was parsed from.
+    pub fn span(&self, path: &str) -> Option<Span> {
+        self.spans.get(path).copied()
+    }
+}
@@ -1,4 +1,16 @@
+use crate::{
+    channel::Channel,
+    elm::{self, Expr, Uses},
+    endpoint::{self, Endpoint},
+    graph::ElmGraphs,
+    port::Port,
+    primitives,
+    source::Span,
+    upgrade::Upgrade,
+    well_known,
+};
 use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, convert::TryFrom};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum EnumVariantData {
@@ -12,12 +24,29 @@ pub enum EnumVariantData {
 pub struct StructField {
     pub name: String,
     pub data: (String, String),
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EnumVariant {
     pub name: String,
     pub data: EnumVariantData,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+    /// Explicit integer discriminant. When every variant of an enum has one, the enum is
+    /// serialized as a JSON number.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discriminant: Option<i64>,
+}
+
+/// An output a type can be generated for.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    Rust,
+    Elm,
+    Ts,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -25,139 +54,819 @@ pub enum TypeSpec {
     Struct {
         name: String,
         fields: Vec<StructField>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        doc: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        targets: Option<Vec<Target>>,
     },
     Enum {
         name: String,
         variants: Vec<EnumVariant>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        doc: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        targets: Option<Vec<Target>>,
+        /// Decode variants this version doesn't know into an extra variant that keeps the raw
+        /// value, instead of failing: `Unknown` in Rust, `Unknown<Name>` in Elm, where
+        /// constructors share one namespace. Not available for enums with discriminants.
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        unknown_fallback: bool,
+    },
+    Newtype {
+        name: String,
+        data: (String, String),
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        doc: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        targets: Option<Vec<Target>>,
+    },
+    Alias {
+        name: String,
+        data: (String, String),
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        doc: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        targets: Option<Vec<Target>>,
     },
 }
 
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    #[default]
+    Public,
+    Crate,
+    Private,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct RustOptions {
+    /// Visibility of generated types and their fields.
+    #[serde(default)]
+    pub visibility: Visibility,
+    /// Wrap the generated types in `mod <module> { ... }`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub module: Option<String>,
+    /// Lines emitted ahead of the generated types, e.g. `use` declarations or `#![allow(...)]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prelude: Vec<String>,
+    /// Generate a server trait and router for the endpoints with this framework.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<ServerFramework>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ServerFramework {
+    /// axum 0.8.
+    Axum,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum DateTimeFormat {
+    /// ISO-8601 / RFC 3339 strings, e.g. `"2020-01-31T12:00:00Z"`.
+    #[default]
+    Iso8601,
+    /// Milliseconds since the Unix epoch.
+    EpochMillis,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum DecoderStyle {
+    /// `Json.Decode.succeed Record |> Json.Decode.Pipeline.required "field" decoder ...`
+    #[default]
+    Pipeline,
+    /// `Json.Decode.mapN Record (Json.Decode.field "field" decoder) ...`, continued with `andMap`
+    /// for records with more than 8 fields.
+    MapN,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct ElmOptions {
+    /// Only use `elm/json`, replacing `Json.Decode.Extra`, `Json.Decode.Pipeline` and
+    /// `Json.Encode.Extra` with small helpers generated into the module.
+    #[serde(default)]
+    pub json_only: bool,
+    /// How record decoders are built.
+    #[serde(default)]
+    pub decoder_style: DecoderStyle,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct JsonOptions {
+    /// Encode integers that may exceed 2^53 (`u64`, `i64`, ...) as JSON strings, so JavaScript
+    /// doesn't silently round them in transit. This only protects the JSON: an Elm `Int` is a
+    /// JavaScript number too, so give such fields the Elm type `String` to keep every digit.
+    #[serde(default)]
+    pub large_int_as_string: bool,
+    /// Representation of `chrono::DateTime<Utc>` and `time::OffsetDateTime`.
+    #[serde(default)]
+    pub datetime: DateTimeFormat,
+}
+
+/// A type defined outside the spec, with hand-written Elm codecs.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExternalType {
+    /// Rust path, e.g. `money::Money`.
+    pub rust: String,
+    /// Elm type, e.g. `Money.Money`.
+    pub elm: String,
+    /// Elm module to import when the type is used, e.g. `Money`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elm_module: Option<String>,
+    /// Elm decoder, e.g. `Money.decoder`.
+    pub decoder: String,
+    /// Elm encoder, e.g. `Money.encode`.
+    pub encoder: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct ApiSpec {
     pub module: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+    #[serde(default)]
+    pub rust: RustOptions,
+    #[serde(default)]
+    pub elm: ElmOptions,
+    #[serde(default)]
+    pub json: JsonOptions,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub externals: Vec<ExternalType>,
     pub types: Vec<TypeSpec>,
+    /// Upgrades between versions of a struct, which become `From` conversions in Rust and upgrade
+    /// functions and version-tolerant decoders in Elm.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub upgrades: Vec<Upgrade>,
+    /// HTTP endpoints, which become Elm request functions and a Rust route table.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub endpoints: Vec<Endpoint>,
+    /// Message channels, e.g. WebSockets, which become frame helpers on both sides.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub channels: Vec<Channel>,
+    /// Elm ports, which make the Elm module a `port module`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ports: Vec<Port>,
+    /// Where each node was in the source, by path: `User`, `User.name`, `Shape::Circle`,
+    /// `getUser.id`, `UserV1 -> UserV2`, ... Empty for specs built in code.
+    #[serde(skip)]
+    pub spans: HashMap<String, Span>,
 }
 
-const INDENT: &str = "    ";
+pub(crate) const INDENT: &str = "    ";
+/// Modules every Elm module imports implicitly. Any other module the generated code refers to is
+/// imported.
+const ELM_DEFAULT_IMPORTS: &[&str] = &[
+    "Basics", "Char", "Cmd", "Debug", "List", "Maybe", "Platform", "Result", "String", "Sub",
+    "Tuple",
+];
 const TYPE_DERIVE_HEADER: &str = "#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]";
 const SERDE_ENUM_HEADER: &str = "#[serde(tag = \"var\", content = \"vardata\")]";
+const SERDE_NEWTYPE_HEADER: &str = "#[serde(transparent)]";
+const SERDE_LARGE_INT_ATTR: &str = "#[serde(with = \"large_int_as_string\")]";
+const RUST_LARGE_INT_MODULE: &str = "\
+mod large_int_as_string {
+    use std::{fmt::Display, str::FromStr};
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Display,
+        S: serde::Serializer,
+    {
+        serializer.collect_str(value)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+        D: serde::Deserializer<'de>,
+    {
+        let s = <String as serde::Deserialize>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}";
+const ELM_LARGE_INT_HELPERS: &str = "\
+decodeStringifiedInt : Json.Decode.Decoder Int
+decodeStringifiedInt =
+    Json.Decode.string
+        |> Json.Decode.andThen
+            (\\value ->
+                case String.toInt value of
+                    Just int ->
+                        Json.Decode.succeed int
+
+                    Nothing ->
+                        Json.Decode.fail (\"Invalid integer: \" ++ value)
+            )
+
+
+encodeStringifiedInt : Int -> Json.Encode.Value
+encodeStringifiedInt =
+    Json.Encode.string << String.fromInt";
+const ELM_REQUIRED_HELPER: &str = "\
+required : String -> Json.Decode.Decoder a -> Json.Decode.Decoder (a -> b) -> Json.Decode.Decoder b
+required key valueDecoder =
+    Json.Decode.map2 (|>) (Json.Decode.field key valueDecoder)";
+const ELM_AND_MAP_HELPER: &str = "\
+andMap : Json.Decode.Decoder a -> Json.Decode.Decoder (a -> b) -> Json.Decode.Decoder b
+andMap =
+    Json.Decode.map2 (|>)";
+const ELM_ENCODE_MAYBE_HELPER: &str = "\
+encodeMaybe : (a -> Json.Encode.Value) -> Maybe a -> Json.Encode.Value
+encodeMaybe encoder =
+    Maybe.map encoder >> Maybe.withDefault Json.Encode.null";
+const REPR_DERIVE_HEADER: &str =
+    "#[derive(Debug, Clone, serde_repr::Serialize_repr, serde_repr::Deserialize_repr)]";
+
+/// How an enum is represented in JSON.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnumRepr {
+    /// `{"var": "Foo", "vardata": ...}`
+    Tagged,
+    /// `"Foo"`, for enums whose variants carry no data.
+    String,
+    /// `1`, for enums whose variants all have a discriminant.
+    Int,
+}
+
+impl EnumRepr {
+    pub fn of(variants: &[EnumVariant]) -> Self {
+        if !variants
+            .iter()
+            .all(|var| matches!(var.data, EnumVariantData::None))
+        {
+            Self::Tagged
+        } else if !variants.is_empty() && variants.iter().all(|var| var.discriminant.is_some()) {
+            Self::Int
+        } else {
+            Self::String
+        }
+    }
+
+    fn elm_type(self) -> &'static str {
+        match self {
+            Self::Int => "Int",
+            _ => "String",
+        }
+    }
+
+    /// `(rust, elm)` type of the raw value kept by the `Unknown` variant of
+    /// `unknown_fallback` enums.
+    fn unknown_data(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::Tagged => Some(("serde_json::Value", "Json.Decode.Value")),
+            Self::String => Some(("String", "String")),
+            Self::Int => None,
+        }
+    }
+
+    fn elm_value(self, var: &EnumVariant) -> String {
+        match (self, var.discriminant) {
+            (Self::Int, Some(value)) => value.to_string(),
+            _ => format!("\"{}\"", var.name),
+        }
+    }
+}
+
+impl Target {
+    pub const ALL: &'static [Target] = &[Self::Rust, Self::Elm, Self::Ts];
+
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::Rust => "Rust",
+            Self::Elm => "Elm",
+            Self::Ts => "TypeScript",
+        }
+    }
+}
+
+impl ElmOptions {
+    fn required(&self, uses: &Uses) -> &'static str {
+        if self.json_only {
+            uses.helper("required");
+            "required"
+        } else {
+            uses.module("Json.Decode.Pipeline");
+            "Json.Decode.Pipeline.required"
+        }
+    }
+
+    fn and_map(&self, uses: &Uses) -> &'static str {
+        if self.json_only {
+            uses.helper("andMap");
+            "andMap"
+        } else {
+            uses.module("Json.Decode.Extra");
+            "Json.Decode.Extra.andMap"
+        }
+    }
+
+    fn encode_maybe(&self, uses: &Uses) -> &'static str {
+        if self.json_only {
+            uses.helper("encodeMaybe");
+            "encodeMaybe"
+        } else {
+            uses.module("Json.Encode.Extra");
+            "Json.Encode.Extra.maybe"
+        }
+    }
+}
+
+impl Visibility {
+    pub fn to_rust(self) -> &'static str {
+        match self {
+            Self::Public => "pub ",
+            Self::Crate => "pub(crate) ",
+            Self::Private => "",
+        }
+    }
+}
 
 impl ApiSpec {
+    /// The external type declared for an Elm type, if any.
+    pub fn external(&self, elm_type: &str) -> Option<&ExternalType> {
+        self.externals.iter().find(|ext| ext.elm == elm_type)
+    }
+
     pub fn to_rust(&self) -> String {
-        self.types
+        let module_doc = match &self.doc {
+            Some(doc) => format!("{}\n\n", doc_lines(doc, "//!")),
+            None => "".into(),
+        };
+
+        let prelude = if self.rust.prelude.is_empty() {
+            "".into()
+        } else {
+            format!("{}\n\n", self.rust.prelude.join("\n"))
+        };
+
+        let mut types_str = self
+            .types
             .iter()
-            .map(|t| t.to_rust())
+            .filter(|t| t.has_target(Target::Rust))
+            .map(|t| t.to_rust(self))
             .collect::<Vec<_>>()
-            .join("\n\n")
+            .join("\n\n");
+
+        if types_str.contains(SERDE_LARGE_INT_ATTR) {
+            types_str = format!("{}\n\n{}", RUST_LARGE_INT_MODULE, types_str);
+        }
+
+        for code in self
+            .upgrades_to_rust()
+            .into_iter()
+            .chain(self.endpoints_to_rust())
+            .chain(self.server_to_rust())
+            .chain(self.channels_to_rust())
+        {
+            if types_str.is_empty() {
+                types_str = code;
+            } else {
+                types_str = format!("{}\n\n{}", types_str, code);
+            }
+        }
+
+        let body = format!("{}{}{}", module_doc, prelude, types_str);
+
+        match &self.rust.module {
+            Some(module) => format!(
+                "{vis}mod {name} {{\n{body}\n}}",
+                vis = self.rust.visibility.to_rust(),
+                name = module,
+                body = indent_lines(body.trim_end(), 1)
+            ),
+            None => body,
+        }
     }
 
     pub fn to_elm(&self) -> String {
-        let exports_str = self
+        let graphs = self.elm_graphs();
+        let uses = Uses::default();
+
+        let mut code = self
             .types
             .iter()
+            .filter(|t| t.has_target(Target::Elm))
             .flat_map(|t| {
-                let (name, expose) = match t {
-                    TypeSpec::Struct { name, .. } => (name, name.clone()),
-                    TypeSpec::Enum { name, .. } => (name, format!("{}(..)", name)),
-                };
                 vec![
-                    expose.clone(),
-                    format!("decode{}", name),
-                    format!("encode{}", name),
+                    t.to_elm(self, &graphs, &uses),
+                    t.to_elm_decoder(self, &graphs, &uses),
+                    t.to_elm_encoder(self, &graphs, &uses),
                 ]
             })
-            .collect::<Vec<_>>()
-            .join(", ");
+            .collect::<Vec<_>>();
 
-        let types_str = self
+        let upgrades = self.upgrades_to_elm(&uses);
+
+        let extras = upgrades
+            .iter()
+            .map(|(_, code)| code.clone())
+            .chain(
+                self.endpoints
+                    .iter()
+                    .map(|e| e.to_elm(self, &graphs, &uses)),
+            )
+            .chain(self.channels.iter().map(|c| c.to_elm(self, &graphs, &uses)))
+            .chain(self.ports.iter().map(|p| p.to_elm(self, &graphs, &uses)))
+            .collect::<Vec<_>>();
+
+        let builtin_types = well_known::elm_types()
+            .into_iter()
+            .filter(|t| {
+                !self
+                    .types
+                    .iter()
+                    .any(|user_type| user_type.name() == t.name())
+                    && uses.has_type(t.name())
+            })
+            .collect::<Vec<_>>();
+
+        for t in &builtin_types {
+            code.push(t.to_elm(self, &graphs, &uses));
+            code.push(t.to_elm_decoder(self, &graphs, &uses));
+            code.push(t.to_elm_encoder(self, &graphs, &uses));
+        }
+        code.extend(extras);
+
+        let mut helpers: Vec<(&str, &str, &[&str])> = vec![
+            (
+                "expectJsonOrError",
+                endpoint::ELM_API_ERROR,
+                &["Http", "Json.Decode"],
+            ),
+            ("boolToString", endpoint::ELM_BOOL_TO_STRING, &[]),
+            (
+                "decodeStringifiedInt",
+                ELM_LARGE_INT_HELPERS,
+                &["Json.Decode", "Json.Encode"],
+            ),
+            (
+                "encodeStringifiedInt",
+                ELM_LARGE_INT_HELPERS,
+                &["Json.Decode", "Json.Encode"],
+            ),
+        ];
+        helpers.extend(well_known::elm_helpers());
+        if self.elm.json_only {
+            helpers.push(("required", ELM_REQUIRED_HELPER, &["Json.Decode"]));
+            helpers.push(("andMap", ELM_AND_MAP_HELPER, &["Json.Decode"]));
+            helpers.push(("encodeMaybe", ELM_ENCODE_MAYBE_HELPER, &["Json.Encode"]));
+        }
+
+        let mut added_helpers = vec![];
+        for (name, helper, modules) in helpers {
+            if !added_helpers.contains(&helper) && uses.has_helper(name) {
+                added_helpers.push(helper);
+                for module in modules {
+                    uses.module(module);
+                }
+            }
+        }
+        code.extend(added_helpers.into_iter().map(String::from));
+
+        let types_str = code.join(elm::DECLARATION_SEPARATOR);
+
+        let mut imports = uses
+            .modules()
+            .into_iter()
+            .filter(|module| {
+                !ELM_DEFAULT_IMPORTS.contains(&module.as_str()) && *module != self.module
+            })
+            .collect::<Vec<_>>();
+        // Unlike `List` and `Maybe`, `Dict` isn't exposed by default.
+        if uses.has_type("Dict") && !self.types.iter().any(|t| t.name() == "Dict") {
+            imports.retain(|module| module != "Dict");
+            imports.push("Dict exposing (Dict)".into());
+            imports.sort_unstable();
+        }
+
+        let mut exports = self
             .types
             .iter()
-            .flat_map(|t| vec![t.to_elm(), t.to_elm_decoder(), t.to_elm_encoder()])
-            .collect::<Vec<_>>()
-            .join("\n\n");
+            .filter(|t| t.has_target(Target::Elm))
+            .chain(&builtin_types)
+            .flat_map(|t| t.elm_exports(&graphs))
+            .collect::<Vec<_>>();
+        if uses.has_helper("expectJsonOrError") {
+            exports.push("ApiError(..)".into());
+        }
+        exports.extend(upgrades.into_iter().map(|(name, _)| name));
+        exports.extend(self.endpoints.iter().map(Endpoint::elm_name));
+        exports.extend(self.channels.iter().flat_map(Channel::elm_exports));
+        exports.extend(self.ports.iter().map(Port::elm_name));
 
-        format!(
-            "\
-module {name} exposing ({exports})
-
-import Json.Decode
-import Json.Decode.Extra
-import Json.Decode.Pipeline
-import Json.Encode
-import Json.Encode.Extra
-
-{types}",
-            name = self.module,
-            exports = exports_str,
-            types = types_str
-        )
+        let exports_str = exports.join(", ");
+
+        let keyword = if self.ports.is_empty() {
+            "module"
+        } else {
+            "port module"
+        };
+        let mut header = vec![format!(
+            "{} {} exposing ({})",
+            keyword, self.module, exports_str
+        )];
+
+        if let Some(doc) = &self.doc {
+            let docs = if exports_str.is_empty() {
+                "".into()
+            } else {
+                format!("\n\n@docs {}", exports_str.replace("(..)", ""))
+            };
+            header.push(format!("{{-| {}{}\n\n-}}", doc.trim_end(), docs));
+        }
+
+        if !imports.is_empty() {
+            header.push(
+                imports
+                    .iter()
+                    .map(|module| format!("import {}", module))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            );
+        }
+
+        let header = header.join("\n\n");
+
+        if types_str.is_empty() {
+            format!("{}\n", header)
+        } else {
+            format!("{}{}{}\n", header, elm::DECLARATION_SEPARATOR, types_str)
+        }
     }
 }
 
 impl TypeSpec {
-    pub fn to_rust(&self) -> String {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Struct { name, .. }
+            | Self::Enum { name, .. }
+            | Self::Newtype { name, .. }
+            | Self::Alias { name, .. } => name,
+        }
+    }
+
+    /// Whether this type is generated for `target`. Types without `targets` are generated for
+    /// every target.
+    pub fn has_target(&self, target: Target) -> bool {
+        let targets = match self {
+            Self::Struct { targets, .. }
+            | Self::Enum { targets, .. }
+            | Self::Newtype { targets, .. }
+            | Self::Alias { targets, .. } => targets,
+        };
+
+        match targets {
+            Some(targets) => targets.contains(&target),
+            None => true,
+        }
+    }
+
+    /// `(rust, elm)` type of the raw value kept by the `Unknown` variant, for enums with an
+    /// `unknown_fallback`.
+    pub fn unknown_data(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::Enum {
+                variants,
+                unknown_fallback: true,
+                ..
+            } => EnumRepr::of(variants).unknown_data(),
+            _ => None,
+        }
+    }
+
+    pub fn elm_exports(&self, graphs: &ElmGraphs) -> Vec<String> {
+        let name = self.name();
+        let is_boxed = graphs.is_boxed_struct(name);
+
+        let mut exports = match self {
+            Self::Enum { .. } => vec![format!("{}(..)", name)],
+            Self::Struct { .. } if is_boxed => vec![format!("{}(..)", name)],
+            _ => vec![name.to_string()],
+        };
+        exports.push(format!("decode{}", name));
+        exports.push(format!("encode{}", name));
         match self {
-            Self::Struct { name, fields } => {
+            Self::Enum { variants, .. } if EnumRepr::of(variants) != EnumRepr::Tagged => {
+                let lower_name = elm_lower_camel(name);
+                let repr_type = EnumRepr::of(variants).elm_type();
+                exports.push(format!("all{}", name));
+                exports.push(format!("{}To{}", lower_name, repr_type));
+                exports.push(format!("{}From{}", lower_name, repr_type));
+            }
+            Self::Newtype { .. } => exports.push(format!("unwrap{}", name)),
+            Self::Struct { fields, .. } if is_boxed => {
+                exports.extend(fields.iter().map(|field| elm_accessor(name, &field.name)))
+            }
+            _ => {}
+        }
+
+        exports
+    }
+
+    /// Every `(rust, elm)` type pair in this type, labelled with its path for error messages.
+    pub fn type_pairs(&self) -> Vec<(String, &(String, String))> {
+        let name = self.name();
+
+        match self {
+            Self::Struct { fields, .. } => fields
+                .iter()
+                .map(|field| (format!("{}.{}", name, field.name), &field.data))
+                .collect(),
+            Self::Enum { variants, .. } => variants
+                .iter()
+                .flat_map(|var| match &var.data {
+                    EnumVariantData::None => vec![],
+                    EnumVariantData::Single(data) => {
+                        vec![(format!("{}::{}", name, var.name), data)]
+                    }
+                    EnumVariantData::Struct(fields) => fields
+                        .iter()
+                        .map(|field| {
+                            (
+                                format!("{}::{}.{}", name, var.name, field.name),
+                                &field.data,
+                            )
+                        })
+                        .collect(),
+                })
+                .collect(),
+            Self::Newtype { data, .. } | Self::Alias { data, .. } => vec![(name.to_string(), data)],
+        }
+    }
+
+    pub fn to_rust(&self, spec: &ApiSpec) -> String {
+        let options = &spec.rust;
+
+        match self {
+            Self::Struct {
+                name, fields, doc, ..
+            } => {
                 let fields_fmt = fields
                     .iter()
-                    .map(|field| field.to_rust(1, true))
+                    .map(|field| field.to_rust(1, options.visibility, spec))
                     .collect::<Vec<_>>()
                     .join("");
 
                 format!(
                     "\
-{header}
-pub struct {name} {{
-{fields}}}",
+{doc}{header}
+{vis}struct {name} {fields}",
+                    doc = rust_doc_comment(doc, 0),
                     header = TYPE_DERIVE_HEADER,
+                    vis = options.visibility.to_rust(),
                     name = name,
-                    fields = fields_fmt
+                    fields = rust_braces(&fields_fmt, 0)
                 )
             }
-            Self::Enum { name, variants } => {
-                let variants_fmt = variants
+            Self::Enum {
+                name,
+                variants,
+                doc,
+                ..
+            } => {
+                let mut variants_fmt = variants
                     .iter()
-                    .map(|var| var.to_rust(1))
+                    .map(|var| var.to_rust(1, spec))
                     .collect::<Vec<_>>()
                     .join("");
+                if let Some((rust_type, _)) = self.unknown_data() {
+                    variants_fmt.push_str(&format!(
+                        "\
+{indent}/// A variant this version doesn't know, as it was received.
+{indent}#[serde(untagged)]
+{indent}Unknown({rust_type}),
+",
+                        indent = INDENT,
+                        rust_type = rust_type
+                    ));
+                }
+
+                let (header, enum_header) = match EnumRepr::of(variants) {
+                    EnumRepr::Tagged => (TYPE_DERIVE_HEADER, format!("{}\n", SERDE_ENUM_HEADER)),
+                    // Unit-only enums use serde's default representation: a bare string.
+                    EnumRepr::String => (TYPE_DERIVE_HEADER, "".into()),
+                    EnumRepr::Int => (
+                        REPR_DERIVE_HEADER,
+                        format!("#[repr({})]\n", rust_repr(variants)),
+                    ),
+                };
 
                 format!(
                     "\
-{header}
-{enum_header}
-pub enum {name} {{
-{variants}}}",
-                    header = TYPE_DERIVE_HEADER,
-                    enum_header = SERDE_ENUM_HEADER,
+{doc}{header}
+{enum_header}{vis}enum {name} {variants}",
+                    doc = rust_doc_comment(doc, 0),
+                    header = header,
+                    enum_header = enum_header,
+                    vis = options.visibility.to_rust(),
                     name = name,
-                    variants = variants_fmt
+                    variants = rust_braces(&variants_fmt, 0)
                 )
             }
+            Self::Newtype {
+                name, data, doc, ..
+            } => format!(
+                "\
+{doc}{header}
+{newtype_header}
+{vis}struct {name}({attr}{vis}{inner});",
+                doc = rust_doc_comment(doc, 0),
+                header = TYPE_DERIVE_HEADER,
+                newtype_header = SERDE_NEWTYPE_HEADER,
+                attr = rust_field_attr(data, spec).map_or("".into(), |attr| format!("{} ", attr)),
+                vis = options.visibility.to_rust(),
+                name = name,
+                inner = rust_type(&data.0)
+            ),
+            Self::Alias {
+                name, data, doc, ..
+            } => format!(
+                "{doc}{vis}type {name} = {inner};",
+                doc = rust_doc_comment(doc, 0),
+                vis = options.visibility.to_rust(),
+                name = name,
+                inner = rust_type(&data.0)
+            ),
         }
     }
 
-    pub fn to_elm(&self) -> String {
+    pub fn to_elm(&self, spec: &ApiSpec, graphs: &ElmGraphs, uses: &Uses) -> String {
+        for (_, data) in self.type_pairs() {
+            uses.code(&data.1);
+        }
+
         match self {
-            Self::Struct { name, fields } => {
-                let sep = format!("\n{}, ", INDENT);
+            Self::Struct {
+                name, fields, doc, ..
+            } => {
+                let fields_fmt = if fields.is_empty() {
+                    format!("{}{{}}", INDENT)
+                } else {
+                    let sep = format!("\n{}, ", INDENT);
 
-                let fields_fmt = fields
+                    format!(
+                        "{indent}{{ {fields}\n{indent}}}",
+                        indent = INDENT,
+                        fields = fields
+                            .iter()
+                            .map(|field| field.to_elm(1))
+                            .collect::<Vec<_>>()
+                            .join(&sep)
+                    )
+                };
+
+                let field_docs = fields
                     .iter()
-                    .map(|field| field.to_elm(1))
-                    .collect::<Vec<_>>()
-                    .join(&sep);
+                    .map(|field| (field.name.as_str(), &field.doc))
+                    .collect::<Vec<_>>();
 
-                format!(
-                    "\
-type alias {name} =
-{indent}{{ {fields}
-{indent}}}",
-                    name = name,
-                    fields = fields_fmt,
-                    indent = INDENT,
-                )
+                if graphs.is_boxed_struct(name) {
+                    let accessors = fields
+                        .iter()
+                        .map(|field| {
+                            format!(
+                                "\
+{accessor} : {name} -> {elm_type}
+{accessor} ({name} record) =
+{indent}record.{field}",
+                                accessor = elm_accessor(name, &field.name),
+                                name = name,
+                                elm_type = field.data.1,
+                                field = field.name,
+                                indent = INDENT,
+                            )
+                        })
+                        .collect::<Vec<_>>();
+
+                    format!(
+                        "\
+{doc}type {name}
+{indent}= {name}
+{fields}{sep}{accessors}",
+                        doc = elm_doc_comment(doc, &field_docs),
+                        name = name,
+                        indent = INDENT,
+                        fields = indent_lines(&fields_fmt, 1),
+                        sep = elm::DECLARATION_SEPARATOR,
+                        accessors = accessors.join(elm::DECLARATION_SEPARATOR),
+                    )
+                } else {
+                    format!(
+                        "\
+{doc}type alias {name} =
+{fields}",
+                        doc = elm_doc_comment(doc, &field_docs),
+                        name = name,
+                        fields = fields_fmt,
+                    )
+                }
             }
-            Self::Enum { name, variants } => {
+            Self::Enum {
+                name,
+                variants,
+                doc,
+                ..
+            } => {
                 let subtypes = variants
                     .iter()
                     .filter_map(|var| {
@@ -165,11 +874,14 @@ type alias {name} =
                             let subtype = TypeSpec::Struct {
                                 name: format!("{}{}", name, var.name),
                                 fields: fields.clone(),
+                                doc: None,
+                                targets: None,
                             };
                             Some(format!(
-                                "{}\n\n{}\n\n",
-                                subtype.to_elm(),
-                                subtype.to_elm_decoder()
+                                "{}{sep}{}{sep}",
+                                subtype.to_elm(spec, graphs, uses),
+                                subtype.elm_decoder_within(name, spec, graphs, uses),
+                                sep = elm::DECLARATION_SEPARATOR
                             ))
                         } else {
                             None
@@ -180,126 +892,645 @@ type alias {name} =
 
                 let sep = format!("\n{}| ", INDENT);
 
-                let variants_fmt = variants
+                let mut variants_fmt = variants
                     .iter()
                     .map(|var| var.to_elm(name))
-                    .collect::<Vec<_>>()
-                    .join(&sep);
+                    .collect::<Vec<_>>();
+                if let Some((_, elm_type)) = self.unknown_data() {
+                    uses.code(elm_type);
+                    variants_fmt.push(format!("Unknown{} {}", name, elm_type));
+                }
+                let variants_fmt = variants_fmt.join(&sep);
+
+                let variant_docs = variants
+                    .iter()
+                    .map(|var| (var.name.as_str(), &var.doc))
+                    .collect::<Vec<_>>();
+
+                let helpers = match EnumRepr::of(variants) {
+                    EnumRepr::Tagged => "".into(),
+                    repr => format!(
+                        "{}{}",
+                        elm::DECLARATION_SEPARATOR,
+                        elm_enum_value_helpers(name, variants, repr, self.unknown_data().is_some())
+                    ),
+                };
 
                 format!(
                     "\
-{subtypes}type {name}
-{indent}= {variants}",
+{subtypes}{doc}type {name}
+{indent}= {variants}{helpers}",
                     subtypes = subtypes,
+                    doc = elm_doc_comment(doc, &variant_docs),
                     name = name,
                     variants = variants_fmt,
                     indent = INDENT,
+                    helpers = helpers,
                 )
             }
+            Self::Newtype {
+                name, data, doc, ..
+            } => format!(
+                "\
+{doc}type {name}
+{indent}= {name} {inner}
+
+
+unwrap{name} : {name} -> {elm_type}
+unwrap{name} ({name} value) =
+{indent}value",
+                doc = elm_doc_comment(doc, &[]),
+                name = name,
+                inner = elm_type_arg(&data.1),
+                elm_type = data.1,
+                indent = INDENT,
+            ),
+            Self::Alias {
+                name, data, doc, ..
+            } => format!(
+                "\
+{doc}type alias {name} =
+{indent}{elm_type}",
+                doc = elm_doc_comment(doc, &[]),
+                name = name,
+                elm_type = data.1,
+                indent = INDENT,
+            ),
         }
     }
 
-    pub fn to_elm_decoder(&self) -> String {
-        match self {
-            Self::Struct { name, fields } => {
-                let sep = format!("\n{}", INDENT.repeat(2));
+    pub fn to_elm_decoder(&self, spec: &ApiSpec, graphs: &ElmGraphs, uses: &Uses) -> String {
+        self.elm_decoder_within(self.name(), spec, graphs, uses)
+    }
 
-                let field_decoders = fields
-                    .iter()
-                    .map(|field| format!("|> {}", field.to_elm_decoder()))
-                    .collect::<Vec<_>>()
-                    .join(&sep);
+    /// Decoder for this type as part of `parent_type_name`, which differs for the records of enum
+    /// struct variants. References back into the parent's cycle are decoded lazily.
+    fn elm_decoder_within(
+        &self,
+        parent_type_name: &str,
+        spec: &ApiSpec,
+        graphs: &ElmGraphs,
+        uses: &Uses,
+    ) -> String {
+        uses.module("Json.Decode");
 
-                format!(
-                    "\
-decode{name} : Json.Decode.Decoder {name}
-decode{name} =
-    Json.Decode.succeed {name}
-        {fields}",
-                    name = name,
-                    fields = field_decoders
-                )
+        let decoder = match self {
+            Self::Struct { name, fields, .. } => {
+                let constructor = if graphs.is_boxed_struct(name) {
+                    elm_boxed_constructor(name, fields)
+                } else {
+                    name.to_string()
+                };
+
+                match spec.elm.decoder_style {
+                    DecoderStyle::Pipeline => Expr::pipeline(
+                        Expr::line(format!("Json.Decode.succeed {}", constructor)),
+                        fields
+                            .iter()
+                            .map(|field| {
+                                Expr::line(field.to_elm_decoder(
+                                    parent_type_name,
+                                    spec,
+                                    graphs,
+                                    uses,
+                                ))
+                            })
+                            .collect(),
+                    ),
+                    DecoderStyle::MapN => elm_map_n_decoder(
+                        &constructor,
+                        fields,
+                        parent_type_name,
+                        spec,
+                        graphs,
+                        uses,
+                    ),
+                }
             }
-            Self::Enum { name, variants } => {
-                let sep = format!("\n{}, ", INDENT.repeat(2));
+            Self::Enum { name, variants, .. } if EnumRepr::of(variants) != EnumRepr::Tagged => {
+                let repr = EnumRepr::of(variants);
+                let value_str = match repr {
+                    EnumRepr::Int => "String.fromInt value",
+                    _ => "value",
+                };
+
+                let fallback = if self.unknown_data().is_some() {
+                    format!("Json.Decode.succeed (Unknown{} value)", name)
+                } else {
+                    format!(
+                        "Json.Decode.fail (\"Unknown {} variant: \" ++ {})",
+                        name, value_str
+                    )
+                };
+
+                let cases = vec![
+                    ("Just var".into(), Expr::line("Json.Decode.succeed var")),
+                    ("Nothing".into(), Expr::line(fallback)),
+                ];
 
-                let variant_decoders = variants
+                Expr::pipeline(
+                    Expr::line(format!("Json.Decode.{}", repr.elm_type().to_lowercase())),
+                    vec![Expr::call(
+                        "Json.Decode.andThen",
+                        vec![Expr::parens(Expr::lambda(
+                            "value",
+                            Expr::case(
+                                format!("{}From{} value", elm_lower_camel(name), repr.elm_type()),
+                                cases,
+                            ),
+                        ))],
+                    )],
+                )
+            }
+            Self::Enum { name, variants, .. } => {
+                let mut cases = variants
                     .iter()
-                    .map(|var| var.to_elm_decoder(name))
-                    .collect::<Vec<_>>()
-                    .join(&sep);
+                    .map(|var| var.to_elm_decoder(name, spec, graphs, uses))
+                    .collect::<Vec<_>>();
+                cases.push((
+                    "_".into(),
+                    Expr::line(if self.unknown_data().is_some() {
+                        format!("Json.Decode.map Unknown{} Json.Decode.value", name)
+                    } else {
+                        format!("Json.Decode.fail (\"Unknown {} variant: \" ++ tag)", name)
+                    }),
+                ));
 
-                format!(
-                    "\
-decode{name} : Json.Decode.Decoder {name}
-decode{name} =
-    Json.Decode.oneOf
-        [ {variants}
-        ]",
-                    name = name,
-                    variants = variant_decoders
+                Expr::pipeline(
+                    Expr::line("Json.Decode.field \"var\" Json.Decode.string"),
+                    vec![Expr::call(
+                        "Json.Decode.andThen",
+                        vec![Expr::parens(Expr::lambda("tag", Expr::case("tag", cases)))],
+                    )],
                 )
             }
-        }
+            Self::Newtype { name, data, .. } => Expr::line(format!(
+                "Json.Decode.map {} {}",
+                name,
+                elm_decoder(data, parent_type_name, spec, graphs, uses)
+            )),
+            Self::Alias { data, .. } => Expr::line(strip_parens(&elm_decoder(
+                data,
+                parent_type_name,
+                spec,
+                graphs,
+                uses,
+            ))),
+        };
+
+        let name = self.name();
+
+        elm::definition(
+            &format!("decode{name} : Json.Decode.Decoder {name}", name = name),
+            &format!("decode{}", name),
+            &decoder,
+        )
     }
 
-    pub fn to_elm_encoder(&self) -> String {
-        match self {
-            Self::Struct { name, fields } => {
-                let sep = format!("\n{}, ", INDENT.repeat(2));
+    pub fn to_elm_encoder(&self, spec: &ApiSpec, graphs: &ElmGraphs, uses: &Uses) -> String {
+        uses.module("Json.Encode");
 
-                let field_encoders = fields
-                    .iter()
-                    .map(|field| field.to_elm_encoder())
-                    .collect::<Vec<_>>()
-                    .join(&sep);
+        let name = self.name();
 
-                format!(
-                    "\
-encode{name} : {name} -> Json.Encode.Value
-encode{name} record =
-    Json.Encode.object
-        [ {fields}
-        ]",
-                    name = name,
-                    fields = field_encoders
+        let (arg, encoder) = match self {
+            Self::Struct { fields, .. } => (
+                if graphs.is_boxed_struct(name) {
+                    format!("({} record)", name)
+                } else {
+                    "record".to_string()
+                },
+                elm_object(
+                    fields
+                        .iter()
+                        .map(|field| field.to_elm_encoder(spec, uses))
+                        .collect(),
+                ),
+            ),
+            Self::Enum { variants, .. } if EnumRepr::of(variants) != EnumRepr::Tagged => {
+                let repr_type = EnumRepr::of(variants).elm_type();
+
+                (
+                    "var".into(),
+                    Expr::line(format!(
+                        "Json.Encode.{} <| {}To{} var",
+                        repr_type.to_lowercase(),
+                        elm_lower_camel(name),
+                        repr_type
+                    )),
                 )
             }
-            Self::Enum { name, variants } => {
-                let variant_cases = variants
+            Self::Enum { variants, .. } => {
+                let mut cases = variants
                     .iter()
-                    .map(|var| var.to_elm_encoder())
-                    .collect::<Vec<_>>()
-                    .join("");
+                    .map(|var| var.to_elm_encoder(spec, uses))
+                    .collect::<Vec<_>>();
+                if self.unknown_data().is_some() {
+                    cases.push((format!("Unknown{} value", name), Expr::line("value")));
+                }
 
-                format!(
-                    "\
-encode{name} : {name} -> Json.Encode.Value
-encode{name} var =
-    case var of{variants}",
-                    name = name,
-                    variants = variant_cases
-                )
+                ("var".into(), Expr::case("var", cases))
             }
-        }
+            Self::Newtype { data, .. } => (
+                format!("({} value)", name),
+                Expr::line(format!("{} <| value", elm_encoder(data, spec, uses))),
+            ),
+            Self::Alias { data, .. } => ("".into(), Expr::line(elm_encoder(data, spec, uses))),
+        };
+
+        let head = if arg.is_empty() {
+            format!("encode{}", name)
+        } else {
+            format!("encode{} {}", name, arg)
+        };
+
+        elm::definition(
+            &format!("encode{name} : {name} -> Json.Encode.Value", name = name),
+            &head,
+            &encoder,
+        )
     }
 }
 
-fn elm_json_decoder(elm_type: &str) -> String {
-    let supported_types = ["String", "Int", "Float", "Bool", "List"];
+pub(crate) fn doc_lines(doc: &str, prefix: &str) -> String {
+    doc.trim_end()
+        .lines()
+        .map(|line| {
+            let line = line.trim_end();
+            if line.is_empty() {
+                prefix.to_string()
+            } else {
+                format!("{} {}", prefix, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
-    let decoders = elm_type
-        .split(' ')
-        .map(|t| {
-            if supported_types.contains(&t) {
-                format!("Json.Decode.{}", t.to_lowercase())
-            } else if t == "Maybe" {
-                String::from("Json.Decode.nullable")
+pub(crate) fn indent_lines(s: &str, indent: usize) -> String {
+    s.lines()
+        .map(|line| {
+            if line.is_empty() {
+                "".into()
             } else {
-                format!("decode{}", t)
+                format!("{}{}", INDENT.repeat(indent), line)
             }
         })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `{}` around the given lines, or just `{}` if there are none, as rustfmt would write it.
+pub(crate) fn rust_braces(body: &str, indent: usize) -> String {
+    if body.is_empty() {
+        "{}".into()
+    } else {
+        format!("{{\n{}{}}}", body, INDENT.repeat(indent))
+    }
+}
+
+/// Spells a Rust type the way rustfmt does, e.g. `HashMap<String,Vec < u8 >>` as
+/// `HashMap<String, Vec<u8>>`.
+pub(crate) fn rust_type(rust_type: &str) -> String {
+    let mut out = String::new();
+    let mut had_space = false;
+
+    for c in rust_type.trim().chars() {
+        if c.is_whitespace() {
+            had_space = true;
+            continue;
+        }
+
+        let space = match (out.chars().next_back(), c) {
+            (None, _) => false,
+            (Some(prev), _) if "<([&:".contains(prev) => false,
+            (_, c) if ",;>)]:".contains(c) => false,
+            (Some(prev), _) if ",;".contains(prev) => true,
+            (Some(prev), '<') | (Some(prev), '(') if prev.is_alphanumeric() => false,
+            _ => had_space,
+        };
+
+        if space {
+            out.push(' ');
+        }
+        out.push(c);
+        had_space = false;
+    }
+
+    out
+}
+
+pub(crate) fn rust_doc_comment(doc: &Option<String>, indent: usize) -> String {
+    match doc {
+        Some(doc) => format!(
+            "{}\n",
+            doc_lines(doc, &format!("{}///", INDENT.repeat(indent)))
+        ),
+        None => "".into(),
+    }
+}
+
+/// Elm has no doc comments on record fields or custom type variants, so any of those that are
+/// documented are listed underneath the type's own doc comment.
+pub(crate) fn elm_doc_comment(doc: &Option<String>, items: &[(&str, &Option<String>)]) -> String {
+    let items_fmt = items
+        .iter()
+        .filter_map(|(name, doc)| {
+            doc.as_ref().map(|doc| {
+                format!(
+                    "  - `{}`: {}",
+                    name,
+                    doc.trim()
+                        .lines()
+                        .map(str::trim)
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                )
+            })
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    match (doc, items_fmt.is_empty()) {
+        (None, true) => "".into(),
+        (Some(doc), true) => format!("{{-| {}\n-}}\n", doc.trim_end()),
+        (Some(doc), false) => format!("{{-| {}\n\n{}\n\n-}}\n", doc.trim_end(), items_fmt),
+        (None, false) => format!("{{-|\n\n{}\n\n-}}\n", items_fmt),
+    }
+}
+
+/// Smallest of `i32`/`i64` that holds every discriminant.
+fn rust_repr(variants: &[EnumVariant]) -> &'static str {
+    let fits_i32 = variants
+        .iter()
+        .filter_map(|var| var.discriminant)
+        .all(|value| i32::try_from(value).is_ok());
+
+    if fits_i32 {
+        "i32"
+    } else {
+        "i64"
+    }
+}
+
+/// Record decoder built from `Json.Decode.mapN`. `elm/json` stops at `map8`, so any further
+/// fields are applied with `andMap`.
+fn elm_map_n_decoder(
+    constructor: &str,
+    fields: &[StructField],
+    parent_type_name: &str,
+    spec: &ApiSpec,
+    graphs: &ElmGraphs,
+    uses: &Uses,
+) -> Expr {
+    const MAX_MAP_N: usize = 8;
+
+    let (mapped, rest) = fields.split_at(fields.len().min(MAX_MAP_N));
+    let map = match mapped.len() {
+        0 => return Expr::line(format!("Json.Decode.succeed {}", constructor)),
+        1 => "Json.Decode.map".to_string(),
+        n => format!("Json.Decode.map{}", n),
+    };
+
+    Expr::pipeline(
+        Expr::call(
+            format!("{} {}", map, constructor),
+            mapped
+                .iter()
+                .map(|field| {
+                    Expr::line(field.to_elm_field_decoder(parent_type_name, spec, graphs, uses))
+                })
+                .collect(),
+        ),
+        rest.iter()
+            .map(|field| {
+                Expr::line(format!(
+                    "{} {}",
+                    spec.elm.and_map(uses),
+                    field.to_elm_field_decoder(parent_type_name, spec, graphs, uses)
+                ))
+            })
+            .collect(),
+    )
+}
+
+/// `(\a b -> Name { a = a, b = b })`, to build a struct generated as a custom type.
+fn elm_boxed_constructor(name: &str, fields: &[StructField]) -> String {
+    let args = fields
+        .iter()
+        .map(|field| field.name.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let record = fields
+        .iter()
+        .map(|field| format!("{name} = {name}", name = field.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!("(\\{} -> {} {{ {} }})", args, name, record)
+}
+
+/// `folderChildren` for the `children` field of `Folder`.
+fn elm_accessor(type_name: &str, field_name: &str) -> String {
+    let mut chars = field_name.chars();
+    let field = match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    };
+
+    format!("{}{}", elm_lower_camel(type_name), field)
+}
+
+/// `Json.Encode.object [ ... ]`
+fn elm_object(fields: Vec<Expr>) -> Expr {
+    Expr::call("Json.Encode.object", vec![Expr::List(fields)])
+}
+
+pub(crate) fn elm_lower_camel(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// `allX`, `xToString`/`xToInt` and `xFromString`/`xFromInt` for enums encoded as bare values.
+/// The `UnknownX` variant of an `unknown_fallback` enum isn't in `allX`, and `xFromString` only
+/// returns known variants.
+fn elm_enum_value_helpers(
+    name: &str,
+    variants: &[EnumVariant],
+    repr: EnumRepr,
+    unknown_fallback: bool,
+) -> String {
+    let lower_name = elm_lower_camel(name);
+    let repr_type = repr.elm_type();
+
+    let all = Expr::List(variants.iter().map(|var| Expr::line(&var.name)).collect());
+
+    let mut to_value_cases = variants
+        .iter()
+        .map(|var| (var.name.clone(), Expr::line(repr.elm_value(var))))
         .collect::<Vec<_>>();
+    if unknown_fallback {
+        to_value_cases.push((format!("Unknown{} value", name), Expr::line("value")));
+    }
+    let to_value = Expr::case("var", to_value_cases);
+
+    let from_value = Expr::case(
+        "value",
+        variants
+            .iter()
+            .map(|var| {
+                (
+                    repr.elm_value(var),
+                    Expr::line(format!("Just {}", var.name)),
+                )
+            })
+            .chain(std::iter::once(("_".into(), Expr::line("Nothing"))))
+            .collect(),
+    );
+
+    [
+        elm::definition(
+            &format!("all{name} : List {name}", name = name),
+            &format!("all{}", name),
+            &all,
+        ),
+        elm::definition(
+            &format!("{}To{} : {} -> {}", lower_name, repr_type, name, repr_type),
+            &format!("{}To{} var", lower_name, repr_type),
+            &to_value,
+        ),
+        elm::definition(
+            &format!(
+                "{}From{} : {} -> Maybe {}",
+                lower_name, repr_type, repr_type, name
+            ),
+            &format!("{}From{} value", lower_name, repr_type),
+            &from_value,
+        ),
+    ]
+    .join(elm::DECLARATION_SEPARATOR)
+}
+
+/// Wraps a multi-word Elm type in parentheses so it can be used as a type argument.
+pub(crate) fn elm_type_arg(elm_type: &str) -> String {
+    if elm_type.contains(' ') {
+        format!("({})", elm_type)
+    } else {
+        elm_type.to_string()
+    }
+}
+
+/// Splits an Elm type into its name and arguments, e.g. `Dict String (List Int)` into `Dict` and
+/// `["String", "(List Int)"]`.
+pub(crate) fn elm_type_args(elm_type: &str) -> (&str, Vec<&str>) {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = None;
+
+    for (i, c) in elm_type.char_indices() {
+        match c {
+            ' ' if depth == 0 => {
+                if let Some(s) = start.take() {
+                    parts.push(&elm_type[s..i]);
+                }
+                continue;
+            }
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(s) = start {
+        parts.push(&elm_type[s..]);
+    }
+
+    match parts.split_first() {
+        Some((head, args)) => (head, args.to_vec()),
+        None => (elm_type, vec![]),
+    }
+}
+
+/// Whether `t` names Elm's `Dict`, which JSON objects decode into.
+pub(crate) fn is_elm_dict(t: &str) -> bool {
+    t == "Dict" || t == "Dict.Dict"
+}
+
+pub(crate) fn strip_parens(s: &str) -> &str {
+    s.strip_prefix('(')
+        .and_then(|s| s.strip_suffix(')'))
+        .unwrap_or(s)
+}
+
+/// Large integers that are sent as JSON strings when `json.large_int_as_string` is set. With the
+/// Elm type `Int` they're parsed into a number, which is exact only up to 2^53; with `String` they
+/// stay as sent.
+fn is_stringified_int(data: &(String, String), spec: &ApiSpec) -> bool {
+    spec.json.large_int_as_string && primitives::is_large_int(&data.0)
+}
+
+fn rust_field_attr(data: &(String, String), spec: &ApiSpec) -> Option<String> {
+    if is_stringified_int(data, spec) {
+        Some(SERDE_LARGE_INT_ATTR.into())
+    } else {
+        well_known::serde_with(&data.0, &spec.json)
+            .map(|with| format!("#[serde(with = \"{}\")]", with))
+    }
+}
+
+pub(crate) fn elm_decoder(
+    data: &(String, String),
+    parent_type_name: &str,
+    spec: &ApiSpec,
+    graphs: &ElmGraphs,
+    uses: &Uses,
+) -> String {
+    if is_stringified_int(data, spec) && data.1 == "Int" {
+        uses.helper("decodeStringifiedInt");
+        "decodeStringifiedInt".into()
+    } else {
+        elm_json_decoder(&data.1, parent_type_name, spec, graphs, uses)
+    }
+}
+
+pub(crate) fn elm_encoder(data: &(String, String), spec: &ApiSpec, uses: &Uses) -> String {
+    if is_stringified_int(data, spec) && data.1 == "Int" {
+        uses.helper("encodeStringifiedInt");
+        "encodeStringifiedInt".into()
+    } else {
+        elm_json_encoder(&data.1, spec, uses)
+    }
+}
+
+/// A decoder that refers back to `parent_type_name` has to be `lazy`, or Elm rejects the
+/// definition as a cyclic value.
+fn elm_json_decoder(
+    elm_type: &str,
+    parent_type_name: &str,
+    spec: &ApiSpec,
+    graphs: &ElmGraphs,
+    uses: &Uses,
+) -> String {
+    let (head, args) = elm_type_args(elm_type);
+    // JSON objects only have string keys, which `Json.Decode.dict` takes care of.
+    let skip = if is_elm_dict(head) { 1 } else { 0 };
+
+    let decoders = std::iter::once(elm_json_decoder_head(
+        head,
+        parent_type_name,
+        spec,
+        graphs,
+        uses,
+    ))
+    .chain(
+        args.iter()
+            .skip(skip)
+            .map(|arg| elm_json_decoder(strip_parens(arg), parent_type_name, spec, graphs, uses)),
+    )
+    .collect::<Vec<_>>();
 
     if decoders.len() > 1 {
         format!("({})", decoders.join(" "))
@@ -308,85 +1539,175 @@ fn elm_json_decoder(elm_type: &str) -> String {
     }
 }
 
-fn elm_json_encoder(elm_type: &str) -> String {
+/// The decoder for a type name, to be applied to the decoders of its arguments.
+fn elm_json_decoder_head(
+    t: &str,
+    parent_type_name: &str,
+    spec: &ApiSpec,
+    graphs: &ElmGraphs,
+    uses: &Uses,
+) -> String {
     let supported_types = ["String", "Int", "Float", "Bool", "List"];
 
-    elm_type
-        .split(' ')
-        .map(|t| {
-            if supported_types.contains(&t) {
-                format!("Json.Encode.{}", t.to_lowercase())
-            } else if t == "Maybe" {
-                String::from("Json.Encode.Extra.maybe")
-            } else {
-                format!("encode{}", t)
-            }
-        })
-        .collect::<Vec<_>>()
-        .join(" ")
+    if let Some(ext) = spec.external(t) {
+        uses_external(ext, &ext.decoder, uses);
+        ext.decoder.clone()
+    } else if let Some(decoder) = well_known::elm_decoder(t, &spec.json, uses) {
+        decoder.to_string()
+    } else if supported_types.contains(&t) {
+        uses.module("Json.Decode");
+        format!("Json.Decode.{}", t.to_lowercase())
+    } else if t == "Maybe" {
+        uses.module("Json.Decode");
+        String::from("Json.Decode.nullable")
+    } else if is_elm_dict(t) {
+        uses.module("Json.Decode");
+        String::from("Json.Decode.dict")
+    } else if graphs.types.in_same_cycle(parent_type_name, t) {
+        uses.module("Json.Decode");
+        format!("(Json.Decode.lazy (\\_ -> decode{}))", t)
+    } else {
+        format!("decode{}", t)
+    }
+}
+
+fn elm_json_encoder(elm_type: &str, spec: &ApiSpec, uses: &Uses) -> String {
+    let (head, args) = elm_type_args(elm_type);
+    let mut encoders = vec![elm_json_encoder_head(head, spec, uses)];
+    if is_elm_dict(head) {
+        // The keys are strings already.
+        encoders.push("identity".into());
+    }
+    let skip = if is_elm_dict(head) { 1 } else { 0 };
+
+    for arg in args.iter().skip(skip) {
+        let encoder = elm_json_encoder(strip_parens(arg), spec, uses);
+        encoders.push(if !elm_type_args(&encoder).1.is_empty() {
+            format!("({})", encoder)
+        } else {
+            encoder
+        });
+    }
+
+    encoders.join(" ")
+}
+
+/// The encoder for a type name, to be applied to the encoders of its arguments.
+fn elm_json_encoder_head(t: &str, spec: &ApiSpec, uses: &Uses) -> String {
+    let supported_types = ["String", "Int", "Float", "Bool", "List"];
+
+    if let Some(ext) = spec.external(t) {
+        uses_external(ext, &ext.encoder, uses);
+        ext.encoder.clone()
+    } else if let Some(encoder) = well_known::elm_encoder(t, &spec.json, uses) {
+        encoder.to_string()
+    } else if supported_types.contains(&t) {
+        uses.module("Json.Encode");
+        format!("Json.Encode.{}", t.to_lowercase())
+    } else if t == "Maybe" {
+        spec.elm.encode_maybe(uses).to_string()
+    } else if is_elm_dict(t) {
+        uses.module("Json.Encode");
+        String::from("Json.Encode.dict")
+    } else {
+        format!("encode{}", t)
+    }
+}
+
+/// Records the module of an external type, and any others its hand-written `codec` refers to.
+fn uses_external(ext: &ExternalType, codec: &str, uses: &Uses) {
+    if let Some(module) = &ext.elm_module {
+        uses.module(module);
+    }
+    uses.code(codec);
 }
 
 impl StructField {
-    pub fn to_rust(&self, indent: usize, add_pub: bool) -> String {
+    pub fn to_rust(&self, indent: usize, vis: Visibility, spec: &ApiSpec) -> String {
+        let attr = match rust_field_attr(&self.data, spec) {
+            Some(attr) => format!("{}{}\n", INDENT.repeat(indent), attr),
+            None => "".into(),
+        };
+
         format!(
-            "{}{}{}: {},\n",
+            "{}{}{}{}{}: {},\n",
+            rust_doc_comment(&self.doc, indent),
+            attr,
             INDENT.repeat(indent),
-            if add_pub { "pub " } else { "" },
+            vis.to_rust(),
             self.name,
-            self.data.0
+            rust_type(&self.data.0)
         )
     }
 
     pub fn to_elm(&self, _indent: usize) -> String {
-        let elm_type = &self.data.1;
-
-        if elm_type.contains(' ') {
-            format!("{} : ({})", self.name, elm_type)
-        } else {
-            format!("{} : {}", self.name, elm_type)
-        }
+        format!("{} : {}", self.name, self.data.1)
     }
 
-    pub fn to_elm_decoder(&self) -> String {
-        let elm_type = &self.data.1;
-
+    pub fn to_elm_decoder(
+        &self,
+        parent_type_name: &str,
+        spec: &ApiSpec,
+        graphs: &ElmGraphs,
+        uses: &Uses,
+    ) -> String {
         format!(
-            "Json.Decode.Pipeline.required \"{name}\" {decoder}",
+            "{required} \"{name}\" {decoder}",
+            required = spec.elm.required(uses),
             name = self.name,
-            decoder = elm_json_decoder(elm_type)
+            decoder = elm_decoder(&self.data, parent_type_name, spec, graphs, uses)
         )
     }
 
-    pub fn to_elm_encoder(&self) -> String {
-        let elm_type = &self.data.1;
-
+    /// `Json.Decode.field` decoder for use as an argument of `Json.Decode.mapN`.
+    pub fn to_elm_field_decoder(
+        &self,
+        parent_type_name: &str,
+        spec: &ApiSpec,
+        graphs: &ElmGraphs,
+        uses: &Uses,
+    ) -> String {
         format!(
-            "(\"{name}\", {encoder} <| record.{name})",
+            "(Json.Decode.field \"{name}\" {decoder})",
             name = self.name,
-            encoder = elm_json_encoder(elm_type)
+            decoder = elm_decoder(&self.data, parent_type_name, spec, graphs, uses)
         )
     }
+
+    pub fn to_elm_encoder(&self, spec: &ApiSpec, uses: &Uses) -> Expr {
+        Expr::Tuple(vec![
+            Expr::line(format!("\"{}\"", self.name)),
+            Expr::line(format!(
+                "{} <| record.{}",
+                elm_encoder(&self.data, spec, uses),
+                self.name
+            )),
+        ])
+    }
 }
 
 impl EnumVariant {
-    pub fn to_rust(&self, indent: usize) -> String {
+    pub fn to_rust(&self, indent: usize, spec: &ApiSpec) -> String {
+        let discriminant = match self.discriminant {
+            Some(value) => format!(" = {}", value),
+            None => "".into(),
+        };
+
         format!(
-            "{}{}{},\n",
+            "{}{}{}{}{},\n",
+            rust_doc_comment(&self.doc, indent),
             INDENT.repeat(indent),
             self.name,
-            self.data.to_rust(indent)
+            self.data.to_rust(indent, spec),
+            discriminant
         )
     }
 
     pub fn to_elm(&self, parent_type_name: &str) -> String {
         match &self.data {
-            EnumVariantData::None => format!("{}", self.name),
+            EnumVariantData::None => self.name.to_string(),
             EnumVariantData::Single((_, elm_type)) => {
-                if elm_type.contains(' ') {
-                    format!("{} ({})", self.name, elm_type)
-                } else {
-                    format!("{} {}", self.name, elm_type)
-                }
+                format!("{} {}", self.name, elm_type_arg(elm_type))
             }
             EnumVariantData::Struct(_fields) => {
                 format!(
@@ -406,95 +1727,85 @@ impl EnumVariant {
         }
     }
 
-    pub fn to_elm_decoder(&self, parent_type_name: &str) -> String {
-        match &self.data {
-            EnumVariantData::None => format!(
-                "Json.Decode.Extra.when (Json.Decode.field \"var\" Json.Decode.string) ((==) \"{name}\") <|\n\
-                {indent}Json.Decode.succeed {name}",
-                name = self.name,
-                indent = INDENT.repeat(3)
-            ),
-            EnumVariantData::Single((_, elm_type)) => format!(
-                "Json.Decode.Extra.when (Json.Decode.field \"var\" Json.Decode.string) ((==) \"{name}\") <|\n\
-                {indent}Json.Decode.map {name} (Json.Decode.field \"vardata\" <| {decoder})",
-                name = self.name,
-                decoder = elm_json_decoder(elm_type),
-                indent = INDENT.repeat(3)
+    /// The `case` branch that decodes this variant, once its tag has been read.
+    pub fn to_elm_decoder(
+        &self,
+        parent_type_name: &str,
+        spec: &ApiSpec,
+        graphs: &ElmGraphs,
+        uses: &Uses,
+    ) -> (String, Expr) {
+        let decoder = match &self.data {
+            EnumVariantData::None => format!("Json.Decode.succeed {}", self.name),
+            EnumVariantData::Single(data) => format!(
+                "Json.Decode.map {} (Json.Decode.field \"vardata\" <| {})",
+                self.name,
+                elm_decoder(data, parent_type_name, spec, graphs, uses)
             ),
             EnumVariantData::Struct(_) => format!(
-                "Json.Decode.Extra.when (Json.Decode.field \"var\" Json.Decode.string) ((==) \"{name}\") <|\n\
-                {indent}Json.Decode.map {name} (Json.Decode.field \"vardata\" <| decode{parent}{name})",
+                "Json.Decode.map {name} (Json.Decode.field \"vardata\" <| decode{parent}{name})",
                 name = self.name,
-                indent = INDENT.repeat(3),
                 parent = parent_type_name,
-            )
-        }
+            ),
+        };
+
+        (format!("\"{}\"", self.name), Expr::line(decoder))
     }
 
-    pub fn to_elm_encoder(&self) -> String {
+    /// The `case` branch that encodes this variant.
+    pub fn to_elm_encoder(&self, spec: &ApiSpec, uses: &Uses) -> (String, Expr) {
+        let tag = Expr::Tuple(vec![
+            Expr::line("\"var\""),
+            Expr::line(format!("Json.Encode.string \"{}\"", self.name)),
+        ]);
+
         match &self.data {
-            EnumVariantData::None => format!(
-                "\n\
-                {tab}{tab}{name} ->\n\
-                {tab}{tab}{tab}Json.Encode.object\n\
-                {tab}{tab}{tab}{tab}[ ( \"var\", Json.Encode.string \"{name}\" )\n\
-                {tab}{tab}{tab}{tab}]",
-                tab = INDENT,
-                name = self.name
-            ),
-            EnumVariantData::Single((_, elm_type)) => format!(
-                "\n\
-                {tab}{tab}{name} value ->\n\
-                {tab}{tab}{tab}Json.Encode.object\n\
-                {tab}{tab}{tab}{tab}[ ( \"var\", Json.Encode.string \"{name}\" )\n\
-                {tab}{tab}{tab}{tab}, ( \"vardata\", {encoder} <| value )\n\
-                {tab}{tab}{tab}{tab}]",
-                tab = INDENT,
-                name = self.name,
-                encoder = elm_json_encoder(elm_type)
+            EnumVariantData::None => (self.name.clone(), elm_object(vec![tag])),
+            EnumVariantData::Single(data) => (
+                format!("{} value", self.name),
+                elm_object(vec![
+                    tag,
+                    Expr::Tuple(vec![
+                        Expr::line("\"vardata\""),
+                        Expr::line(format!("{} <| value", elm_encoder(data, spec, uses))),
+                    ]),
+                ]),
             ),
-            EnumVariantData::Struct(fields) => format!(
-                "\n\
-                {tab}{tab}{name} record ->\n\
-                {tab}{tab}{tab}Json.Encode.object\n\
-                {tab}{tab}{tab}{tab}[ ( \"var\", Json.Encode.string \"{name}\" )\n\
-                {tab}{tab}{tab}{tab}, ( \"vardata\", Json.Encode.object\n\
-                {tab}{tab}{tab}{tab}{tab}[{encoder}\n\
-                {tab}{tab}{tab}{tab}{tab}] )\n\
-                {tab}{tab}{tab}{tab}]",
-                tab = INDENT,
-                name = self.name,
-                encoder = fields
-                    .iter()
-                    .map(|field| format!(
-                        " ( \"{name}\", {encoder} <| record.{name} )",
-                        name = field.name,
-                        encoder = elm_json_encoder(&field.data.1)
-                    ))
-                    .collect::<Vec<_>>()
-                    .join(&format!("\n{tab}{tab}{tab}{tab}{tab},", tab = INDENT))
+            EnumVariantData::Struct(fields) => (
+                format!("{} record", self.name),
+                elm_object(vec![
+                    tag,
+                    Expr::Tuple(vec![
+                        Expr::line("\"vardata\""),
+                        elm_object(
+                            fields
+                                .iter()
+                                .map(|field| field.to_elm_encoder(spec, uses))
+                                .collect(),
+                        ),
+                    ]),
+                ]),
             ),
         }
     }
 }
 
 impl EnumVariantData {
-    pub fn to_rust(&self, indent: usize) -> String {
+    pub fn to_rust(&self, indent: usize, spec: &ApiSpec) -> String {
         match self {
             Self::None => "".into(),
-            Self::Single((rust_type, _)) => format!("({})", rust_type),
+            Self::Single(data) => match rust_field_attr(data, spec) {
+                Some(attr) => format!("({} {})", attr, rust_type(&data.0)),
+                None => format!("({})", rust_type(&data.0)),
+            },
             Self::Struct(fields) => {
                 let fields_fmt = fields
                     .iter()
-                    .map(|field| field.to_rust(indent + 1, false))
+                    .map(|field| field.to_rust(indent + 1, Visibility::Private, spec))
                     .collect::<Vec<_>>()
                     .join("");
 
-                format!(
-                    " {{\n{fields}{indent}}}",
-                    fields = fields_fmt,
-                    indent = INDENT.repeat(indent)
-                )
+                format!(" {}", rust_braces(&fields_fmt, indent))
             }
         }
     }
@@ -0,0 +1,192 @@
+//! TypeScript declarations for the JSON the generated code reads and writes.
+
+use crate::{elm::Uses, primitives, spec::*, well_known};
+
+/// TypeScript type of the JSON for the Elm type `elm_type`, e.g. `Array<number | null>` for
+/// `List (Maybe Int)`.
+pub fn ts_type(elm_type: &str, spec: &ApiSpec) -> String {
+    let elm_type = elm_type.trim();
+    let (head, rest) = match elm_type.find(' ') {
+        Some(i) => (&elm_type[..i], elm_type[i..].trim()),
+        None => (elm_type, ""),
+    };
+
+    match head {
+        "List" => format!("Array<{}>", ts_type(strip_parens(rest), spec)),
+        "Maybe" => format!("{} | null", ts_type(strip_parens(rest), spec)),
+        "Dict" | "Dict.Dict" => match elm_type_args(elm_type).1.as_slice() {
+            [_, value] => format!("Record<string, {}>", ts_type(strip_parens(value), spec)),
+            _ => "unknown".into(),
+        },
+        "String" | "Url.Url" => "string".into(),
+        "Int" | "Float" => "number".into(),
+        "Bool" => "boolean".into(),
+        "Time.Posix" => match spec.json.datetime {
+            DateTimeFormat::Iso8601 => "string".into(),
+            DateTimeFormat::EpochMillis => "number".into(),
+        },
+        _ if spec.external(head).is_some() => "unknown".into(),
+        _ => head.to_string(),
+    }
+}
+
+/// Like [`ts_type`], but knows that large integers are sent as strings when
+/// `json.large_int_as_string` is set.
+fn ts_field_type(data: &(String, String), spec: &ApiSpec) -> String {
+    if spec.json.large_int_as_string && primitives::is_large_int(&data.0) && data.1 == "Int" {
+        "string".into()
+    } else {
+        ts_type(&data.1, spec)
+    }
+}
+
+fn ts_object(fields: &[StructField], spec: &ApiSpec) -> String {
+    if fields.is_empty() {
+        return "{}".into();
+    }
+
+    let fields = fields
+        .iter()
+        .map(|field| {
+            format!(
+                "{}{}: {};\n",
+                INDENT,
+                field.name,
+                ts_field_type(&field.data, spec)
+            )
+        })
+        .collect::<String>();
+
+    format!("{{\n{}}}", fields)
+}
+
+/// `{ a: number; b: string }`, for objects nested in a union.
+fn ts_inline_object(fields: &[StructField], spec: &ApiSpec) -> String {
+    if fields.is_empty() {
+        return "{}".into();
+    }
+
+    let fields = fields
+        .iter()
+        .map(|field| format!("{}: {}", field.name, ts_field_type(&field.data, spec)))
+        .collect::<Vec<_>>();
+
+    format!("{{ {} }}", fields.join("; "))
+}
+
+fn ts_doc_comment(doc: &Option<String>, indent: usize) -> String {
+    let pad = INDENT.repeat(indent);
+
+    match doc.as_deref().map(str::trim) {
+        Some(doc) if !doc.contains('\n') => format!("{}/** {} */\n", pad, doc),
+        Some(doc) => format!(
+            "{}/**\n{}\n{} */\n",
+            pad,
+            doc_lines(doc, &format!("{} *", pad)),
+            pad
+        ),
+        None => "".into(),
+    }
+}
+
+impl TypeSpec {
+    pub fn to_ts(&self, spec: &ApiSpec) -> String {
+        let doc = ts_doc_comment(self.doc(), 0);
+
+        let decl = match self {
+            TypeSpec::Struct { name, fields, .. } => {
+                format!("export interface {} {}", name, ts_object(fields, spec))
+            }
+            TypeSpec::Newtype { name, data, .. } | TypeSpec::Alias { name, data, .. } => {
+                format!("export type {} = {};", name, ts_field_type(data, spec))
+            }
+            TypeSpec::Enum { name, variants, .. } => {
+                let repr = EnumRepr::of(variants);
+                let options = variants
+                    .iter()
+                    .map(|var| match (repr, &var.data) {
+                        (EnumRepr::Int, _) => var.discriminant.unwrap_or_default().to_string(),
+                        (EnumRepr::String, _) => format!("\"{}\"", var.name),
+                        (EnumRepr::Tagged, EnumVariantData::None) => {
+                            format!("{{ var: \"{}\" }}", var.name)
+                        }
+                        (EnumRepr::Tagged, EnumVariantData::Single(data)) => format!(
+                            "{{ var: \"{}\"; vardata: {} }}",
+                            var.name,
+                            ts_field_type(data, spec)
+                        ),
+                        (EnumRepr::Tagged, EnumVariantData::Struct(fields)) => format!(
+                            "{{ var: \"{}\"; vardata: {} }}",
+                            var.name,
+                            ts_inline_object(fields, spec)
+                        ),
+                    })
+                    .chain(self.unknown_data().map(|_| match repr {
+                        EnumRepr::Tagged => "{ var: string; vardata?: unknown }".into(),
+                        _ => "string".into(),
+                    }))
+                    .collect::<Vec<_>>();
+
+                match repr {
+                    _ if options.is_empty() => format!("export type {} = never;", name),
+                    EnumRepr::Tagged => format!(
+                        "export type {} =\n{};",
+                        name,
+                        options
+                            .iter()
+                            .map(|option| format!("{}| {}", INDENT, option))
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    ),
+                    _ => format!("export type {} = {};", name, options.join(" | ")),
+                }
+            }
+        };
+
+        format!("{}{}", doc, decl)
+    }
+
+    fn doc(&self) -> &Option<String> {
+        match self {
+            TypeSpec::Struct { doc, .. }
+            | TypeSpec::Enum { doc, .. }
+            | TypeSpec::Newtype { doc, .. }
+            | TypeSpec::Alias { doc, .. } => doc,
+        }
+    }
+}
+
+impl ApiSpec {
+    /// TypeScript declarations for the types named in `roots` and the types they depend on, as
+    /// far as they are generated for TypeScript.
+    pub fn ts_types(&self, roots: &[&str]) -> String {
+        let graph = self.type_graph();
+        let keep = graph.dependencies(roots);
+
+        let types = self
+            .types
+            .iter()
+            .filter(|t| keep.contains(&t.name()) && t.has_target(Target::Ts))
+            .collect::<Vec<_>>();
+
+        let uses = Uses::default();
+        for (_, data) in types.iter().flat_map(|t| t.type_pairs()) {
+            uses.code(&data.1);
+        }
+
+        let mut decls = types.iter().map(|t| t.to_ts(self)).collect::<Vec<_>>();
+
+        let builtin_types = well_known::elm_types();
+        for t in &builtin_types {
+            let is_user_type = self
+                .types
+                .iter()
+                .any(|user_type| user_type.name() == t.name());
+            if !is_user_type && uses.has_type(t.name()) {
+                decls.push(t.to_ts(self));
+            }
+        }
+
+        decls.join("\n\n")
+    }
+}
@@ -0,0 +1,308 @@
+//! Upgrades between versions of a struct, e.g. `UserV1` to `UserV2`, for payloads that outlive
+//! the code that wrote them.
+
+use crate::{
+    elm::{self, Expr, Uses},
+    spec::*,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Upgrade {
+    /// Older version, e.g. `UserV1`.
+    pub from: String,
+    /// Newer version, e.g. `UserV2`.
+    pub to: String,
+    /// Fields of `to` that aren't copied from the field of the same name in `from`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldUpgrade>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FieldUpgrade {
+    pub name: String,
+    /// Field of the older version this one is copied from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// `(rust, elm)` expressions for a field the older version doesn't have.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<(String, String)>,
+}
+
+/// Where a field of the newer version comes from.
+pub(crate) enum FieldSource<'a> {
+    /// A field of the older version, with an upgrade if its type is itself an older version.
+    Field(&'a StructField, Option<&'a Upgrade>),
+    Default(&'a (String, String)),
+}
+
+impl Upgrade {
+    /// Name of the Elm function that upgrades a `from`, e.g. `upgradeUserV1`.
+    pub fn elm_name(&self) -> String {
+        format!("upgrade{}", self.from)
+    }
+
+    /// How the upgrade is found in [`ApiSpec::spans`], e.g. `UserV1 -> UserV2`.
+    pub fn path(&self) -> String {
+        format!("{} -> {}", self.from, self.to)
+    }
+
+    /// The fields of `to`, each with where it comes from, or `Err` with the field that can't be
+    /// filled in.
+    pub(crate) fn field_sources<'a>(
+        &'a self,
+        spec: &'a ApiSpec,
+    ) -> Result<Vec<(&'a StructField, FieldSource<'a>)>, String> {
+        let (from_fields, to_fields) =
+            match (spec.struct_fields(&self.from), spec.struct_fields(&self.to)) {
+                (Some(from), Some(to)) => (from, to),
+                _ => {
+                    return Err(format!(
+                        "`{}` and `{}` must both be structs",
+                        self.from, self.to
+                    ))
+                }
+            };
+
+        to_fields
+            .iter()
+            .map(|field| {
+                let mapping = self.fields.iter().find(|m| m.name == field.name);
+                let from_name = match mapping {
+                    Some(FieldUpgrade {
+                        default: Some(default),
+                        from: None,
+                        ..
+                    }) => return Ok((field, FieldSource::Default(default))),
+                    Some(FieldUpgrade {
+                        from: Some(from),
+                        default: None,
+                        ..
+                    }) => from,
+                    Some(_) => {
+                        return Err(format!(
+                            "`{}.{}` needs either a `from` or a `default`",
+                            self.to, field.name
+                        ))
+                    }
+                    None => &field.name,
+                };
+
+                let from_field = match from_fields.iter().find(|f| &f.name == from_name) {
+                    Some(from_field) => from_field,
+                    None => {
+                        return Err(format!(
+                            "`{}.{}` can't be copied from `{}.{}`, which doesn't exist; give it \
+                            a `from` or a `default`",
+                            self.to, field.name, self.from, from_name
+                        ))
+                    }
+                };
+
+                if from_field.data == field.data {
+                    return Ok((field, FieldSource::Field(from_field, None)));
+                }
+
+                let (old_type, new_type) = (&from_field.data.0, &field.data.0);
+                match spec
+                    .upgrades
+                    .iter()
+                    .find(|u| &u.from == old_type && &u.to == new_type)
+                {
+                    Some(upgrade) => Ok((field, FieldSource::Field(from_field, Some(upgrade)))),
+                    None => Err(format!(
+                        "`{}.{}` is a `{}`, but `{}.{}` is a `{}`, with no upgrade between them",
+                        self.to, field.name, new_type, self.from, from_field.name, old_type
+                    )),
+                }
+            })
+            .collect()
+    }
+
+    pub fn to_rust(&self, spec: &ApiSpec) -> Option<String> {
+        let sources = self.field_sources(spec).ok()?;
+
+        let uses_old = sources
+            .iter()
+            .any(|(_, source)| matches!(source, FieldSource::Field(..)));
+        let fields = sources
+            .iter()
+            .map(|(field, source)| {
+                let value = match source {
+                    FieldSource::Field(from, None) => format!("old.{}", from.name),
+                    FieldSource::Field(from, Some(_)) => format!("old.{}.into()", from.name),
+                    FieldSource::Default((rust, _)) => rust.clone(),
+                };
+                format!("{}{}: {},\n", INDENT.repeat(3), field.name, value)
+            })
+            .collect::<String>();
+
+        Some(format!(
+            "\
+impl From<{from}> for {to} {{
+{indent}fn from({old}: {from}) -> Self {{
+{indent}{indent}Self {{
+{fields}{indent}{indent}}}
+{indent}}}
+}}",
+            from = self.from,
+            to = self.to,
+            old = if uses_old { "old" } else { "_old" },
+            fields = fields,
+            indent = INDENT,
+        ))
+    }
+
+    pub fn to_elm(&self, spec: &ApiSpec, uses: &Uses) -> Option<String> {
+        let sources = self.field_sources(spec).ok()?;
+
+        let record = Expr::Record(
+            sources
+                .iter()
+                .map(|(field, source)| {
+                    let value = match source {
+                        FieldSource::Field(from, None) => format!("old.{}", from.name),
+                        FieldSource::Field(from, Some(upgrade)) => {
+                            format!("{} old.{}", upgrade.elm_name(), from.name)
+                        }
+                        FieldSource::Default((_, elm)) => {
+                            uses.code(elm);
+                            elm.clone()
+                        }
+                    };
+                    (field.name.clone(), Expr::line(value))
+                })
+                .collect(),
+        );
+
+        let upgrade = elm::definition(
+            &format!("{} : {} -> {}", self.elm_name(), self.from, self.to),
+            &format!("{} old", self.elm_name()),
+            &record,
+        );
+
+        Some(format!(
+            "{{-| Upgrades `{}` to `{}`.\n-}}\n{}",
+            self.from, self.to, upgrade
+        ))
+    }
+}
+
+impl ApiSpec {
+    pub(crate) fn struct_fields(&self, name: &str) -> Option<&[StructField]> {
+        self.types.iter().find_map(|t| match t {
+            TypeSpec::Struct {
+                name: struct_name,
+                fields,
+                ..
+            } if struct_name == name => Some(fields.as_slice()),
+            _ => None,
+        })
+    }
+
+    /// Whether both versions of `upgrade` are generated for `target`.
+    pub(crate) fn has_upgrade_target(&self, upgrade: &Upgrade, target: Target) -> bool {
+        [&upgrade.from, &upgrade.to].iter().all(|name| {
+            self.types
+                .iter()
+                .any(|t| t.name() == name.as_str() && t.has_target(target))
+        })
+    }
+
+    /// The versions that upgrades lead to from `name`, following them one after another.
+    pub(crate) fn upgraded_versions(&self, name: &str) -> Vec<&str> {
+        let mut versions = vec![];
+        let mut queue = vec![name];
+
+        while let Some(name) = queue.pop() {
+            for upgrade in self.upgrades.iter().filter(|u| u.from == name) {
+                if !versions.contains(&upgrade.to.as_str()) {
+                    versions.push(upgrade.to.as_str());
+                    queue.push(&upgrade.to);
+                }
+            }
+        }
+
+        versions
+    }
+
+    /// Elm upgrade functions, followed for each upgraded version by one decoder that accepts it
+    /// in any version, with the names they're exposed as.
+    pub(crate) fn upgrades_to_elm(&self, uses: &Uses) -> Vec<(String, String)> {
+        let upgrades = self
+            .upgrades
+            .iter()
+            .filter(|upgrade| self.has_upgrade_target(upgrade, Target::Elm))
+            .filter_map(|upgrade| upgrade.to_elm(self, uses).map(|code| (upgrade, code)))
+            .collect::<Vec<_>>();
+
+        let mut definitions = vec![];
+        for (i, (upgrade, code)) in upgrades.iter().enumerate() {
+            definitions.push((upgrade.elm_name(), code.clone()));
+
+            if upgrades[i + 1..].iter().any(|(u, _)| u.to == upgrade.to) {
+                continue;
+            }
+            let older = upgrades
+                .iter()
+                .map(|(u, _)| *u)
+                .filter(|u| u.to == upgrade.to)
+                .collect::<Vec<_>>();
+            definitions.push((
+                format!("decodeAny{}", upgrade.to),
+                self.elm_decode_any(&upgrade.to, &older, uses),
+            ));
+        }
+
+        definitions
+    }
+
+    /// `decodeAny<to>`, which tries `to` itself and then each version upgraded to it.
+    fn elm_decode_any(&self, to: &str, upgrades: &[&Upgrade], uses: &Uses) -> String {
+        uses.module("Json.Decode");
+
+        let decoders = std::iter::once(Expr::line(format!("decode{}", to)))
+            .chain(upgrades.iter().map(|upgrade| {
+                // Older versions that can themselves be upgraded are decoded in any of their
+                // versions.
+                let from_decoder = if self.upgrades.iter().any(|u| u.to == upgrade.from) {
+                    format!("decodeAny{}", upgrade.from)
+                } else {
+                    format!("decode{}", upgrade.from)
+                };
+
+                Expr::line(format!(
+                    "Json.Decode.map {} {}",
+                    upgrade.elm_name(),
+                    from_decoder
+                ))
+            }))
+            .collect();
+
+        format!(
+            "{{-| Decodes any version of `{}`, upgrading older ones.\n-}}\n{}",
+            to,
+            elm::definition(
+                &format!("decodeAny{to} : Json.Decode.Decoder {to}", to = to),
+                &format!("decodeAny{}", to),
+                &Expr::call("Json.Decode.oneOf", vec![Expr::List(decoders)]),
+            )
+        )
+    }
+
+    /// `From` conversions for the upgrades, if there are any.
+    pub fn upgrades_to_rust(&self) -> Option<String> {
+        let upgrades = self
+            .upgrades
+            .iter()
+            .filter(|upgrade| self.has_upgrade_target(upgrade, Target::Rust))
+            .filter_map(|upgrade| upgrade.to_rust(self))
+            .collect::<Vec<_>>();
+
+        if upgrades.is_empty() {
+            None
+        } else {
+            Some(upgrades.join("\n\n"))
+        }
+    }
+}
@@ -0,0 +1,632 @@
+use crate::{
+    dsl::DslError, elm::Uses, endpoint::*, graph::ElmGraphs, primitives, source::Span, spec::*,
+    upgrade::Upgrade, well_known,
+};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Where in the source the problem is, if the spec was parsed from one.
+    pub span: Option<Span>,
+    /// What could be done about it.
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            span: None,
+            hint: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            span: None,
+            hint: None,
+        }
+    }
+
+    pub fn at(mut self, span: Option<Span>) -> Self {
+        self.span = span;
+        self
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "warning: {}", self.message),
+            Severity::Error => write!(f, "error: {}", self.message),
+        }
+    }
+}
+
+impl From<DslError> for Diagnostic {
+    fn from(err: DslError) -> Self {
+        Diagnostic::error(err.message).at(Some(err.span))
+    }
+}
+
+impl ApiSpec {
+    /// Checks the spec for problems that serde can't catch. Code can still be generated when
+    /// only warnings are returned.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+
+        if self.elm.json_only && self.json.datetime == DateTimeFormat::Iso8601 {
+            let uses_datetime = self.types.iter().any(|t| {
+                t.type_pairs()
+                    .iter()
+                    .any(|(_, (_, elm_type))| elm_type.contains("Time.Posix"))
+            });
+
+            if uses_datetime {
+                diagnostics.push(
+                    Diagnostic::warning(
+                        "`elm.json_only` is set, but ISO-8601 date/times need \
+                        rtfeldman/elm-iso8601-date-strings",
+                    )
+                    .with_hint("consider `json.datetime: EpochMillis`"),
+                );
+            }
+        }
+
+        // Recursive structs become custom types in Elm, but a cycle of plain aliases can't be
+        // broken that way.
+        let graphs = self.elm_graphs();
+        for cycle in graphs.aliases.cycles() {
+            let has_struct = self
+                .types
+                .iter()
+                .any(|t| matches!(t, TypeSpec::Struct { .. }) && cycle.contains(&t.name()));
+
+            if !has_struct {
+                let names = cycle
+                    .iter()
+                    .map(|name| format!("`{}`", name))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                diagnostics.push(
+                    Diagnostic::error(format!(
+                        "the aliases {} refer to themselves, which Elm doesn't allow",
+                        names
+                    ))
+                    .at(self.span(cycle[0]))
+                    .with_hint("make one of them a Newtype or Struct"),
+                );
+            }
+        }
+
+        // TypeScript declarations are only generated for the types that go through ports.
+        let port_payloads = self
+            .ports
+            .iter()
+            .map(|port| port.payload.as_str())
+            .collect::<Vec<_>>();
+        let ts_types = graphs.types.dependencies(&port_payloads);
+
+        for t in &self.types {
+            for &target in Target::ALL {
+                if !t.has_target(target) || (target == Target::Ts && !ts_types.contains(&t.name()))
+                {
+                    continue;
+                }
+                for name in graphs.types.references(t.name()) {
+                    let referenced = self.types.iter().find(|t| t.name() == name);
+                    if matches!(referenced, Some(r) if !r.has_target(target)) {
+                        diagnostics.push(
+                            Diagnostic::error(format!(
+                                "`{}` is generated for {}, but refers to `{}`, which isn't",
+                                t.name(),
+                                target.display_name(),
+                                name
+                            ))
+                            .at(self.span(t.name())),
+                        );
+                    }
+                }
+            }
+        }
+
+        diagnostics.extend(self.types.iter().flat_map(|t| validate_type(t, self)));
+        diagnostics.extend(
+            self.endpoints
+                .iter()
+                .flat_map(|e| validate_endpoint(e, self)),
+        );
+
+        for (i, upgrade) in self.upgrades.iter().enumerate() {
+            diagnostics.extend(validate_upgrade(upgrade, self, &graphs));
+
+            if self.upgrades[..i].iter().any(|u| u.from == upgrade.from) {
+                diagnostics.push(
+                    Diagnostic::error(format!("`{}` is upgraded more than once", upgrade.from))
+                        .at(self.span(&upgrade.path())),
+                );
+            }
+
+            // Reported at the first upgrade of each cycle.
+            let newer = self.upgraded_versions(&upgrade.from);
+            let on_cycle = |u: &Upgrade| {
+                newer.contains(&u.from.as_str())
+                    && (u.to == upgrade.from
+                        || self
+                            .upgraded_versions(&u.to)
+                            .contains(&upgrade.from.as_str()))
+            };
+            if on_cycle(upgrade) && !self.upgrades[..i].iter().any(on_cycle) {
+                diagnostics.push(
+                    Diagnostic::error(format!(
+                        "upgrading `{}` leads back to `{}`",
+                        upgrade.from, upgrade.from
+                    ))
+                    .at(self.span(&upgrade.path()))
+                    .with_hint("upgrades must go from older to newer versions"),
+                );
+            }
+        }
+
+        for port in &self.ports {
+            if !self.types.iter().any(|t| t.name() == port.payload) {
+                diagnostics.push(
+                    Diagnostic::error(format!(
+                        "port `{}` carries `{}`, which isn't a type in the spec",
+                        port.name, port.payload
+                    ))
+                    .at(self.span(&port.name)),
+                );
+            }
+        }
+
+        for channel in &self.channels {
+            for name in &[&channel.client, &channel.server] {
+                if !self.types.iter().any(|t| t.name() == name.as_str()) {
+                    diagnostics.push(
+                        Diagnostic::error(format!(
+                            "channel `{}` carries `{}`, which isn't a type in the spec",
+                            channel.name, name
+                        ))
+                        .at(self.span(&channel.name)),
+                    );
+                }
+            }
+        }
+
+        if !self.endpoints.is_empty() {
+            let mut generated = vec!["Route"];
+            if self.rust.server.is_some() {
+                generated.extend(&["Server", "router", "handlers"]);
+            }
+
+            for t in self.types.iter().filter(|t| generated.contains(&t.name())) {
+                diagnostics.push(
+                    Diagnostic::error(format!(
+                        "the type `{}` clashes with the Rust code generated for the endpoints",
+                        t.name()
+                    ))
+                    .at(self.span(t.name())),
+                );
+            }
+        }
+
+        diagnostics
+    }
+}
+
+fn validate_type(t: &TypeSpec, spec: &ApiSpec) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    for (path, (rust_type, elm_type)) in t.type_pairs() {
+        diagnostics.extend(validate_primitive(&path, rust_type, elm_type, spec));
+        diagnostics.extend(validate_elm_codec(&path, elm_type, spec));
+    }
+
+    if let TypeSpec::Enum {
+        name,
+        variants,
+        unknown_fallback,
+        ..
+    } = t
+    {
+        if variants.is_empty() {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "enum `{}` has no variants, which Elm can't decode or encode",
+                    name
+                ))
+                .at(spec.span(name)),
+            );
+        }
+
+        if *unknown_fallback {
+            if EnumRepr::of(variants) == EnumRepr::Int {
+                diagnostics.push(
+                    Diagnostic::error(format!(
+                        "enum `{}` has discriminants, so it can't have an `unknown_fallback`",
+                        name
+                    ))
+                    .at(spec.span(name)),
+                );
+            }
+            let elm_name = format!("Unknown{}", name);
+            for var in variants {
+                if var.name == "Unknown" || var.name == elm_name {
+                    diagnostics.push(
+                        Diagnostic::error(format!(
+                            "variant `{}::{}` clashes with the variant added by `unknown_fallback`",
+                            name, var.name
+                        ))
+                        .at(spec.span(&format!("{}::{}", name, var.name))),
+                    );
+                }
+            }
+        }
+
+        let with_discriminant = variants
+            .iter()
+            .filter(|var| var.discriminant.is_some())
+            .collect::<Vec<_>>();
+
+        if !with_discriminant.is_empty() && with_discriminant.len() != variants.len() {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "enum `{}` must have a discriminant on every variant or on none",
+                    name
+                ))
+                .at(spec.span(name)),
+            );
+        }
+
+        for var in &with_discriminant {
+            if !matches!(var.data, EnumVariantData::None) {
+                diagnostics.push(
+                    Diagnostic::error(format!(
+                        "variant `{}::{}` has a discriminant but also carries data",
+                        name, var.name
+                    ))
+                    .at(spec.span(&format!("{}::{}", name, var.name))),
+                );
+            }
+        }
+
+        for (i, var) in with_discriminant.iter().enumerate() {
+            if let Some(other) = with_discriminant[..i]
+                .iter()
+                .find(|other| other.discriminant == var.discriminant)
+            {
+                diagnostics.push(
+                    Diagnostic::error(format!(
+                        "variants `{}::{}` and `{}::{}` have the same discriminant",
+                        name, other.name, name, var.name
+                    ))
+                    .at(spec.span(&format!("{}::{}", name, var.name))),
+                );
+            }
+        }
+    }
+
+    diagnostics
+}
+
+fn validate_upgrade(upgrade: &Upgrade, spec: &ApiSpec, graphs: &ElmGraphs) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let span = spec.span(&upgrade.path());
+
+    for name in &[&upgrade.from, &upgrade.to] {
+        if graphs.is_boxed_struct(name) {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "`{}` refers to itself, which upgrades don't support",
+                    name
+                ))
+                .at(span),
+            );
+        }
+    }
+
+    if let Err(message) = upgrade.field_sources(spec) {
+        diagnostics.push(
+            Diagnostic::error(format!(
+                "upgrade from `{}` to `{}`: {}",
+                upgrade.from, upgrade.to, message
+            ))
+            .at(span),
+        );
+    } else {
+        let to_fields = spec.struct_fields(&upgrade.to).unwrap_or_default();
+        for field in &upgrade.fields {
+            if !to_fields.iter().any(|f| f.name == field.name) {
+                diagnostics.push(
+                    Diagnostic::error(format!(
+                        "upgrade from `{}` to `{}`: `{}` has no field `{}`",
+                        upgrade.from, upgrade.to, upgrade.to, field.name
+                    ))
+                    .at(span),
+                );
+            }
+        }
+    }
+
+    diagnostics
+}
+
+fn validate_endpoint(endpoint: &Endpoint, spec: &ApiSpec) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let span = spec.span(&endpoint.name);
+    let param_span = |name: &str| spec.span(&format!("{}.{}", endpoint.name, name));
+
+    if spec
+        .endpoints
+        .iter()
+        .filter(|e| e.name == endpoint.name)
+        .count()
+        > 1
+    {
+        diagnostics.push(
+            Diagnostic::error(format!(
+                "there is more than one endpoint named `{}`",
+                endpoint.name
+            ))
+            .at(span),
+        );
+    }
+
+    if !endpoint.path.starts_with('/') {
+        diagnostics.push(
+            Diagnostic::error(format!(
+                "the path of endpoint `{}` must start with `/`",
+                endpoint.name
+            ))
+            .at(span),
+        );
+    }
+
+    let placeholders = endpoint
+        .path_segments()
+        .into_iter()
+        .filter_map(|segment| match segment {
+            PathSegment::Param(name) => Some(name),
+            PathSegment::Literal(_) => None,
+        })
+        .collect::<Vec<_>>();
+
+    for name in &placeholders {
+        if !endpoint.params.iter().any(|param| param.name == *name) {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "the path of endpoint `{}` has a placeholder `{{{}}}`, but no param of that \
+                    name",
+                    endpoint.name, name
+                ))
+                .at(span)
+                .with_hint(format!("add a param named `{}`", name)),
+            );
+        }
+    }
+
+    for param in &endpoint.params {
+        if !placeholders.contains(&param.name.as_str()) {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "`{}.{}` doesn't appear in the path `{}`",
+                    endpoint.name, param.name, endpoint.path
+                ))
+                .at(param_span(&param.name))
+                .with_hint(format!(
+                    "add `{{{}}}` to the path, or make it a query param",
+                    param.name
+                )),
+            );
+        }
+    }
+
+    for param in &endpoint.params {
+        if elm_url_string(&param.data.1, "value", spec, &Uses::default()).is_none() {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "`{}.{}` is an Elm `{}`, which can't be put in a URL path",
+                    endpoint.name, param.name, param.data.1
+                ))
+                .at(param_span(&param.name)),
+            );
+        }
+    }
+
+    for param in &endpoint.query {
+        let elm_type = param.data.1.strip_prefix("Maybe ").unwrap_or(&param.data.1);
+        if elm_url_string(elm_type, "value", spec, &Uses::default()).is_none() {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "`{}.{}` is an Elm `{}`, which can't be put in a query string",
+                    endpoint.name, param.name, param.data.1
+                ))
+                .at(param_span(&param.name)),
+            );
+        }
+    }
+
+    // The Elm client takes `baseUrl`, the params, `body` and `toMsg`, and maps optional query
+    // params with `\value -> ...`. Elm doesn't allow shadowing.
+    let mut elm_names = vec!["baseUrl", "toMsg"];
+    if endpoint.body.is_some() {
+        elm_names.push("body");
+    }
+    if endpoint
+        .query
+        .iter()
+        .any(|param| param.data.1.starts_with("Maybe "))
+    {
+        elm_names.push("value");
+    }
+    for param in endpoint.params.iter().chain(&endpoint.query) {
+        let arg = elm_camel_case(&param.name);
+        if elm_names.contains(&arg.as_str()) {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "`{}.{}` clashes with the `{}` of the generated Elm client",
+                    endpoint.name, param.name, arg
+                ))
+                .at(param_span(&param.name))
+                .with_hint("rename the param"),
+            );
+        }
+    }
+
+    // The server handlers bind `server`, `query` and `body` next to the path params, and the
+    // server methods take the body as `body` after the path and query params.
+    if spec.rust.server.is_some() {
+        let reserved = |param: &StructField, names: &[&str]| names.contains(&param.name.as_str());
+        let clashing = endpoint
+            .params
+            .iter()
+            .filter(|param| reserved(param, &["server", "query", "body"]))
+            .chain(
+                endpoint
+                    .query
+                    .iter()
+                    .filter(|param| endpoint.body.is_some() && reserved(param, &["body"])),
+            );
+
+        for param in clashing {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "`{}.{}` clashes with the `{}` of the generated server code",
+                    endpoint.name, param.name, param.name
+                ))
+                .at(param_span(&param.name))
+                .with_hint("rename the param"),
+            );
+        }
+    }
+
+    for (path, (rust_type, elm_type)) in endpoint.type_pairs() {
+        diagnostics.extend(validate_primitive(&path, rust_type, elm_type, spec));
+        diagnostics.extend(validate_elm_codec(&path, elm_type, spec));
+    }
+
+    diagnostics
+}
+
+/// Errors for Elm types the generated decoders and encoders can't handle.
+fn validate_elm_codec(path: &str, elm_type: &str, spec: &ApiSpec) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let span = spec.span(path);
+
+    if elm_type.contains(',') || elm_type.contains('{') {
+        diagnostics.push(
+            Diagnostic::error(format!(
+                "`{}` is an Elm `{}`, and tuples and records have no generated JSON codecs",
+                path, elm_type
+            ))
+            .at(span)
+            .with_hint("use a type of the spec instead"),
+        );
+        return diagnostics;
+    }
+
+    let mut pending = vec![elm_type];
+    while let Some(elm_type) = pending.pop() {
+        let (head, args) = elm_type_args(strip_parens(elm_type));
+        if is_elm_dict(head) && args.first().map(|key| strip_parens(key)) != Some("String") {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "`{}` is an Elm `{}`, but JSON objects only have `String` keys",
+                    path, elm_type
+                ))
+                .at(span),
+            );
+        }
+        pending.extend(args);
+    }
+
+    diagnostics
+}
+
+fn validate_primitive(
+    path: &str,
+    rust_type: &str,
+    elm_type: &str,
+    spec: &ApiSpec,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let span = spec.span(path);
+
+    let stringified = spec.json.large_int_as_string && primitives::is_large_int(rust_type);
+
+    if let Some(ext) = spec.externals.iter().find(|ext| ext.rust == rust_type) {
+        if ext.elm != elm_type {
+            diagnostics.push(
+                Diagnostic::warning(format!(
+                    "`{}` is the external type `{}`, which is declared as Elm `{}`, not `{}`",
+                    path, rust_type, ext.elm, elm_type
+                ))
+                .at(span),
+            );
+        }
+    } else if let Some(primitive) = primitives::lookup(rust_type) {
+        if primitive.elm != elm_type && !(stringified && elm_type == "String") {
+            diagnostics.push(
+                Diagnostic::warning(format!(
+                    "`{}` is a Rust `{}`, which maps to Elm `{}`, not `{}`",
+                    path, rust_type, primitive.elm, elm_type
+                ))
+                .at(span),
+            );
+        }
+
+        if !primitive.json_safe && !stringified {
+            diagnostics.push(
+                Diagnostic::warning(format!(
+                    "`{}` is a Rust `{}`, which can exceed 2^53 and lose precision in JavaScript",
+                    path, rust_type
+                ))
+                .at(span)
+                .with_hint("consider setting `json.large_int_as_string`"),
+            );
+        }
+    } else if let Some(expected) = well_known::elm_type(rust_type) {
+        if expected != elm_type {
+            diagnostics.push(
+                Diagnostic::warning(format!(
+                    "`{}` is a Rust `{}`, which maps to Elm `{}`, not `{}`",
+                    path, rust_type, expected, elm_type
+                ))
+                .at(span),
+            );
+        }
+    } else if rust_type
+        .split(|c: char| !c.is_alphanumeric())
+        .any(primitives::is_large_int)
+    {
+        // `Option<u64>`, `Vec<i64>`, ...: serde's `with` can only wrap the whole field.
+        diagnostics.push(
+            Diagnostic::warning(format!(
+                "`{}` contains a large integer in `{}`, which can lose precision in JavaScript \
+                and can't be encoded as a string",
+                path, rust_type
+            ))
+            .at(span),
+        );
+    }
+
+    diagnostics
+}
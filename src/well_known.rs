@@ -0,0 +1,153 @@
+//! Types from common crates that have a standard JSON representation, and the Elm code that
+//! reads and writes them.
+
+use crate::{
+    elm::Uses,
+    spec::{DateTimeFormat, JsonOptions, TypeSpec},
+};
+
+const CHRONO_DATETIME: &[&str] = &[
+    "DateTime<Utc>",
+    "chrono::DateTime<Utc>",
+    "chrono::DateTime<chrono::Utc>",
+];
+const TIME_DATETIME: &[&str] = &["OffsetDateTime", "time::OffsetDateTime"];
+const UUID: &[&str] = &["Uuid", "uuid::Uuid"];
+const URL: &[&str] = &["Url", "url::Url"];
+const DECIMAL: &[&str] = &["Decimal", "rust_decimal::Decimal"];
+
+const ELM_URL_HELPERS: &str = "\
+decodeUrl : Json.Decode.Decoder Url.Url
+decodeUrl =
+    Json.Decode.string
+        |> Json.Decode.andThen
+            (\\value ->
+                case Url.fromString value of
+                    Just url ->
+                        Json.Decode.succeed url
+
+                    Nothing ->
+                        Json.Decode.fail (\"Invalid URL: \" ++ value)
+            )
+
+
+encodeUrl : Url.Url -> Json.Encode.Value
+encodeUrl =
+    Json.Encode.string << Url.toString";
+
+fn normalize(rust_type: &str) -> String {
+    rust_type.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// Splits `Option<T>` into `(T, true)`.
+fn strip_option(rust_type: &str) -> (&str, bool) {
+    let inner = rust_type
+        .strip_prefix("Option<")
+        .or_else(|| rust_type.strip_prefix("std::option::Option<"))
+        .and_then(|s| s.strip_suffix('>'));
+
+    match inner {
+        Some(inner) => (inner, true),
+        None => (rust_type, false),
+    }
+}
+
+/// The Elm type a well-known Rust type maps to.
+pub fn elm_type(rust_type: &str) -> Option<&'static str> {
+    let rust_type = normalize(rust_type);
+    let rust_type = rust_type.as_str();
+
+    if CHRONO_DATETIME.contains(&rust_type) || TIME_DATETIME.contains(&rust_type) {
+        Some("Time.Posix")
+    } else if UUID.contains(&rust_type) {
+        Some("Uuid")
+    } else if URL.contains(&rust_type) {
+        Some("Url.Url")
+    } else if DECIMAL.contains(&rust_type) {
+        // `rust_decimal` serializes to a string, which keeps every digit.
+        Some("String")
+    } else {
+        None
+    }
+}
+
+/// Module for `#[serde(with = "...")]` when serde's default representation of a well-known type
+/// isn't the one the Elm side expects.
+pub fn serde_with(rust_type: &str, json: &JsonOptions) -> Option<&'static str> {
+    let rust_type = normalize(rust_type);
+    let (inner, optional) = strip_option(&rust_type);
+
+    let with = if CHRONO_DATETIME.contains(&inner) {
+        match (json.datetime, optional) {
+            (DateTimeFormat::Iso8601, _) => return None,
+            (DateTimeFormat::EpochMillis, false) => "chrono::serde::ts_milliseconds",
+            (DateTimeFormat::EpochMillis, true) => "chrono::serde::ts_milliseconds_option",
+        }
+    } else if TIME_DATETIME.contains(&inner) {
+        match (json.datetime, optional) {
+            (DateTimeFormat::Iso8601, false) => "time::serde::rfc3339",
+            (DateTimeFormat::Iso8601, true) => "time::serde::rfc3339::option",
+            (DateTimeFormat::EpochMillis, false) => "time::serde::timestamp::milliseconds",
+            (DateTimeFormat::EpochMillis, true) => "time::serde::timestamp::milliseconds::option",
+        }
+    } else {
+        return None;
+    };
+
+    Some(with)
+}
+
+/// Decoder for a single well-known Elm type.
+pub fn elm_decoder(elm_type: &str, json: &JsonOptions, uses: &Uses) -> Option<&'static str> {
+    let decoder = match (elm_type, json.datetime) {
+        ("Time.Posix", DateTimeFormat::Iso8601) => "Iso8601.decoder",
+        ("Time.Posix", DateTimeFormat::EpochMillis) => {
+            "(Json.Decode.map Time.millisToPosix Json.Decode.int)"
+        }
+        ("Url.Url", _) => {
+            uses.helper("decodeUrl");
+            "decodeUrl"
+        }
+        _ => return None,
+    };
+
+    uses.code(decoder);
+    Some(decoder)
+}
+
+/// Encoder for a single well-known Elm type.
+pub fn elm_encoder(elm_type: &str, json: &JsonOptions, uses: &Uses) -> Option<&'static str> {
+    let encoder = match (elm_type, json.datetime) {
+        ("Time.Posix", DateTimeFormat::Iso8601) => "Iso8601.encode",
+        ("Time.Posix", DateTimeFormat::EpochMillis) => "(Json.Encode.int << Time.posixToMillis)",
+        ("Url.Url", _) => {
+            uses.helper("encodeUrl");
+            "encodeUrl"
+        }
+        _ => return None,
+    };
+
+    uses.code(encoder);
+    Some(encoder)
+}
+
+/// Types generated into the Elm module, and exposed, when the generated code refers to them.
+pub fn elm_types() -> Vec<TypeSpec> {
+    vec![TypeSpec::Newtype {
+        name: "Uuid".into(),
+        data: ("uuid::Uuid".into(), "String".into()),
+        doc: Some("A UUID, kept as its string representation.".into()),
+        targets: None,
+    }]
+}
+
+/// Private helper functions generated into the Elm module when the generated code calls them, by
+/// name, with the modules they use.
+pub fn elm_helpers() -> Vec<(&'static str, &'static str, &'static [&'static str])> {
+    const MODULES: &[&str] = &["Json.Decode", "Json.Encode", "Url"];
+
+    vec![
+        ("decodeUrl", ELM_URL_HELPERS, MODULES),
+        ("encodeUrl", ELM_URL_HELPERS, MODULES),
+    ]
+}